@@ -1,8 +1,10 @@
 mod control;
 mod port;
+mod snapshot;
 mod status;
 mod utils;
 pub use control::*;
 pub use port::abi::*;
+pub use snapshot::*;
 pub use status::*;
 pub use utils::*;
@@ -2,7 +2,7 @@ use std::cell::RefCell;
 use std::fmt::Debug;
 use std::rc::Rc;
 
-use super::Shared;
+use super::{Shared, Snapshot};
 
 pub trait ControlBuilder {
     fn build(self) -> ControlRef;
@@ -13,10 +13,10 @@ impl<T: 'static + Control> ControlShared<T> {
     pub fn new(control: T) -> Self {
         Self(control.into())
     }
-    pub fn borrow(&self) -> std::cell::Ref<T> {
+    pub fn borrow(&self) -> std::cell::Ref<'_, T> {
         self.0.borrow()
     }
-    pub fn borrow_mut(&self) -> std::cell::RefMut<T> {
+    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, T> {
         self.0.borrow_mut()
     }
     pub fn into_shared(self) -> Shared<T> {
@@ -74,6 +74,15 @@ impl ControlRef {
     pub fn inner_signal(&self) -> Vec<(&'static str, u32)> {
         self.0.borrow().inner_signal()
     }
+    pub fn snapshot(&self) -> Snapshot {
+        self.0.borrow().snapshot()
+    }
+    pub fn restore(&self, snapshot: &Snapshot) {
+        self.0.borrow_mut().restore(snapshot)
+    }
+    pub fn set(&self, name: &str, value: u32) -> bool {
+        self.0.borrow_mut().set(name, value)
+    }
 }
 
 impl Clone for ControlRef {
@@ -89,16 +98,27 @@ pub trait Control: Debug {
     fn falling_edge(&mut self) {
         self.rasing_edge();
     }
+    // named signals for the tui and tracers, none unless a component lists them
     fn input(&self) -> Vec<(&'static str, u32)> {
-        unimplemented!("input not implemented")
+        vec![]
     }
     fn output(&self) -> Vec<(&'static str, u32)> {
-        unimplemented!("output not implemented")
+        vec![]
     }
     fn inout(&self) -> Vec<(&'static str, u32, u32)> {
-        unimplemented!("inout not implemented")
+        vec![]
     }
     fn inner_signal(&self) -> Vec<(&'static str, u32)> {
-        unimplemented!("inner_signal not implemented")
+        vec![]
+    }
+    // capture the state kept across cycles
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::None
+    }
+    // restore the state captured by snapshot
+    fn restore(&mut self, _snapshot: &Snapshot) {}
+    // overwrite a latch between cycles, by the name of the output or the field in `inout`
+    fn set(&mut self, _name: &str, _value: u32) -> bool {
+        false
     }
 }
@@ -5,6 +5,8 @@ use std::rc::Rc;
 use super::super::utils::Shared;
 pub trait IndexPort: Debug {
     fn read(&self, index: usize) -> u32;
+    // change the state behind the port directly, e.g. from a debugger
+    fn write(&mut self, index: usize, data: u32);
 }
 #[derive(Debug)]
 pub struct IndexPortShared<T: 'static + IndexPort>(Shared<T>);
@@ -12,10 +14,10 @@ impl<T: IndexPort> IndexPortShared<T> {
     pub fn new(component: T) -> Self {
         Self(component.into())
     }
-    pub fn borrow(&self) -> Ref<T> {
+    pub fn borrow(&self) -> Ref<'_, T> {
         self.0.borrow()
     }
-    pub fn borrow_mut(&self) -> RefMut<T> {
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
         self.0.borrow_mut()
     }
     pub fn shared(&self) -> Shared<T> {
@@ -42,6 +44,9 @@ impl IndexPortRef {
     pub fn read(&self, index: usize) -> u32 {
         self.0.borrow().read(index)
     }
+    pub fn write(&self, index: usize, data: u32) {
+        self.0.borrow_mut().write(index, data)
+    }
 }
 impl<T: 'static + IndexPort> From<Shared<T>> for IndexPortRef {
     fn from(shared: Shared<T>) -> Self {
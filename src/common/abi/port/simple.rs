@@ -20,10 +20,10 @@ impl<T: Port> PortShared<T> {
     pub fn new(component: T) -> Self {
         Self(component.into())
     }
-    pub fn borrow(&self) -> Ref<T> {
+    pub fn borrow(&self) -> Ref<'_, T> {
         self.0.borrow()
     }
-    pub fn borrow_mut(&self) -> RefMut<T> {
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
         self.0.borrow_mut()
     }
     pub fn into_shared(self) -> Shared<T> {
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// state of a component kept across cycles, restorable with `Control::restore`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Snapshot {
+    #[default]
+    None,
+    Word(u32),
+    Words(Vec<u32>),
+    Counter(usize),
+    Counters(BTreeMap<String, usize>),
+    Pages(BTreeMap<usize, Vec<u8>>),
+    Asm {
+        // (fetch id, address) of the instruction in each stage
+        stages: Vec<Option<(usize, u32)>>,
+        set: Vec<u32>,
+        retired: usize,
+        next_id: usize,
+        cycle: usize,
+    },
+    Group(Vec<Snapshot>),
+}
+impl Snapshot {
+    pub fn group(&self) -> &[Snapshot] {
+        match self {
+            Snapshot::Group(group) => group,
+            _ => panic!("snapshot: expected group, found {:?}", self),
+        }
+    }
+}
@@ -4,10 +4,10 @@ use std::{cell::RefCell, rc::Rc};
 pub struct Shared<T: 'static>(Rc<RefCell<T>>);
 
 impl<T: 'static> Shared<T> {
-    pub fn borrow(&self) -> std::cell::Ref<T> {
+    pub fn borrow(&self) -> std::cell::Ref<'_, T> {
         self.0.borrow()
     }
-    pub fn borrow_mut(&self) -> std::cell::RefMut<T> {
+    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, T> {
         self.0.borrow_mut()
     }
     pub fn into_inner(self) -> Rc<RefCell<T>> {
@@ -1,6 +1,7 @@
 use crate::common::abi::*;
 use crate::common::build::*;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Range;
 use std::{cell::RefCell, collections::BTreeSet, fmt::Debug, rc::Rc};
 mod reg;
 // pub use reg::Alloc;
@@ -17,25 +18,59 @@ pub enum Stage {
     Memory,
     WriteBack,
 }
-impl ToString for Stage {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Stage::None => "".to_string(),
-            Stage::Fetch => "Fetch".to_string(),
-            Stage::Decode => "Decode".to_string(),
-            Stage::Execute => "Execute".to_string(),
-            Stage::Memory => "Memory".to_string(),
-            Stage::WriteBack => "WriteBack".to_string(),
+            Stage::None => write!(f, ""),
+            Stage::Fetch => write!(f, "Fetch"),
+            Stage::Decode => write!(f, "Decode"),
+            Stage::Execute => write!(f, "Execute"),
+            Stage::Memory => write!(f, "Memory"),
+            Stage::WriteBack => write!(f, "WriteBack"),
         }
     }
 }
+// the stage at `index` in the pipeline, from fetch to write back
+impl From<usize> for Stage {
+    fn from(index: usize) -> Self {
+        match index {
+            0 => Stage::Fetch,
+            1 => Stage::Decode,
+            2 => Stage::Execute,
+            3 => Stage::Memory,
+            4 => Stage::WriteBack,
+            _ => Stage::None,
+        }
+    }
+}
+// cycles of stage occupancy kept for the pipeline diagram
+pub const TRACE_LIMIT: usize = 1 << 16;
+
+// an instruction in flight, `id` numbers the fetches so that
+// the same address fetched twice stays apart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub id: usize,
+    pub pc: u32,
+}
 pub struct Inst {
+    pub pc: u32,
     pub asm: String,
     pub stage: Stage,
 }
 
 trait AsmPort: Control + Debug {
-    fn read(&self, len_hint: usize) -> Vec<Inst>;
+    // about `len_hint` rows around the instructions in flight, moved by `shift` rows
+    fn read(&self, len_hint: usize, shift: isize) -> Vec<Inst>;
+    // address of the instruction in each stage, from fetch to write back
+    fn stages(&self) -> Vec<Option<u32>>;
+    // number of instructions that have left the write back stage
+    fn retired(&self) -> usize;
+    // stage occupancy of the cycles in `cycles` that are still recorded, the current one
+    // included, with the first cycle returned
+    fn trace(&self, cycles: Range<usize>) -> (usize, Vec<Vec<Option<Slot>>>);
+    // objdump line of the instruction at `pc`
+    fn disasm(&self, pc: u32) -> Option<String>;
 }
 #[derive(Default)]
 struct AsmPortShared<T: 'static + AsmPort>(Shared<T>);
@@ -53,10 +88,22 @@ impl<T: 'static + AsmPort> Clone for AsmPortShared<T> {
     }
 }
 #[derive(Debug)]
-pub struct AsmPortRef(Rc<RefCell<(dyn AsmPort)>>);
+pub struct AsmPortRef(Rc<RefCell<dyn AsmPort>>);
 impl AsmPortRef {
-    pub fn read(&self, len_hint: usize) -> Vec<Inst> {
-        self.0.borrow().read(len_hint)
+    pub fn read(&self, len_hint: usize, shift: isize) -> Vec<Inst> {
+        self.0.borrow().read(len_hint, shift)
+    }
+    pub fn stages(&self) -> Vec<Option<u32>> {
+        self.0.borrow().stages()
+    }
+    pub fn retired(&self) -> usize {
+        self.0.borrow().retired()
+    }
+    pub fn trace(&self, cycles: Range<usize>) -> (usize, Vec<Vec<Option<Slot>>>) {
+        self.0.borrow().trace(cycles)
+    }
+    pub fn disasm(&self, pc: u32) -> Option<String> {
+        self.0.borrow().disasm(pc)
     }
     pub fn rasing_edge(&self) {
         self.0.borrow_mut().rasing_edge()
@@ -64,6 +111,12 @@ impl AsmPortRef {
     pub fn falling_edge(&self) {
         self.0.borrow_mut().falling_edge()
     }
+    pub fn snapshot(&self) -> Snapshot {
+        self.0.borrow().snapshot()
+    }
+    pub fn restore(&self, snapshot: &Snapshot) {
+        self.0.borrow_mut().restore(snapshot)
+    }
 }
 impl<T: 'static + AsmPort> From<Shared<T>> for AsmPortRef {
     fn from(asm: Shared<T>) -> Self {
@@ -209,8 +262,13 @@ pub struct Asm {
     pub ex_clr: PortRef,
     pub ex_clr_cache: u32,
     pub set: BTreeSet<u32>,
-    pub stages: Vec<Option<u32>>,
-    pub entry: usize,
+    pub stages: Vec<Option<Slot>>,
+    pub retired: usize,
+    pub next_id: usize,
+    // falling edges seen, the cycle `stages` belongs to
+    pub cycle: usize,
+    // occupancy of the cycles before `cycle`, at most TRACE_LIMIT of them
+    pub trace: VecDeque<Vec<Option<Slot>>>,
     pub mem: AsmMemory,
 }
 impl Asm {
@@ -224,7 +282,10 @@ impl Asm {
         mem: AsmMemory,
     ) -> Self {
         let mut stages = vec![None; 5];
-        stages[0] = Some(entry as u32);
+        stages[0] = Some(Slot {
+            id: 0,
+            pc: entry as u32,
+        });
         Self {
             addr,
             addr_cache: 0,
@@ -238,7 +299,10 @@ impl Asm {
             ex_clr_cache: 0,
             set: BTreeSet::from([entry as u32]),
             stages,
-            entry,
+            retired: 0,
+            next_id: 1,
+            cycle: 0,
+            trace: VecDeque::new(),
             mem,
         }
     }
@@ -252,14 +316,24 @@ impl Control for Asm {
         self.ex_clr_cache = self.ex_clr.read();
     }
     fn falling_edge(&mut self) {
+        if self.trace.len() == TRACE_LIMIT {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(self.stages.clone());
+        self.cycle += 1;
         if let Some(Some(stage)) = self.stages.last() {
-            self.set.remove(stage);
+            self.set.remove(&stage.pc);
+            self.retired += 1;
         }
         self.stages.rotate_right(1);
         if self.if_en_cache != 1 {
             self.stages[0] = self.stages[1];
         } else {
-            self.stages[0] = Some(self.addr_cache);
+            self.stages[0] = Some(Slot {
+                id: self.next_id,
+                pc: self.addr_cache,
+            });
+            self.next_id += 1;
             self.set.insert(self.addr_cache);
         }
         if self.id_clr_cache != 0 {
@@ -271,10 +345,50 @@ impl Control for Asm {
             self.stages[2] = None;
         }
     }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Asm {
+            stages: self
+                .stages
+                .iter()
+                .map(|slot| slot.map(|slot| (slot.id, slot.pc)))
+                .collect(),
+            set: self.set.iter().copied().collect(),
+            retired: self.retired,
+            next_id: self.next_id,
+            cycle: self.cycle,
+        }
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let Snapshot::Asm {
+            stages,
+            set,
+            retired,
+            next_id,
+            cycle,
+        } = snapshot
+        else {
+            panic!("Asm: invalid snapshot {:?}", snapshot);
+        };
+        self.stages = stages
+            .iter()
+            .map(|slot| slot.map(|(id, pc)| Slot { id, pc }))
+            .collect();
+        self.set = set.iter().copied().collect();
+        self.retired = *retired;
+        self.next_id = *next_id;
+        // the recorded cycles from `cycle` on belong to the abandoned future
+        let first = self.cycle - self.trace.len();
+        self.trace.truncate(cycle.saturating_sub(first));
+        self.cycle = *cycle;
+        if self.cycle - self.trace.len() != first {
+            // restored before the oldest recorded cycle
+            self.trace.clear();
+        }
+    }
 }
 impl AsmPort for Asm {
-    fn read(&self, mut len_hint: usize) -> Vec<Inst> {
-        if self.set.is_empty() {
+    fn read(&self, mut len_hint: usize, shift: isize) -> Vec<Inst> {
+        if self.set.is_empty() || self.mem.data.is_empty() {
             return vec![];
         }
         len_hint *= 4;
@@ -285,6 +399,9 @@ impl AsmPort for Asm {
             start = start.saturating_sub(more / 2);
             end = start + len_hint;
         }
+        let shift = (shift * 4).max(-(start as isize));
+        start = start.saturating_add_signed(shift);
+        end = end.saturating_add_signed(shift);
         let min = *self.mem.data.first_key_value().unwrap().0;
         let max = *self.mem.data.last_key_value().unwrap().0;
         if start < min {
@@ -299,7 +416,8 @@ impl AsmPort for Asm {
             .mem
             .data
             .range(start..=end)
-            .map(|(_, asm)| Inst {
+            .map(|(&pc, asm)| Inst {
+                pc: pc as u32,
                 asm: asm.clone(),
                 stage: Stage::None,
             })
@@ -314,7 +432,7 @@ impl AsmPort for Asm {
                 Stage::WriteBack,
             ])
             .for_each(|(stage, stage_name)| {
-                if let Some(addr) = stage {
+                if let Some(Slot { pc: addr, .. }) = stage {
                     if *addr >= start as u32 && *addr < end as u32 {
                         res[((*addr) as usize - start) / 4].stage = stage_name;
                     }
@@ -322,6 +440,30 @@ impl AsmPort for Asm {
             });
         res
     }
+    fn stages(&self) -> Vec<Option<u32>> {
+        self.stages
+            .iter()
+            .map(|slot| slot.map(|slot| slot.pc))
+            .collect()
+    }
+    fn retired(&self) -> usize {
+        self.retired
+    }
+    fn trace(&self, cycles: Range<usize>) -> (usize, Vec<Vec<Option<Slot>>>) {
+        let first = self.cycle - self.trace.len();
+        let start = cycles.start.max(first);
+        let end = cycles.end.min(self.cycle + 1);
+        let trace = (start..end)
+            .map(|cycle| match self.trace.get(cycle - first) {
+                Some(stages) => stages.clone(),
+                None => self.stages.clone(),
+            })
+            .collect();
+        (start, trace)
+    }
+    fn disasm(&self, pc: u32) -> Option<String> {
+        self.mem.data.get(&(pc as usize)).cloned()
+    }
 }
 
 pub mod build {
@@ -332,7 +474,10 @@ pub mod build {
     pub use super::AsmBuilder;
     pub use super::AsmMemBuilder;
     pub use super::AsmPortRef;
+    pub use super::Inst;
+    pub use super::Slot;
     pub use super::Stage;
+    pub use super::TRACE_LIMIT;
 
     pub use super::Connect as AsmConnect;
 }
@@ -8,6 +8,15 @@ impl Lat {
     pub fn new(value: u32) -> Self {
         Self { data: value }
     }
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::Word(self.data)
+    }
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        match snapshot {
+            Snapshot::Word(data) => self.data = *data,
+            _ => panic!("Lat: invalid snapshot {:?}", snapshot),
+        }
+    }
 }
 impl Port for Lat {
     fn read(&self) -> u32 {
@@ -14,6 +14,11 @@ pub enum Connect {
     Data = 1,
     WriteEn = 2,
     ReadEn = 3,
+    // funct3 of the load or store, a word when left unconnected
+    Width = 4,
+}
+pub enum IndexAlloc {
+    Mem,
 }
 
 pub struct MemBuilder {
@@ -31,6 +36,9 @@ impl MemBuilder {
             reader: PortShared::new(MemReader::new(mem.into())),
         }
     }
+    pub fn load(&mut self, addr: usize, data: &[u8]) {
+        self.writer.mem.borrow_mut().load(addr, data);
+    }
 }
 impl Default for MemBuilder {
     fn default() -> Self {
@@ -55,16 +63,33 @@ impl PortBuilder for MemBuilder {
             Self::Connect::Data => self.writer.write_data = pin,
             Self::Connect::WriteEn => self.writer.write_en = pin,
             Self::Connect::ReadEn => self.reader.borrow_mut().read_en = pin,
+            Self::Connect::Width => {
+                self.writer.width = Some(pin.clone());
+                self.reader.borrow_mut().width = Some(pin);
+            }
         }
     }
     fn alloc(&mut self, _: Self::Alloc) -> PortRef {
         PortRef::from(self.reader.clone())
     }
 }
+impl IndexPortBuilder for MemBuilder {
+    type IndexAlloc = IndexAlloc;
+    type IndexConnect = ();
+    fn index_connect(&mut self, _pin: IndexPortRef, _id: Self::IndexConnect) {
+        unreachable!("MemBuilder does not have any index input")
+    }
+    fn index_alloc(&mut self, _id: Self::IndexAlloc) -> IndexPortRef {
+        self.writer.mem.shared().into()
+    }
+}
 
 pub mod build {
+    pub use super::mem::Mem;
+    pub use super::mem::PAGE_SIZE as MEM_PAGE_SIZE;
     pub use super::Alloc as MemAlloc;
     pub use super::Connect as MemConnect;
+    pub use super::IndexAlloc as MemIndexAlloc;
     pub use super::MemBuilder;
 }
 #[cfg(test)]
@@ -1,7 +1,7 @@
 use crate::common::abi::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-const PAGE_SIZE: usize = 0x1000;
+pub const PAGE_SIZE: usize = 0x1000;
 
 #[derive(Debug)]
 pub struct Mem {
@@ -30,29 +30,53 @@ impl Mem {
     }
 }
 impl Mem {
+    // copy bytes in at any address, e.g. the data segments of a program
+    pub fn load(&mut self, addr: usize, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            let page = self
+                .data
+                .entry((addr + i) / PAGE_SIZE)
+                .or_insert([0; PAGE_SIZE]);
+            page[(addr + i) % PAGE_SIZE] = *byte;
+        }
+    }
+    // a word at any address, one that crosses into the next page too
     pub fn write(&mut self, addr: usize, data: u32) {
-        let page = addr / PAGE_SIZE;
-        let offset = addr % PAGE_SIZE;
-        let page = self.data.entry(page).or_insert_with(|| [0; PAGE_SIZE]);
-        page[offset] = (data & 0xff) as u8;
-        page[offset + 1] = ((data >> 8) & 0xff) as u8;
-        page[offset + 2] = ((data >> 16) & 0xff) as u8;
-        page[offset + 3] = ((data >> 24) & 0xff) as u8;
+        self.load(addr, &data.to_le_bytes());
+    }
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::Pages(
+            self.data
+                .iter()
+                .map(|(page, data)| (*page, data.to_vec()))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let Snapshot::Pages(pages) = snapshot else {
+            panic!("Mem: invalid snapshot {:?}", snapshot);
+        };
+        self.data = pages
+            .iter()
+            .map(|(page, data)| {
+                let mut buf = [0; PAGE_SIZE];
+                buf.copy_from_slice(data);
+                (*page, buf)
+            })
+            .collect();
     }
 }
 impl IndexPort for Mem {
     fn read(&self, addr: usize) -> u32 {
-        let page = addr / PAGE_SIZE;
-        let offset = addr % PAGE_SIZE;
-        if let Some(page) = self.data.get(&page) {
-            u32::from_ne_bytes([
-                page[offset],
-                page[offset + 1],
-                page[offset + 2],
-                page[offset + 3],
-            ])
-        } else {
-            0
-        }
+        let byte = |i: usize| {
+            let addr = addr + i;
+            self.data
+                .get(&(addr / PAGE_SIZE))
+                .map_or(0, |page| page[addr % PAGE_SIZE])
+        };
+        u32::from_le_bytes([byte(0), byte(1), byte(2), byte(3)])
+    }
+    fn write(&mut self, addr: usize, data: u32) {
+        Mem::write(self, addr, data)
     }
 }
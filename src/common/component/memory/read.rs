@@ -4,6 +4,7 @@ use crate::common::build::*;
 pub struct MemReader {
     pub read_en: PortRef,
     pub addr: PortRef,
+    pub width: Option<PortRef>,
     mem: IndexPortRef,
 }
 impl MemReader {
@@ -11,16 +12,24 @@ impl MemReader {
         Self {
             read_en: bomb().into(),
             addr: bomb().into(),
+            width: None,
             mem,
         }
     }
 }
 impl Port for MemReader {
     fn read(&self) -> u32 {
-        if self.read_en.read() == 1 {
-            self.mem.read(self.addr.read() as usize)
-        } else {
-            0
+        if self.read_en.read() != 1 {
+            return 0;
+        }
+        let word = self.mem.read(self.addr.read() as usize);
+        // lb, lh, lbu and lhu take the low bytes, lw and the rest the word
+        match self.width.as_ref().map_or(0b010, |width| width.read()) {
+            0b000 => word as u8 as i8 as u32,
+            0b001 => word as u16 as i16 as u32,
+            0b100 => word & 0xff,
+            0b101 => word & 0xffff,
+            _ => word,
         }
     }
 }
@@ -9,7 +9,9 @@ pub struct MemWriter {
     addr_cache: u32,
     pub write_data: PortRef,
     write_data_cache: u32,
-    mem: IndexPortShared<Mem>,
+    pub width: Option<PortRef>,
+    width_cache: u32,
+    pub(super) mem: IndexPortShared<Mem>,
 }
 impl MemWriter {
     pub fn new(mem: IndexPortShared<Mem>) -> Self {
@@ -20,6 +22,8 @@ impl MemWriter {
             addr_cache: 0,
             write_data: bomb().into(),
             write_data_cache: 0,
+            width: None,
+            width_cache: 0b010,
             mem,
         }
     }
@@ -30,15 +34,30 @@ impl Control for MemWriter {
             self.write_en_cache = 1;
             self.addr_cache = self.addr.read();
             self.write_data_cache = self.write_data.read();
+            self.width_cache = self.width.as_ref().map_or(0b010, |width| width.read());
         } else {
             self.write_en_cache = 0;
         }
     }
     fn falling_edge(&mut self) {
         if self.write_en_cache == 1 {
-            self.mem
-                .borrow_mut()
-                .write(self.addr_cache as usize, self.write_data_cache);
+            // sb and sh keep the other bytes of the word
+            let mask = match self.width_cache & 0b11 {
+                0b00 => 0xff,
+                0b01 => 0xffff,
+                _ => u32::MAX,
+            };
+            let addr = self.addr_cache as usize;
+            let mut mem = self.mem.borrow_mut();
+            let word = mem.read(addr);
+            mem.write(addr, (word & !mask) | (self.write_data_cache & mask));
         }
     }
+    fn snapshot(&self) -> Snapshot {
+        self.mem.borrow().snapshot()
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.mem.borrow_mut().restore(snapshot);
+        self.write_en_cache = 0;
+    }
 }
@@ -105,6 +105,23 @@ impl Control for Reg {
     fn output(&self) -> Vec<(&'static str, u32)> {
         vec![("out", self.output.borrow().data)]
     }
+    fn snapshot(&self) -> Snapshot {
+        self.output.borrow().snapshot()
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.output.borrow_mut().restore(snapshot);
+        self.data = self.output.borrow().data;
+        self.en_cache = 1;
+        self.clr_cache = 0;
+    }
+    fn set(&mut self, name: &str, value: u32) -> bool {
+        if name != "out" {
+            return false;
+        }
+        self.output.borrow_mut().data = value;
+        self.data = value;
+        true
+    }
 }
 pub mod build {
     pub use super::Alloc as RegAlloc;
@@ -1,5 +1,5 @@
 pub fn u2i(u: u32) -> i32 {
-    unsafe { std::mem::transmute::<u32, i32>(u) }
+    u as i32
 }
 // pub fn i2u(i: i32) -> u32 {
 //     unsafe { std::mem::transmute::<i32, u32>(i) }
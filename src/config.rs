@@ -1,26 +1,65 @@
 use goblin::{elf, Object};
-use std::{fs::remove_file, process::Command};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::{fs::remove_file, process};
 mod args;
 mod file;
-#[derive(Debug, Default, Clone)]
+pub use args::{Args, Command, DapArgs, Format, FuzzArgs, GdbArgs, RpcArgs, RunArgs};
+pub use file::{LayoutConfig, PredictorConfig, TuiConfig, PREDICTORS};
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub insts: Vec<u8>,
     pub start: usize,
     pub asm: String,
     pub entry: usize,
+    // other allocated sections, loaded into data memory at their address
+    #[serde(default)]
+    pub data: Vec<(usize, Vec<u8>)>,
+    #[serde(default)]
+    pub symbols: BTreeMap<String, usize>,
+    // source file and line of the first instruction of each statement, from the debug info
+    #[serde(default)]
+    pub lines: BTreeMap<usize, (String, u32)>,
+    // the branch predictor the pipeline is built with
+    #[serde(default)]
+    pub predictor: PredictorConfig,
 }
-pub fn init() -> Result<Program, String> {
-    let args = args::init();
-    let file = file::init();
-    let compiler = args.compiler_path.unwrap_or(file.compiler);
-    let objdump = args.objdump_path.unwrap_or(file.objdump);
-    let file = args.file.unwrap_or(file.file);
-    let mut pg = Program::default();
-    let output = Command::new(&compiler)
+impl Program {
+    // a program of just these instructions from address 0, as in the tests and the fuzzer
+    pub fn from_words(insts: &[u32]) -> Self {
+        Program {
+            insts: insts.iter().flat_map(|x| x.to_ne_bytes()).collect(),
+            ..Default::default()
+        }
+    }
+}
+pub fn parse_args() -> Args {
+    args::init()
+}
+// the script from the command line, or else from the config file
+pub fn script_path(args: &Args) -> Result<Option<String>, String> {
+    match &args.script {
+        Some(path) => Ok(Some(path.clone())),
+        None => Ok(file::init()?.script),
+    }
+}
+// the [tui] section of the config file
+pub fn tui_config() -> Result<TuiConfig, String> {
+    Ok(file::init()?.tui)
+}
+pub fn init(args: &Args) -> Result<Program, String> {
+    let file = file::init()?;
+    let predictor = file.predictor;
+    predictor.validate()?;
+    let compiler = args.compiler_path.clone().unwrap_or(file.compiler);
+    let objdump = args.objdump_path.clone().unwrap_or(file.objdump);
+    let file = args.file.clone().unwrap_or(file.file);
+    let output = process::Command::new(&compiler)
         .args([
             "-march=rv32i",
             "-mabi=ilp32",
             "-O0",
+            "-g",
             "-x",
             "c",
             "-static",
@@ -36,24 +75,8 @@ pub fn init() -> Result<Program, String> {
         return Err(output.stderr.iter().map(|&x| x as char).collect());
     }
     let dat = std::fs::read("a.out").unwrap();
-    match Object::parse(&dat).unwrap() {
-        Object::Elf(elf) => {
-            pg.entry = elf.entry as usize;
-            for sh in elf.section_headers {
-                if sh.sh_type == elf::section_header::SHT_PROGBITS
-                    && &elf.shdr_strtab[sh.sh_name] == ".text"
-                {
-                    pg.start = sh.sh_addr as usize;
-                    pg.insts.extend_from_slice(
-                        &dat[sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize],
-                    );
-                    break;
-                }
-            }
-        }
-        _ => return Err("Not an ELF file".to_string()),
-    }
-    let status = Command::new(objdump)
+    let mut pg = load_elf(&dat)?;
+    let status = process::Command::new(objdump)
         .args(["-d", "a.out", "-M", "numeric"])
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -66,5 +89,92 @@ pub fn init() -> Result<Program, String> {
     let stdout = String::from_utf8(status.stdout).unwrap();
     let pos = stdout.find("Disassembly of section .text:").unwrap();
     pg.asm = stdout[pos + 30..].to_string();
+    pg.predictor = predictor;
+    Ok(pg)
+}
+// .text goes to instruction memory, every other allocated section with contents to data memory
+pub fn load_elf(dat: &[u8]) -> Result<Program, String> {
+    let Ok(Object::Elf(elf)) = Object::parse(dat) else {
+        return Err("Not an ELF file".to_string());
+    };
+    let mut pg = Program {
+        entry: elf.entry as usize,
+        ..Default::default()
+    };
+    for sh in &elf.section_headers {
+        if sh.sh_type != elf::section_header::SHT_PROGBITS
+            || sh.sh_flags & elf::section_header::SHF_ALLOC as u64 == 0
+        {
+            continue;
+        }
+        let bytes = dat
+            .get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize)
+            .ok_or("Section out of file")?;
+        if &elf.shdr_strtab[sh.sh_name] == ".text" {
+            pg.start = sh.sh_addr as usize;
+            pg.insts.extend_from_slice(bytes);
+        } else {
+            pg.data.push((sh.sh_addr as usize, bytes.to_vec()));
+        }
+    }
+    pg.lines = load_lines(&elf, dat).map_err(|e| format!("Bad debug info: {}", e))?;
+    for sym in elf.syms.iter() {
+        match elf.strtab.get_at(sym.st_name) {
+            Some(name) if !name.is_empty() && sym.st_type() != elf::sym::STT_SECTION => {
+                pg.symbols.insert(name.to_string(), sym.st_value as usize);
+            }
+            _ => {}
+        }
+    }
     Ok(pg)
 }
+// the statements of the .debug_line table, empty without debug info
+fn load_lines(elf: &elf::Elf, dat: &[u8]) -> Result<BTreeMap<usize, (String, u32)>, gimli::Error> {
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+        let data = elf
+            .section_headers
+            .iter()
+            .find(|sh| &elf.shdr_strtab[sh.sh_name] == id.name())
+            .and_then(|sh| dat.get(sh.sh_offset as usize..(sh.sh_offset + sh.sh_size) as usize))
+            .unwrap_or(&[]);
+        Ok(gimli::EndianSlice::new(data, gimli::LittleEndian))
+    })?;
+    let mut lines = BTreeMap::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(program) = unit.line_program.clone() else {
+            continue;
+        };
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            let (Some(file), Some(line)) = (row.file(header), row.line()) else {
+                continue;
+            };
+            if row.end_sequence() || !row.is_stmt() {
+                continue;
+            }
+            let mut path = std::path::PathBuf::new();
+            if let Some(dir) = &unit.comp_dir {
+                path.push(dir.to_string_lossy().as_ref());
+            }
+            if let Some(dir) = file.directory(header) {
+                path.push(dwarf.attr_string(&unit, dir)?.to_string_lossy().as_ref());
+            }
+            path.push(
+                dwarf
+                    .attr_string(&unit, file.path_name())?
+                    .to_string_lossy()
+                    .as_ref(),
+            );
+            let path: std::path::PathBuf = path
+                .components()
+                .filter(|c| *c != std::path::Component::CurDir)
+                .collect();
+            lines
+                .entry(row.address() as usize)
+                .or_insert((path.display().to_string(), line.get() as u32));
+        }
+    }
+    Ok(lines)
+}
@@ -1,4 +1,5 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::ops::Range;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -9,6 +10,171 @@ pub struct Args {
     pub objdump_path: Option<String>,
     #[arg(short, long)]
     pub file: Option<String>,
+    /// restore a state saved from the tui instead of compiling
+    #[arg(short, long)]
+    pub restore: Option<String>,
+    /// call back this Rhai script every cycle, instead of the one in the config file
+    #[arg(long)]
+    pub script: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// simulate without the tui until the program halts, then print a report
+    Run(RunArgs),
+    /// compare the pipeline against the functional model on random programs
+    Fuzz(FuzzArgs),
+    /// debug the program with gdb over the remote serial protocol
+    Gdb(GdbArgs),
+    /// debug the program from an editor over the debug adapter protocol
+    Dap(DapArgs),
+    /// drive the simulator from scripts with line-delimited JSON-RPC
+    Rpc(RpcArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// stop after this many cycles if the program has not halted
+    #[arg(short, long, default_value_t = 1_000_000)]
+    pub max_cycles: usize,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    /// dump the register file
+    #[arg(long)]
+    pub regs: bool,
+    /// dump data memory words, as <ADDR>:<LEN> in bytes from a word address, e.g. 0x7fffff00:64
+    #[arg(long, value_parser = parse_range)]
+    pub mem: Vec<(u32, u32)>,
+    /// write the pipeline diagram of the run, as CSV if the path ends in .csv
+    #[arg(long)]
+    pub diagram: Option<String>,
+    /// write a Kanata log of the run for the Konata pipeline visualizer
+    #[arg(long)]
+    pub kanata: Option<String>,
+    /// write the pipeline registers, signals, registers and stages of the last cycle as an
+    /// HTML page, or as SVG if the path ends in .svg
+    #[arg(long)]
+    pub snapshot: Option<String>,
+    /// write a snapshot of every cycle in START..END instead, numbered like cycle-12.html
+    #[arg(long, value_parser = parse_cycles, requires = "snapshot")]
+    pub snapshot_cycles: Option<Range<usize>>,
+    /// write a VCD waveform of all the named signals
+    #[arg(long)]
+    pub vcd: Option<String>,
+    /// write a commit log of the retired instructions in the format of spike --log-commits
+    #[arg(long)]
+    pub commit_log: Option<String>,
+    /// check every retired instruction against the functional model and stop at the first mismatch
+    #[arg(long)]
+    pub lockstep: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FuzzArgs {
+    /// seed of the first program, a random one if not given
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// number of programs to run
+    #[arg(short, long, default_value_t = 1000)]
+    pub iterations: usize,
+    /// instructions in each program
+    #[arg(long, default_value_t = 64)]
+    pub len: usize,
+    /// how far back a dependent source register reaches, in instructions
+    #[arg(long, default_value_t = 3)]
+    pub max_distance: usize,
+    /// chance that a source register depends on a recent instruction
+    #[arg(long, default_value_t = 0.5, value_parser = parse_chance)]
+    pub dependency: f64,
+    /// chance that an instruction is a branch or a jump
+    #[arg(long, default_value_t = 0.1, value_parser = parse_chance)]
+    pub branch_density: f64,
+    /// chance that an instruction is a load or a store
+    #[arg(long, default_value_t = 0.2, value_parser = parse_chance)]
+    pub memory_density: f64,
+    /// cycles a program may take before it counts as hung
+    #[arg(short, long, default_value_t = 100_000)]
+    pub max_cycles: usize,
+    /// write the shrunk reproducer of a failure here as assembler input
+    #[arg(long)]
+    pub out: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GdbArgs {
+    /// address to listen on for `target remote`
+    #[arg(default_value = "127.0.0.1:1234")]
+    pub listen: String,
+    /// listen on this Unix socket instead
+    #[arg(long)]
+    pub unix: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DapArgs {
+    /// serve one client on this address instead of stdin and stdout
+    #[arg(long)]
+    pub listen: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RpcArgs {
+    /// serve one client on this address instead of stdin and stdout
+    #[arg(long)]
+    pub listen: Option<String>,
+    /// serve one client on this Unix socket instead
+    #[arg(long)]
+    pub unix: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+fn parse_num(s: &str) -> Result<u32, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("invalid number {}: {}", s, e))
+}
+
+fn parse_range(s: &str) -> Result<(u32, u32), String> {
+    let (addr, len) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected <ADDR>:<LEN>, found {}", s))?;
+    let (addr, len) = (parse_num(addr)?, parse_num(len)?);
+    if addr % 4 != 0 {
+        return Err(format!("{:#x} is not word aligned", addr));
+    }
+    if addr.checked_add(len).is_none() {
+        return Err(format!("{} runs past the end of memory", s));
+    }
+    Ok((addr, len))
+}
+
+// a probability, from 0 to 1
+fn parse_chance(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(chance) if (0.0..=1.0).contains(&chance) => Ok(chance),
+        Ok(_) => Err(format!("{} is not between 0 and 1", s)),
+        Err(e) => Err(format!("invalid chance {}: {}", s, e)),
+    }
+}
+
+fn parse_cycles(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("expected <START>..<END>, found {}", s))?;
+    let cycle = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|e| format!("invalid cycle {}: {}", s, e))
+    };
+    Ok(cycle(start)?..cycle(end)?)
 }
 
 pub fn init() -> Args {
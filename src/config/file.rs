@@ -1,9 +1,84 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+// anything left out keeps its default, so a file may hold just a [tui] section
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub compiler: String,
     pub objdump: String,
     pub file: String,
+    // Rhai script called back every cycle
+    pub script: Option<String>,
+    pub tui: TuiConfig,
+    pub predictor: PredictorConfig,
+}
+// the [predictor] section, how fetch guesses the way of a conditional branch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PredictorConfig {
+    // one of PREDICTORS
+    pub kind: String,
+    // log2 of the entries in each table of the dynamic predictors
+    pub bits: u32,
+    // global history bits of gshare and tournament
+    pub history: u32,
+}
+pub const PREDICTORS: [&str; 7] = [
+    "not-taken",
+    "always-taken",
+    "btfn",
+    "1-bit",
+    "2-bit",
+    "gshare",
+    "tournament",
+];
+impl PredictorConfig {
+    // a kind of PREDICTORS with tables and history of up to 2^24 entries
+    pub fn validate(&self) -> Result<(), String> {
+        if !PREDICTORS.contains(&self.kind.as_str()) || self.bits > 24 || self.history > 24 {
+            return Err(format!(
+                "no predictor {} with {} bits and {} bits of history, one of {} with up to 24 bits",
+                self.kind,
+                self.bits,
+                self.history,
+                PREDICTORS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+impl Default for PredictorConfig {
+    fn default() -> Self {
+        Self {
+            kind: String::from("not-taken"),
+            bits: 10,
+            history: 8,
+        }
+    }
+}
+// the [tui] section, anything left out keeps its default
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TuiConfig {
+    // default, monochrome or high-contrast
+    pub theme: Option<String>,
+    // action = key, e.g. step = "n"
+    pub keys: BTreeMap<String, String>,
+    // element = colour over the theme, e.g. fetch = "lightgreen"
+    pub colors: BTreeMap<String, String>,
+    pub layout: LayoutConfig,
+    // cycles per second of the play mode, 4 when left out
+    pub speed: Option<u32>,
+}
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    // percent of the height for the tabs, 24 rows when left out
+    pub top: Option<u16>,
+    // percent of the width below for the ASM pane, the Decode panel gets the rest,
+    // 100 hides it
+    pub asm: Option<u16>,
 }
 impl Default for Config {
     fn default() -> Self {
@@ -11,10 +86,47 @@ impl Default for Config {
             compiler: String::from("riscv32-unknown-elf-gcc"),
             objdump: String::from("riscv32-unknown-elf-objdump"),
             file: String::from("main.c"),
+            script: None,
+            tui: TuiConfig::default(),
+            predictor: PredictorConfig::default(),
         }
     }
 }
 
-pub fn init() -> Config {
-    xcfg::load::<Config>("config.").ok().unwrap_or_default()
+const PATHS: [&str; 3] = ["config.toml", "config.yaml", "config.yml"];
+
+// the config file in the working directory, the defaults when there is none
+pub fn init() -> Result<Config, String> {
+    load(PATHS.into_iter().find(|path| Path::new(path).is_file()))
+}
+fn load(path: Option<&str>) -> Result<Config, String> {
+    match path {
+        // the parse errors of toml and yaml tell the line and column
+        Some(path) => xcfg::load::<Config>(path).map_err(|e| format!("{}: {}", path, e.message)),
+        None => Ok(Config::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        assert_eq!(load(None).unwrap().file, "main.c");
+        let dir = std::env::temp_dir().join("rv-simulator-test-config");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "[tui]\nspeed = 8\n").unwrap();
+        let config = load(Some(path)).unwrap();
+        assert_eq!(
+            (config.tui.speed, config.compiler.as_str()),
+            (Some(8), "riscv32-unknown-elf-gcc")
+        );
+        std::fs::write(path, "file = \"a.c\"\n[tui\n").unwrap();
+        let err = load(Some(path)).unwrap_err();
+        assert!(err.starts_with(path) && err.contains("line 2"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
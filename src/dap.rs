@@ -0,0 +1,678 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use crate::config::DapArgs;
+use crate::debuggee::{Debuggee, Stop};
+use crate::simulator::{Rv32i, XREG_NAMES};
+
+// retirements between two looks for a pause request while running
+const POLL_INTERVAL: usize = 1024;
+// cycles one stepCycle request may take, like the run limit of the tui
+const STEP_LIMIT: u64 = 1_000_000;
+const REGISTERS: i64 = 1;
+const PIPELINE: i64 = 2;
+// the pipeline registers follow, one reference each
+const PIPELINE_REGS: i64 = 10;
+
+// serve the debug adapter protocol on stdin and stdout, or to one client on `args.listen`
+pub fn serve(rv: Rv32i, args: &DapArgs) -> io::Result<()> {
+    match &args.listen {
+        Some(addr) => {
+            let listener = TcpListener::bind(addr)?;
+            eprintln!("waiting for a DAP client on {}", listener.local_addr()?);
+            let (stream, _) = listener.accept()?;
+            let requests = spawn_reader(stream.try_clone()?);
+            Server::new(rv, stream, requests).run()
+        }
+        None => {
+            let requests = spawn_reader(io::stdin());
+            Server::new(rv, io::stdout(), requests).run()
+        }
+    }
+}
+
+// parse messages on their own thread, so a running program can look for a pause
+fn spawn_reader(input: impl Read + Send + 'static) -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut input = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// Content-Length framed JSON, none at the end of the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let len = len.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length"))?;
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+// a request the debuggee cannot serve
+type Failure = String;
+
+pub struct Server<W: Write> {
+    pub debuggee: Debuggee,
+    out: W,
+    seq: i64,
+    requests: Receiver<Value>,
+    // came in while the program was running
+    pending: VecDeque<Value>,
+    // breakpoint addresses by source path, and by instruction
+    source_breakpoints: BTreeMap<String, Vec<u32>>,
+    instruction_breakpoints: Vec<u32>,
+}
+impl<W: Write> Server<W> {
+    pub fn new(rv: Rv32i, out: W, requests: Receiver<Value>) -> Self {
+        Self {
+            debuggee: Debuggee::new(rv),
+            out,
+            seq: 0,
+            requests,
+            pending: VecDeque::new(),
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: vec![],
+        }
+    }
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            let request = match self.pending.pop_front() {
+                Some(request) => request,
+                None => match self.requests.recv() {
+                    Ok(request) => request,
+                    Err(_) => return Ok(()),
+                },
+            };
+            if !self.handle(&request)? {
+                return Ok(());
+            }
+        }
+    }
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.out.flush()
+    }
+    fn respond(&mut self, request: &Value, body: Result<Value, Failure>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+    fn stopped(&mut self, reason: &str, description: Option<String>) -> io::Result<()> {
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": 1,
+                "allThreadsStopped": true,
+            }),
+        )
+    }
+    // answer one request, false once the client is done
+    pub fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let args = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        match command {
+            "initialize" => {
+                self.respond(request, Ok(capabilities()))?;
+                self.event("initialized", json!({}))?;
+            }
+            "configurationDone" => {
+                self.respond(request, Ok(json!({})))?;
+                self.stopped("entry", None)?;
+            }
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                self.respond(request, Ok(json!({ "allThreadsContinued": true })))?;
+                let granularity = args["granularity"].as_str().unwrap_or("statement");
+                self.resume(command, granularity == "instruction")?;
+            }
+            "pause" => {
+                self.respond(request, Ok(json!({})))?;
+                self.stopped("pause", None)?;
+            }
+            "stepCycle" => {
+                let count = args["count"].as_u64().unwrap_or(1).min(STEP_LIMIT);
+                for _ in 0..count {
+                    if self.debuggee.rv.halted() {
+                        break;
+                    }
+                    self.debuggee.step_cycle();
+                }
+                let cycle = self.debuggee.rv.cycle;
+                self.respond(request, Ok(json!({ "cycle": cycle })))?;
+                self.stopped("step", Some(format!("cycle {}", cycle)))?;
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            _ => {
+                let body = self.query(command, args);
+                self.respond(request, body)?;
+            }
+        }
+        Ok(true)
+    }
+    // the requests that do not run the program
+    fn query(&mut self, command: &str, args: &Value) -> Result<Value, Failure> {
+        match command {
+            "launch" | "attach" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": 1, "name": "hart 0" }] })),
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(args),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                    { "name": "Pipeline", "variablesReference": PIPELINE, "expensive": false },
+                ]
+            })),
+            "variables" => self.variables(args["variablesReference"].as_i64().unwrap_or(0)),
+            "disassemble" => self.disassemble(args),
+            "readMemory" => self.read_memory(args),
+            _ => Err(format!("{} is not supported", command)),
+        }
+    }
+    // the line the instruction at `pc` belongs to
+    fn line(&self, pc: u32) -> Option<&(String, u32)> {
+        let lines = &self.debuggee.rv.pgbak.lines;
+        lines
+            .range(..=pc as usize)
+            .next_back()
+            .map(|(_, line)| line)
+    }
+    // run for continue and the three kinds of step, then report why it stopped
+    fn resume(&mut self, command: &str, instruction: bool) -> io::Result<()> {
+        let start = self.line(self.debuggee.pc()).cloned();
+        let lines = self.debuggee.rv.pgbak.lines.clone();
+        // calls entered and not yet returned from since the step began
+        let mut depth = 0;
+        let done = |commit: &crate::simulator::Commit, debuggee: &Debuggee| {
+            let (rd, rs1) = ((commit.inst >> 7) & 0x1f, (commit.inst >> 15) & 0x1f);
+            let returned = match commit.inst & 0x7f {
+                // jal or jalr that links through ra is a call
+                0b110_1111 | 0b110_0111 if rd == 1 => {
+                    depth += 1;
+                    false
+                }
+                // jalr x0, 0(ra) is a return
+                0b110_0111 if rd == 0 && rs1 == 1 => {
+                    depth -= 1;
+                    depth < 0
+                }
+                _ => false,
+            };
+            let pc = debuggee.pc() as usize;
+            // the first instruction of another line
+            let new_line = || match (lines.get(&pc), &start) {
+                (Some(line), Some(start)) => line != start,
+                (_, None) => true,
+                (None, _) => false,
+            };
+            match command {
+                "continue" => false,
+                _ if instruction => true,
+                "stepOut" => returned,
+                "next" => depth <= 0 && new_line(),
+                _ => new_line(),
+            }
+        };
+        let requests = &self.requests;
+        let pending = &mut self.pending;
+        let mut pause = None;
+        let mut retired = 0;
+        let stop = self.debuggee.resume(done, || {
+            retired += 1;
+            if retired % POLL_INTERVAL != 0 {
+                return Ok(false);
+            }
+            loop {
+                match requests.try_recv() {
+                    Ok(request) if request["command"] == "pause" => {
+                        pause = Some(request);
+                        return Ok(true);
+                    }
+                    Ok(request) => pending.push_back(request),
+                    Err(TryRecvError::Empty) => return Ok(false),
+                    // keep running, the client can no longer pause us
+                    Err(TryRecvError::Disconnected) => return Ok(false),
+                }
+            }
+        })?;
+        if let Some(request) = pause {
+            self.respond(&request, Ok(json!({})))?;
+        }
+        match stop {
+            Stop::Step => self.stopped("step", None),
+            Stop::Breakpoint => self.stopped("breakpoint", None),
+            Stop::Interrupt => self.stopped("pause", None),
+            Stop::Watch(_, addr) => {
+                self.stopped("data breakpoint", Some(format!("access to {:#x}", addr)))
+            }
+            Stop::Exited(code) => {
+                self.event("exited", json!({ "exitCode": code }))?;
+                self.event("terminated", json!({}))
+            }
+        }
+    }
+    fn update_breakpoints(&mut self) {
+        let sources = self.source_breakpoints.values().flatten();
+        self.debuggee.breakpoints = sources
+            .chain(&self.instruction_breakpoints)
+            .copied()
+            .collect();
+    }
+    // a line without code moves to the next line of the file that has some
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, Failure> {
+        let path = args["source"]["path"].as_str().ok_or("no source path")?;
+        let mut addrs = vec![];
+        let mut breakpoints = vec![];
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as u32;
+            let found = self
+                .debuggee
+                .rv
+                .pgbak
+                .lines
+                .iter()
+                .filter(|(_, (file, l))| same_file(path, file) && *l >= line)
+                .min_by_key(|(addr, (_, l))| (*l, **addr));
+            breakpoints.push(match found {
+                Some((addr, (_, line))) => {
+                    addrs.push(*addr as u32);
+                    json!({
+                        "verified": true,
+                        "line": line,
+                        "instructionReference": format!("{:#010x}", addr),
+                    })
+                }
+                None => json!({ "verified": false, "message": "no code at this line" }),
+            });
+        }
+        self.source_breakpoints.insert(path.to_string(), addrs);
+        self.update_breakpoints();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Result<Value, Failure> {
+        let mut breakpoints = vec![];
+        self.instruction_breakpoints.clear();
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let addr = breakpoint["instructionReference"]
+                .as_str()
+                .and_then(parse_addr)
+                .map(|addr| addr.wrapping_add(breakpoint["offset"].as_i64().unwrap_or(0) as u32));
+            breakpoints.push(json!({ "verified": addr.is_some() }));
+            self.instruction_breakpoints.extend(addr);
+        }
+        self.update_breakpoints();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+    fn stack_trace(&self) -> Value {
+        let pc = self.debuggee.pc();
+        let name = self
+            .debuggee
+            .rv
+            .pgbak
+            .symbols
+            .iter()
+            .filter(|(_, addr)| **addr <= pc as usize)
+            .max_by_key(|(_, addr)| **addr)
+            .map_or("??", |(name, _)| name.as_str());
+        let mut frame = json!({
+            "id": 0,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("{:#010x}", pc),
+        });
+        if let Some((path, line)) = self.line(pc) {
+            frame["source"] = source(path);
+            frame["line"] = json!(line);
+        }
+        json!({ "stackFrames": [frame], "totalFrames": 1 })
+    }
+    fn variables(&self, reference: i64) -> Result<Value, Failure> {
+        let rv = &self.debuggee.rv;
+        let variables: Vec<Value> = match reference {
+            REGISTERS => XREG_NAMES
+                .iter()
+                .enumerate()
+                .map(|(i, name)| variable(&format!("x{}/{}", i, name), rv.xregs.read(i)))
+                .chain([variable("pc", self.debuggee.pc())])
+                .collect(),
+            PIPELINE => {
+                let stages = rv.asm.stages();
+                pipeline_regs(rv)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, _))| {
+                        // a register holds the instruction of the stage after it
+                        let value = match stages[i + 1] {
+                            Some(pc) => format!("{:#010x}: {}", pc, rv.disasm(pc)),
+                            None => "bubble".to_string(),
+                        };
+                        json!({
+                            "name": name,
+                            "value": value,
+                            "variablesReference": PIPELINE_REGS + i as i64,
+                        })
+                    })
+                    .collect()
+            }
+            reference if (PIPELINE_REGS..PIPELINE_REGS + 4).contains(&reference) => {
+                let (_, reg) = &pipeline_regs(rv)[(reference - PIPELINE_REGS) as usize];
+                reg.inout()
+                    .iter()
+                    .map(|(name, _, out)| variable(name, *out))
+                    .collect()
+            }
+            _ => return Err(format!("no variables {}", reference)),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+    fn disassemble(&self, args: &Value) -> Result<Value, Failure> {
+        let base = args["memoryReference"]
+            .as_str()
+            .and_then(parse_addr)
+            .ok_or("bad memory reference")?;
+        let offset = args["offset"].as_i64().unwrap_or(0)
+            + args["instructionOffset"].as_i64().unwrap_or(0) * 4;
+        let count = args["instructionCount"].as_u64().unwrap_or(0) as u32;
+        let start = base.wrapping_add(offset as u32) & !3;
+        let rv = &self.debuggee.rv;
+        let instructions: Vec<Value> = (0..count)
+            .map(|i| {
+                let pc = start.wrapping_add(i * 4);
+                let mut inst = json!({
+                    "address": format!("{:#010x}", pc),
+                    "instructionBytes": format!("{:08x}", rv.imem.read(pc as usize)),
+                    "instruction": rv.disasm(pc),
+                });
+                if let Some((path, line)) = rv.pgbak.lines.get(&(pc as usize)) {
+                    inst["location"] = source(path);
+                    inst["line"] = json!(line);
+                }
+                inst
+            })
+            .collect();
+        Ok(json!({ "instructions": instructions }))
+    }
+    fn read_memory(&self, args: &Value) -> Result<Value, Failure> {
+        let base = args["memoryReference"]
+            .as_str()
+            .and_then(parse_addr)
+            .ok_or("bad memory reference")?;
+        let addr = base.wrapping_add(args["offset"].as_i64().unwrap_or(0) as u32);
+        let count = args["count"].as_u64().unwrap_or(0) as u32;
+        Ok(json!({
+            "address": format!("{:#010x}", addr),
+            "data": base64(&self.debuggee.read_mem(addr, count)),
+        }))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsDisassembleRequest": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsSteppingGranularity": true,
+        "supportsReadMemoryRequest": true,
+        "supportsTerminateRequest": true,
+    })
+}
+fn pipeline_regs(rv: &Rv32i) -> [(&'static str, &crate::common::abi::ControlRef); 4] {
+    [
+        ("IfId", &rv.if_id),
+        ("IdEx", &rv.id_ex),
+        ("ExMem", &rv.ex_mem),
+        ("MemWb", &rv.mem_wb),
+    ]
+}
+fn variable(name: &str, value: u32) -> Value {
+    json!({
+        "name": name,
+        "value": format!("{:#010x}", value),
+        "variablesReference": 0,
+        "memoryReference": format!("{:#010x}", value),
+    })
+}
+// debug info paths are relative to where the program was compiled
+fn source(path: &str) -> Value {
+    let path = Path::new(path);
+    let absolute = match path.is_relative() {
+        true => std::env::current_dir().map_or(path.to_path_buf(), |dir| dir.join(path)),
+        false => path.to_path_buf(),
+    };
+    json!({
+        "name": path.file_name().map(|name| name.to_string_lossy()),
+        "path": absolute.display().to_string(),
+    })
+}
+// the client has absolute paths, the debug info maybe not
+fn same_file(client: &str, debug_info: &str) -> bool {
+    Path::new(client).ends_with(debug_info) || Path::new(debug_info).ends_with(client)
+}
+fn parse_addr(reference: &str) -> Option<u32> {
+    match reference.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => reference.parse().ok(),
+    }
+}
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | (*byte as u32) << (16 - i * 8)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3f] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_elf;
+    use crate::simulator::Rv32iBuilder;
+
+    struct Client {
+        server: Server<Vec<u8>>,
+        seq: i64,
+        _requests: mpsc::Sender<Value>,
+    }
+    impl Client {
+        fn new() -> Self {
+            let pg = load_elf(include_bytes!("../tests/dap/bin/count.elf")).unwrap();
+            let (tx, rx) = mpsc::channel();
+            Self {
+                server: Server::new(Rv32iBuilder::new(pg).slf_build(), vec![], rx),
+                seq: 0,
+                _requests: tx,
+            }
+        }
+        // the messages the server sent back, the response first
+        fn request(&mut self, command: &str, arguments: Value) -> Vec<Value> {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            self.server.handle(&request).unwrap();
+            let out = std::mem::take(&mut self.server.out);
+            let mut input = &out[..];
+            let mut messages = vec![];
+            while let Some(message) = read_message(&mut input).unwrap() {
+                messages.push(message);
+            }
+            assert_eq!(messages[0]["request_seq"], self.seq);
+            messages
+        }
+        fn body(&mut self, command: &str, arguments: Value) -> Value {
+            self.request(command, arguments)[0]["body"].clone()
+        }
+        // the reason a run stopped and the frame it stopped in
+        fn run(&mut self, command: &str, arguments: Value) -> (Value, Value) {
+            let messages = self.request(command, arguments);
+            let frame = self.body("stackTrace", json!({ "threadId": 1 }));
+            (
+                messages[1]["body"]["reason"].clone(),
+                frame["stackFrames"][0].clone(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let mut client = Client::new();
+        let messages = client.request("initialize", json!({ "adapterID": "rv" }));
+        assert_eq!(messages[0]["body"]["supportsDisassembleRequest"], true);
+        assert_eq!(messages[1]["event"], "initialized");
+        // line 7 is a label, the breakpoint goes to the call below it
+        let body = client.body(
+            "setBreakpoints",
+            json!({ "source": { "path": "/src/count.S" }, "breakpoints": [{ "line": 7 }] }),
+        );
+        assert_eq!(body["breakpoints"][0]["line"], 8);
+        assert_eq!(body["breakpoints"][0]["instructionReference"], "0x00010008");
+
+        let (reason, frame) = client.run("configurationDone", json!({}));
+        assert_eq!(reason, "entry");
+        assert_eq!(frame["name"], "_start");
+        assert_eq!(frame["line"], 5);
+        assert_eq!(frame["source"]["name"], "count.S");
+        let (reason, frame) = client.run("continue", json!({ "threadId": 1 }));
+        assert_eq!(reason, "breakpoint");
+        assert_eq!(
+            (frame["name"].clone(), frame["line"].clone()),
+            (json!("loop"), json!(8))
+        );
+        // over the call
+        let (reason, frame) = client.run("next", json!({ "threadId": 1 }));
+        assert_eq!(reason, "step");
+        assert_eq!(frame["line"], 9);
+        let regs = client.body("variables", json!({ "variablesReference": REGISTERS }));
+        assert_eq!(regs["variables"][10]["name"], "x10/a0");
+        assert_eq!(regs["variables"][10]["value"], "0x00000001");
+        assert_eq!(regs["variables"][32]["value"], "0x00010010");
+
+        client.run("stepIn", json!({ "threadId": 1 }));
+        let (_, frame) = client.run("stepIn", json!({ "threadId": 1 }));
+        assert_eq!(frame["line"], 8);
+        // into the call, and out again
+        let (_, frame) = client.run("stepIn", json!({ "threadId": 1 }));
+        assert_eq!(
+            (frame["name"].clone(), frame["line"].clone()),
+            (json!("inc"), json!(13))
+        );
+        let (_, frame) = client.run("stepIn", json!({ "granularity": "instruction" }));
+        assert_eq!(frame["instructionPointerReference"], "0x00010020");
+        let (_, frame) = client.run("stepOut", json!({ "threadId": 1 }));
+        assert_eq!(frame["line"], 9);
+
+        let pipeline = client.body("variables", json!({ "variablesReference": PIPELINE }));
+        let names: Vec<_> = pipeline["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["IfId", "IdEx", "ExMem", "MemWb"]);
+        assert!(pipeline["variables"][3]["value"]
+            .as_str()
+            .unwrap()
+            .starts_with("0x00010010"));
+        let if_id = client.body("variables", json!({ "variablesReference": PIPELINE_REGS }));
+        assert!(if_id["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|v| v["name"] == "inst"));
+        let cycle = client.server.debuggee.rv.cycle;
+        let messages = client.request("stepCycle", json!({}));
+        assert_eq!(messages[0]["body"]["cycle"], cycle + 1);
+        assert_eq!(
+            messages[1]["body"]["description"],
+            format!("cycle {}", cycle + 1)
+        );
+
+        let body = client.body(
+            "disassemble",
+            json!({ "memoryReference": "0x10000", "instructionOffset": 1, "instructionCount": 2 }),
+        );
+        assert_eq!(body["instructions"][0]["address"], "0x00010004");
+        assert_eq!(body["instructions"][0]["instructionBytes"], "00300293");
+        assert_eq!(body["instructions"][1]["line"], 8);
+        let body = client.body(
+            "readMemory",
+            json!({ "memoryReference": "0x10000", "count": 4 }),
+        );
+        assert_eq!(body["data"], "EwUAAA==");
+
+        client.body(
+            "setBreakpoints",
+            json!({ "source": { "path": "/src/count.S" }, "breakpoints": [] }),
+        );
+        let messages = client.request("continue", json!({ "threadId": 1 }));
+        assert_eq!(messages[1]["event"], "exited");
+        assert_eq!(messages[1]["body"]["exitCode"], 3);
+        assert_eq!(messages[2]["event"], "terminated");
+        let request = json!({ "seq": 100, "type": "request", "command": "disconnect" });
+        assert!(!client.server.handle(&request).unwrap());
+    }
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
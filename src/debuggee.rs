@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+use std::io;
+
+use crate::common::abi::*;
+use crate::simulator::{Commit, Rv32i};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Watch {
+    Write,
+    Read,
+    Access,
+}
+
+// why a resumed program stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint,
+    // the kind of watchpoint and the address of the access that hit it
+    Watch(Watch, u32),
+    Interrupt,
+    // exit code in a0
+    Exited(u32),
+}
+
+// run control shared by the debugger frontends. the program is seen as it is
+// before the instruction in write back retires, with its pc as the pc, and
+// runs one retirement at a time
+#[derive(Debug)]
+pub struct Debuggee {
+    pub rv: Rv32i,
+    pub breakpoints: BTreeSet<u32>,
+    // (kind, address, length)
+    pub watchpoints: Vec<(Watch, u32, u32)>,
+    // address and old value of the word a store in write back wrote in MEM. it has
+    // not retired yet, so reads still see the old value and a refetch puts it back
+    overwritten: Option<(u32, u32)>,
+}
+impl Debuggee {
+    pub fn new(rv: Rv32i) -> Self {
+        let mut debuggee = Self {
+            rv,
+            breakpoints: BTreeSet::new(),
+            watchpoints: vec![],
+            overwritten: None,
+        };
+        debuggee.fill();
+        debuggee
+    }
+    // one cycle, keeping what the store in MEM overwrites
+    pub fn step_cycle(&mut self) {
+        let mem = self.rv.ex_mem.inout();
+        let get = |name: &str| mem.iter().find(|s| s.0 == name).unwrap().2;
+        self.overwritten = (get("mem_write") == 1).then(|| {
+            let addr = get("alu_res");
+            (addr, self.rv.dmem.read(addr as usize))
+        });
+        self.rv.step();
+    }
+    // run until an instruction is about to retire
+    fn fill(&mut self) {
+        while self.rv.asm.stages()[4].is_none() {
+            self.step_cycle();
+        }
+    }
+    // the instruction in write back, or the oldest in flight after stepping by cycles
+    pub fn pc(&self) -> u32 {
+        self.rv
+            .asm
+            .stages()
+            .into_iter()
+            .rev()
+            .flatten()
+            .next()
+            .unwrap()
+    }
+    // let the instruction in write back retire and bring up the next one
+    pub fn retire(&mut self) -> Option<Commit> {
+        let commit = Commit::new(&self.rv);
+        if commit.is_some() {
+            self.step_cycle();
+        }
+        self.fill();
+        commit
+    }
+    // the instructions in flight have seen the old registers or memory
+    fn refetch(&mut self, pc: u32) {
+        self.roll_back();
+        self.rv.redirect(pc);
+        self.fill();
+    }
+    // undo the write of the store in write back, which is about to be squashed
+    fn roll_back(&mut self) {
+        if let Some((addr, old)) = self.overwritten.take() {
+            self.rv.dmem.write(addr as usize, old);
+        }
+    }
+    // x0..x31, then the pc as 32
+    pub fn reg(&self, i: usize) -> Option<u32> {
+        match i {
+            0..=31 => Some(self.rv.xregs.read(i)),
+            32 => Some(self.pc()),
+            _ => None,
+        }
+    }
+    pub fn write_reg(&mut self, i: usize, value: u32) -> bool {
+        match i {
+            0..=31 => {
+                self.rv.xregs.write(i, value);
+                self.refetch(self.pc());
+            }
+            32 => self.refetch(value),
+            _ => return false,
+        }
+        true
+    }
+    pub fn write_regs(&mut self, regs: &[u32; 33]) {
+        for (i, value) in regs[..32].iter().enumerate() {
+            self.rv.xregs.write(i, *value);
+        }
+        self.refetch(regs[32]);
+    }
+    // instructions come from the instruction memory, everything else from data memory
+    fn mem_port(&self, addr: u32) -> &IndexPortRef {
+        let start = self.rv.pgbak.start as u32;
+        match addr.wrapping_sub(start) < self.rv.pgbak.insts.len() as u32 {
+            true => &self.rv.imem,
+            false => &self.rv.dmem,
+        }
+    }
+    pub fn read_mem(&self, addr: u32, len: u32) -> Vec<u8> {
+        (0..len)
+            .map(|i| {
+                let addr = addr.wrapping_add(i);
+                let port = self.mem_port(addr);
+                match self.overwritten {
+                    Some((at, old))
+                        if addr.wrapping_sub(at) < 4 && std::ptr::eq(port, &self.rv.dmem) =>
+                    {
+                        (old >> (addr.wrapping_sub(at) * 8)) as u8
+                    }
+                    _ => (port.read((addr & !3) as usize) >> ((addr & 3) * 8)) as u8,
+                }
+            })
+            .collect()
+    }
+    pub fn write_mem(&mut self, addr: u32, data: &[u8]) {
+        self.roll_back();
+        for (i, byte) in data.iter().enumerate() {
+            let addr = addr.wrapping_add(i as u32);
+            let port = self.mem_port(addr);
+            let shift = (addr & 3) * 8;
+            let word = port.read((addr & !3) as usize) & !(0xff << shift);
+            port.write((addr & !3) as usize, word | ((*byte as u32) << shift));
+        }
+        self.refetch(self.pc());
+    }
+    // the watchpoint the retired instruction hit
+    fn watched(&self, commit: &Commit) -> Option<Stop> {
+        let size = 1 << ((commit.inst >> 12) & 0b11);
+        let (access, addr) = match (commit.load, commit.store) {
+            (Some(addr), _) => (Watch::Read, addr),
+            (_, Some((addr, _, _))) => (Watch::Write, addr),
+            _ => return None,
+        };
+        let (watch, _, _) = self.watchpoints.iter().find(|(watch, start, len)| {
+            // an end past the last address is past every access
+            (*watch == access || *watch == Watch::Access)
+                && start.checked_add(*len).is_none_or(|end| addr < end)
+                && addr.checked_add(size).is_none_or(|end| *start < end)
+        })?;
+        Some(Stop::Watch(*watch, addr))
+    }
+    // retire instructions until `done` with the retired one and the debuggee,
+    // a breakpoint, a watchpoint, `interrupted` or the end of the program
+    pub fn resume(
+        &mut self,
+        mut done: impl FnMut(&Commit, &Self) -> bool,
+        mut interrupted: impl FnMut() -> io::Result<bool>,
+    ) -> io::Result<Stop> {
+        loop {
+            if self.rv.halted() {
+                return Ok(Stop::Exited(self.rv.xregs.read(10)));
+            }
+            let Some(commit) = self.retire() else {
+                continue;
+            };
+            if let Some(stop) = self.watched(&commit) {
+                return Ok(stop);
+            }
+            if done(&commit, self) {
+                return Ok(Stop::Step);
+            }
+            if self.breakpoints.contains(&self.pc()) {
+                return Ok(Stop::Breakpoint);
+            }
+            if interrupted()? {
+                return Ok(Stop::Interrupt);
+            }
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use crate::config::GdbArgs;
+use crate::debuggee::{Debuggee, Stop, Watch};
+use crate::simulator::{Rv32i, XREG_NAMES};
+
+// retirements between two looks for an interrupt from gdb while running
+const POLL_INTERVAL: usize = 1024;
+// the longest packet gdb is told to send, and the most memory read at once
+// so that the hex of the reply fits in one
+const PACKET_SIZE: u32 = 0x4000;
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// a connection gdb talks over
+pub trait Stream: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+impl Stream for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+impl Stream for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+// wait for gdb on `args.listen` and serve it until it detaches
+pub fn serve(rv: Rv32i, args: &GdbArgs) -> io::Result<()> {
+    let mut stub = Stub::new(rv);
+    match &args.unix {
+        Some(path) => {
+            let listener = UnixListener::bind(path)?;
+            eprintln!("waiting for gdb on {}", path);
+            let (stream, _) = listener.accept()?;
+            let result = stub.run(stream);
+            std::fs::remove_file(path)?;
+            result
+        }
+        None => {
+            let listener = TcpListener::bind(&args.listen)?;
+            eprintln!("waiting for gdb on {}", listener.local_addr()?);
+            let (stream, _) = listener.accept()?;
+            stub.run(stream)
+        }
+    }
+}
+
+// speaks for a debuggee to gdb
+#[derive(Debug)]
+pub struct Stub {
+    pub debuggee: Debuggee,
+}
+impl Stub {
+    pub fn new(rv: Rv32i) -> Self {
+        Self {
+            debuggee: Debuggee::new(rv),
+        }
+    }
+    pub fn run(&mut self, mut stream: impl Stream) -> io::Result<()> {
+        while let Some(packet) = read_packet(&mut stream)? {
+            let reply = match packet.as_bytes().first() {
+                Some(b'c') => self.resume(false, &mut stream)?,
+                Some(b's') => self.resume(true, &mut stream)?,
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    write_packet(&mut stream, "OK")?;
+                    return Ok(());
+                }
+                _ => self.handle(&packet),
+            };
+            write_packet(&mut stream, &reply)?;
+        }
+        Ok(())
+    }
+    // the reply to a packet that does not run the program
+    pub fn handle(&mut self, packet: &str) -> String {
+        let debuggee = &mut self.debuggee;
+        let (kind, args) = packet.split_at(1.min(packet.len()));
+        let reply = match kind {
+            "?" => Some(format!("S{:02x}", SIGTRAP)),
+            "g" => (0..33).map(|i| debuggee.reg(i).map(hex_word)).collect(),
+            "G" => (0..33)
+                .map(|i| parse_word(args.get(i * 8..i * 8 + 8)?))
+                .collect::<Option<Vec<_>>>()
+                .map(|regs| {
+                    debuggee.write_regs(&regs.try_into().unwrap());
+                    "OK".to_string()
+                }),
+            "p" => usize::from_str_radix(args, 16)
+                .ok()
+                .and_then(|i| debuggee.reg(i))
+                .map(hex_word),
+            "P" => args.split_once('=').and_then(|(i, value)| {
+                let i = usize::from_str_radix(i, 16).ok()?;
+                debuggee
+                    .write_reg(i, parse_word(value)?)
+                    .then(|| "OK".to_string())
+            }),
+            "m" => parse_range(args).map(|(addr, len)| {
+                debuggee
+                    .read_mem(addr, len.min(PACKET_SIZE / 2))
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect()
+            }),
+            "M" => args.split_once(':').and_then(|(range, data)| {
+                let (addr, len) = parse_range(range)?;
+                let data = (0..len as usize)
+                    .map(|i| u8::from_str_radix(data.get(i * 2..i * 2 + 2)?, 16).ok())
+                    .collect::<Option<Vec<_>>>()?;
+                debuggee.write_mem(addr, &data);
+                Some("OK".to_string())
+            }),
+            "Z" | "z" => point(debuggee, kind == "Z", args),
+            "H" => Some("OK".to_string()),
+            "q" => query(args),
+            _ => Some(String::new()),
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+    // single step or continue, and the stop reply
+    fn resume(&mut self, step: bool, stream: &mut impl Stream) -> io::Result<String> {
+        let mut retired = 0;
+        let stop = self.debuggee.resume(
+            |_, _| step,
+            || {
+                retired += 1;
+                match retired % POLL_INTERVAL {
+                    0 => interrupted(stream),
+                    _ => Ok(false),
+                }
+            },
+        )?;
+        Ok(match stop {
+            Stop::Step | Stop::Breakpoint => format!("S{:02x}", SIGTRAP),
+            Stop::Interrupt => format!("S{:02x}", SIGINT),
+            Stop::Exited(code) => format!("W{:02x}", code & 0xff),
+            Stop::Watch(watch, addr) => {
+                let name = match watch {
+                    Watch::Write => "watch",
+                    Watch::Read => "rwatch",
+                    Watch::Access => "awatch",
+                };
+                format!("T{:02x}{}:{:x};", SIGTRAP, name, addr)
+            }
+        })
+    }
+}
+
+// Z0/Z1 breakpoints, Z2 write, Z3 read and Z4 access watchpoints
+fn point(debuggee: &mut Debuggee, insert: bool, args: &str) -> Option<String> {
+    let (kind, range) = args.split_once(',')?;
+    let (addr, len) = parse_range(range)?;
+    let watch = match kind {
+        "0" | "1" => {
+            match insert {
+                true => debuggee.breakpoints.insert(addr),
+                false => debuggee.breakpoints.remove(&addr),
+            };
+            return Some("OK".to_string());
+        }
+        "2" => Watch::Write,
+        "3" => Watch::Read,
+        "4" => Watch::Access,
+        _ => return Some(String::new()),
+    };
+    let watchpoints = &mut debuggee.watchpoints;
+    match insert {
+        true => watchpoints.push((watch, addr, len)),
+        false => watchpoints.retain(|w| *w != (watch, addr, len)),
+    }
+    Some("OK".to_string())
+}
+fn query(args: &str) -> Option<String> {
+    if args.starts_with("Supported") {
+        return Some(format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE));
+    }
+    if args == "Attached" {
+        return Some("1".to_string());
+    }
+    let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") else {
+        return Some(String::new());
+    };
+    let (offset, len) = parse_range(range)?;
+    let xml = target_xml();
+    let start = (offset as usize).min(xml.len());
+    let end = (start + len as usize).min(xml.len());
+    let more = if end < xml.len() { 'm' } else { 'l' };
+    Some(format!("{}{}", more, &xml[start..end]))
+}
+
+// whether gdb sent ^C while the program was running
+fn interrupted(stream: &mut impl Stream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut buf = [0; 1];
+    let result = match stream.read(&mut buf) {
+        Ok(n) => Ok(n == 1 && buf[0] == 0x03),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    };
+    stream.set_nonblocking(false)?;
+    result
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><architecture>riscv:rv32</architecture>\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">",
+    );
+    for (i, name) in XREG_NAMES.iter().enumerate() {
+        let kind = match i {
+            1 => "code_ptr",
+            2 => "data_ptr",
+            _ => "int",
+        };
+        let _ = write!(
+            xml,
+            "<reg name=\"{}\" bitsize=\"32\" type=\"{}\" regnum=\"{}\"/>",
+            name, kind, i
+        );
+    }
+    xml.push_str(
+        "<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"32\"/></feature></target>",
+    );
+    xml
+}
+
+// registers go over the wire in target byte order
+fn hex_word(value: u32) -> String {
+    format!("{:08x}", value.swap_bytes())
+}
+fn parse_word(hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex, 16).ok().map(u32::swap_bytes)
+}
+// ADDR,LENGTH
+fn parse_range(args: &str) -> Option<(u32, u32)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u32::from_str_radix(addr, 16).ok()?,
+        u32::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+// the next $packet#checksum, acknowledged; none when gdb hung up
+fn read_packet(stream: &mut (impl Read + Write)) -> io::Result<Option<String>> {
+    let mut byte = [0; 1];
+    loop {
+        // skip acks and interrupts that came too late
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        let mut data = vec![];
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum)?;
+        let ok = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            == Some(data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)));
+        if ok {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        // ask for it again
+        stream.write_all(b"-")?;
+    }
+}
+fn write_packet(stream: &mut impl Write, data: &str) -> io::Result<()> {
+    let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    write!(stream, "${}#{:02x}", data, checksum)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn stub() -> Stub {
+        // 0:  addi x1, x0, 1
+        // 4:  add x5, x5, x1
+        // 8:  sw x5, 256(x0)
+        // c:  lw x6, 256(x0)
+        // 10: add x7, x6, x5
+        // 14: jal x1, 8
+        // 18: addi x0, x0, 0
+        // 1c: ecall
+        let insts: [u32; 8] = [
+            0x00100093, 0x001282b3, 0x10502023, 0x10002303, 0x005303b3, 0x008000ef, 0x00000013,
+            0x00000073,
+        ];
+        let pg = Program::from_words(&insts);
+        Stub::new(Rv32iBuilder::new(pg).slf_build())
+    }
+    #[test]
+    fn test_stub() {
+        let (mut stream, _gdb) = UnixStream::pair().unwrap();
+        let mut stub = stub();
+        assert_eq!(stub.handle("p20"), "00000000");
+        assert_eq!(stub.handle("g").len(), 33 * 8);
+        assert_eq!(stub.handle("m0,4"), "93001000");
+        assert_eq!(stub.resume(true, &mut stream).unwrap(), "S05");
+        assert_eq!(stub.debuggee.pc(), 4);
+        assert_eq!(stub.handle("p1"), "01000000");
+
+        assert_eq!(stub.handle("Z2,100,4"), "OK");
+        assert_eq!(stub.resume(false, &mut stream).unwrap(), "T05watch:100;");
+        assert_eq!(stub.debuggee.pc(), 0xc);
+        assert_eq!(stub.handle("m100,4"), "01000000");
+        assert_eq!(stub.handle("z2,100,4"), "OK");
+        // the load in flight has to see the new value
+        assert_eq!(stub.handle("M100,4:2a000000"), "OK");
+        assert_eq!(stub.handle("Z0,14,4"), "OK");
+        assert_eq!(stub.resume(false, &mut stream).unwrap(), "S05");
+        assert_eq!(stub.debuggee.pc(), 0x14);
+        assert_eq!(stub.handle("p7"), "2b000000");
+
+        // back to the add with another operand
+        assert_eq!(stub.handle("P5=02000000"), "OK");
+        assert_eq!(stub.handle("P20=10000000"), "OK");
+        assert_eq!(stub.debuggee.pc(), 0x10);
+        assert_eq!(stub.resume(true, &mut stream).unwrap(), "S05");
+        assert_eq!(stub.handle("p7"), "2c000000");
+        assert_eq!(stub.handle("P0=01000000"), "OK");
+        assert_eq!(stub.handle("p0"), "00000000");
+
+        assert_eq!(stub.resume(false, &mut stream).unwrap(), "W00");
+        assert_eq!(stub.handle("p1"), "18000000");
+    }
+    #[test]
+    fn test_precise() {
+        let (mut stream, _gdb) = UnixStream::pair().unwrap();
+        let mut stub = stub();
+        // the sw is in write back and already stored in MEM, but has not retired
+        assert_eq!(stub.handle("Z0,8,4"), "OK");
+        assert_eq!(stub.resume(false, &mut stream).unwrap(), "S05");
+        assert_eq!(stub.debuggee.pc(), 8);
+        assert_eq!(stub.handle("m100,4"), "00000000");
+        // squashed and fetched again, it stores the new x5 once it retires
+        assert_eq!(stub.handle("P5=05000000"), "OK");
+        assert_eq!(stub.handle("m100,4"), "00000000");
+        assert_eq!(stub.resume(true, &mut stream).unwrap(), "S05");
+        assert_eq!(stub.handle("m100,4"), "05000000");
+        // a write under the store is made before it
+        assert_eq!(stub.handle("P20=08000000"), "OK");
+        assert_eq!(stub.handle("M100,4:07000000"), "OK");
+        assert_eq!(stub.handle("m100,4"), "07000000");
+        assert_eq!(stub.resume(true, &mut stream).unwrap(), "S05");
+        assert_eq!(stub.handle("m100,4"), "05000000");
+    }
+    #[test]
+    fn test_limits() {
+        let mut stub = stub();
+        assert_eq!(stub.handle("m0,ffffffff").len(), PACKET_SIZE as usize);
+        // a watchpoint that runs past the end of memory still covers the store
+        let (mut stream, _gdb) = UnixStream::pair().unwrap();
+        assert_eq!(stub.handle("Z2,100,ffffffff"), "OK");
+        assert_eq!(stub.resume(false, &mut stream).unwrap(), "T05watch:100;");
+        // a packet that came garbled is asked for again
+        let (mut stream, mut gdb) = UnixStream::pair().unwrap();
+        gdb.write_all(b"$g#00$g#67").unwrap();
+        assert_eq!(read_packet(&mut stream).unwrap().as_deref(), Some("g"));
+        let mut acks = [0; 2];
+        gdb.read_exact(&mut acks).unwrap();
+        assert_eq!(&acks, b"-+");
+    }
+    #[test]
+    fn test_session() {
+        let (stream, mut gdb) = UnixStream::pair().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut replies = vec![];
+            for packet in ["qSupported:swbreak+", "?", "Z0,10,4", "c", "p20"] {
+                write_packet(&mut gdb, packet).unwrap();
+                let mut ack = [0; 1];
+                gdb.read_exact(&mut ack).unwrap();
+                assert_eq!(&ack, b"+");
+                replies.push(read_packet(&mut gdb).unwrap().unwrap());
+            }
+            write_packet(&mut gdb, "k").unwrap();
+            gdb.read_exact(&mut [0; 1]).unwrap();
+            replies
+        });
+        stub().run(stream).unwrap();
+        let replies = client.join().unwrap();
+        assert_eq!(
+            replies,
+            [
+                "PacketSize=4000;qXfer:features:read+",
+                "S05",
+                "OK",
+                "S05",
+                "10000000"
+            ]
+        );
+    }
+}
@@ -0,0 +1,466 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+
+use crate::config::{Format, FuzzArgs, RunArgs};
+use crate::script::Script;
+use crate::simulator::fuzz::{self, Failure, FuzzConfig, Generator};
+use crate::simulator::{
+    batch_path, Commit, Figure, Kanata, Lockstep, Mismatch, Rv32i, Stats, Vcd, XREG_NAMES,
+};
+
+#[derive(Debug, Serialize)]
+pub struct MemDump {
+    pub addr: u32,
+    pub words: Vec<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub halted: bool,
+    // a0 when the program halted
+    pub exit_code: Option<u32>,
+    pub cycles: usize,
+    pub retired: usize,
+    pub cpi: f64,
+    pub predictor: String,
+    // share of the conditional branches predicted right
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_accuracy: Option<f64>,
+    pub stats: Stats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regs: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mem: Vec<MemDump>,
+    // report of the first difference from the reference model in lockstep mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mismatch: Option<String>,
+    // why the script stopped the run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<String>,
+}
+impl Report {
+    pub fn new(rv: &Rv32i, halted: bool) -> Self {
+        Self {
+            halted,
+            exit_code: halted.then(|| rv.xregs.read(10)),
+            cycles: rv.cycle,
+            retired: rv.retired(),
+            cpi: rv.cpi(),
+            predictor: rv.pgbak.predictor.kind.clone(),
+            branch_accuracy: rv.stats.accuracy(),
+            stats: rv.stats.clone(),
+            regs: None,
+            mem: vec![],
+            mismatch: None,
+            stopped: None,
+        }
+    }
+    pub fn format(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_string(),
+            Format::Json => serde_json::to_string_pretty(self).unwrap(),
+        }
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.exit_code, &self.mismatch, &self.stopped) {
+            (Some(code), _, _) => writeln!(f, "exit code: {}", code)?,
+            (None, Some(_), _) => writeln!(f, "exit code: none (lockstep mismatch)")?,
+            (None, None, Some(reason)) => {
+                writeln!(f, "exit code: none (stopped by script: {})", reason)?
+            }
+            (None, None, None) => writeln!(f, "exit code: none (cycle limit reached)")?,
+        }
+        writeln!(f, "cycles: {}", self.cycles)?;
+        writeln!(f, "retired: {}", self.retired)?;
+        write!(f, "cpi: {:.3}", self.cpi)?;
+        for (name, count) in self.stats.counters() {
+            writeln!(f)?;
+            write!(f, "{}: {}", name, count)?;
+        }
+        writeln!(f)?;
+        write!(f, "predictor: {}", self.predictor)?;
+        if let Some(accuracy) = self.branch_accuracy {
+            writeln!(f)?;
+            write!(f, "branch_accuracy: {:.1}%", accuracy * 100.0)?;
+        }
+        if !self.stats.opcodes.is_empty() {
+            writeln!(f)?;
+            write!(f, "retired by opcode:")?;
+            for (op, count) in &self.stats.opcodes {
+                writeln!(f)?;
+                write!(f, "{:>8}: {}", op, count)?;
+            }
+        }
+        if let Some(regs) = &self.regs {
+            writeln!(f)?;
+            write!(f, "registers:")?;
+            for (i, (value, name)) in regs.iter().zip(XREG_NAMES).enumerate() {
+                if i % 4 == 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "{:>8}: {:08x} ", format!("x{}/{}", i, name), value)?;
+            }
+        }
+        for dump in &self.mem {
+            writeln!(f)?;
+            write!(f, "memory {:#010x}:", dump.addr)?;
+            for (i, word) in dump.words.iter().enumerate() {
+                if i % 4 == 0 {
+                    writeln!(f)?;
+                    write!(f, "{:08x}:", dump.addr as usize + i * 4)?;
+                }
+                write!(f, " {:08x}", word)?;
+            }
+        }
+        if let Some(mismatch) = &self.mismatch {
+            writeln!(f)?;
+            write!(f, "{}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+// the optional outputs and checks of a run, fed around every step
+struct Probes {
+    kanata: Option<Kanata<BufWriter<File>>>,
+    vcd: Option<Vcd<BufWriter<File>>>,
+    // where and which cycles the batch of snapshots goes
+    snapshots: Option<(String, Range<usize>)>,
+    commit_log: Option<BufWriter<File>>,
+    lockstep: Option<Lockstep>,
+    mismatch: Option<Box<Mismatch>>,
+    script: Option<Script>,
+    stopped: Option<String>,
+}
+impl Probes {
+    fn new(rv: &Rv32i, args: &RunArgs, script: Option<Script>) -> io::Result<Self> {
+        if args.lockstep && rv.cycle != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "lockstep has to start from the beginning of the program",
+            ));
+        }
+        let probes = Self {
+            kanata: match &args.kanata {
+                Some(path) => Some(Kanata::create(path, rv)?),
+                None => None,
+            },
+            vcd: match &args.vcd {
+                Some(path) => Some(Vcd::create(path, rv)?),
+                None => None,
+            },
+            snapshots: args.snapshot.clone().zip(args.snapshot_cycles.clone()),
+            commit_log: match &args.commit_log {
+                Some(path) => Some(BufWriter::new(File::create(path)?)),
+                None => None,
+            },
+            lockstep: args.lockstep.then(|| Lockstep::new(rv)),
+            mismatch: None,
+            script,
+            stopped: None,
+        };
+        probes.snapshot(rv)?;
+        Ok(probes)
+    }
+    fn snapshot(&self, rv: &Rv32i) -> io::Result<()> {
+        match &self.snapshots {
+            Some((path, cycles)) if cycles.contains(&rv.cycle) => {
+                Figure::new(rv).write(&batch_path(path, rv.cycle))
+            }
+            _ => Ok(()),
+        }
+    }
+    // check and log the halting instruction, which stays in write back
+    fn halt(&mut self, rv: &Rv32i) -> io::Result<()> {
+        if let Some(lockstep) = &mut self.lockstep {
+            if let Err(mismatch) = lockstep.check(rv) {
+                self.mismatch = Some(mismatch);
+                return Ok(());
+            }
+        }
+        if let (Some(out), Some(commit)) = (&mut self.commit_log, Commit::new(rv)) {
+            writeln!(out, "{}", commit)?;
+        }
+        Ok(())
+    }
+    // step once, unless the instruction about to retire disagrees with the reference model
+    fn step(&mut self, rv: &mut Rv32i) -> io::Result<()> {
+        if let Some(lockstep) = &mut self.lockstep {
+            if let Err(mismatch) = lockstep.check(rv) {
+                self.mismatch = Some(mismatch);
+                return Ok(());
+            }
+        }
+        let commit = Commit::new(rv);
+        if let (Some(out), Some(commit)) = (&mut self.commit_log, &commit) {
+            writeln!(out, "{}", commit)?;
+        }
+        rv.step();
+        if let Some(kanata) = &mut self.kanata {
+            kanata.record(rv)?;
+        }
+        if let Some(vcd) = &mut self.vcd {
+            vcd.record(rv)?;
+        }
+        self.snapshot(rv)?;
+        if let Some(script) = &mut self.script {
+            let result = script.cycle(rv, commit.as_ref());
+            // prints go to stderr to keep the report on stdout clean
+            for line in script.take_output() {
+                eprintln!("{}", line);
+            }
+            result.map_err(io::Error::other)?;
+            for edit in script.take_edits() {
+                edit.apply(rv).map_err(io::Error::other)?;
+            }
+            self.stopped = self.stopped.take().or(script.take_stop());
+        }
+        Ok(())
+    }
+    fn finish(self) -> io::Result<()> {
+        if let Some(kanata) = self.kanata {
+            kanata.finish()?;
+        }
+        if let Some(vcd) = self.vcd {
+            vcd.finish()?;
+        }
+        if let Some(mut out) = self.commit_log {
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// run the simulator until the program halts, the cycle limit is hit or the script stops it
+pub fn run(rv: &mut Rv32i, args: &RunArgs, script: Option<Script>) -> io::Result<Report> {
+    let mut probes = Probes::new(rv, args, script)?;
+    while !rv.halted()
+        && rv.cycle < args.max_cycles
+        && probes.mismatch.is_none()
+        && probes.stopped.is_none()
+    {
+        probes.step(rv)?;
+    }
+    if rv.halted() && probes.mismatch.is_none() {
+        probes.halt(rv)?;
+    }
+    let halted = rv.halted() && probes.mismatch.is_none();
+    let mut report = Report::new(rv, halted);
+    if halted {
+        // the halting instruction counts as retired without another clock edge,
+        // which would let the instructions after it write memory
+        report.retired += 1;
+        report.cpi = report.cycles as f64 / report.retired as f64;
+        report.stats.retire(rv);
+    }
+    report.mismatch = probes.mismatch.as_ref().map(|m| m.to_string());
+    report.stopped = probes.stopped.take();
+    if args.regs {
+        report.regs = Some((0..32).map(|i| rv.xregs.read(i)).collect());
+    }
+    report.mem = args
+        .mem
+        .iter()
+        .map(|&(addr, len)| MemDump {
+            addr,
+            words: (addr..addr + len)
+                .step_by(4)
+                .map(|a| rv.dmem.read(a as usize))
+                .collect(),
+        })
+        .collect();
+    if let (Some(path), None) = (&args.snapshot, &args.snapshot_cycles) {
+        Figure::new(rv).write(path)?;
+    }
+    probes.finish()?;
+    Ok(report)
+}
+
+// outcome of a fuzzing session, with a failing program shrunk to a reproducer
+#[derive(Debug)]
+pub struct FuzzReport {
+    // seed of the first program, the others follow it
+    pub seed: u64,
+    pub programs: usize,
+    pub failure: Option<(u64, Failure, Vec<u32>)>,
+}
+impl fmt::Display for FuzzReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} programs from seed {}", self.programs, self.seed)?;
+        match &self.failure {
+            Some((seed, failure, insts)) => {
+                writeln!(f, ", seed {} failed: {}", seed, failure)?;
+                write!(f, "{}", fuzz::listing(insts))
+            }
+            None => write!(f, " passed"),
+        }
+    }
+}
+
+// check the pipeline against the functional model on random programs,
+// stopping at the first that fails
+pub fn fuzz(args: &FuzzArgs) -> io::Result<FuzzReport> {
+    let config = FuzzConfig {
+        len: args.len,
+        max_distance: args.max_distance,
+        dependency: args.dependency,
+        branch_density: args.branch_density,
+        memory_density: args.memory_density,
+    };
+    let mut report = FuzzReport {
+        seed: args.seed.unwrap_or_else(rand::random),
+        programs: 0,
+        failure: None,
+    };
+    while report.programs < args.iterations && report.failure.is_none() {
+        let seed = report.seed.wrapping_add(report.programs as u64);
+        report.programs += 1;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let insts = Generator::new(&mut rng, &config).generate();
+        let Err(failure) = fuzz::check(&insts, args.max_cycles) else {
+            continue;
+        };
+        // only keep shrinking towards the same kind of failure
+        let timeout = matches!(failure, Failure::Timeout(_));
+        let insts = fuzz::shrink(&insts, |insts| match fuzz::check(insts, args.max_cycles) {
+            Err(Failure::Timeout(_)) => timeout,
+            Err(Failure::Mismatch(_)) => !timeout,
+            Ok(()) => false,
+        });
+        let failure = fuzz::check(&insts, args.max_cycles).unwrap_err();
+        if let Some(path) = &args.out {
+            std::fs::write(path, fuzz::listing(&insts))?;
+        }
+        report.failure = Some((seed, failure, insts));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn run_insts(insts: &[u32], max_cycles: usize) -> Report {
+        run_script(insts, max_cycles, None)
+    }
+    fn run_script(insts: &[u32], max_cycles: usize, script: Option<&str>) -> Report {
+        let script = script.map(|source| Script::new("test.rhai", source).unwrap());
+        run_args(insts, &args(max_cycles), script)
+    }
+    fn run_args(insts: &[u32], args: &RunArgs, script: Option<Script>) -> Report {
+        let pg = Program::from_words(insts);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        run(&mut rv, args, script).unwrap()
+    }
+    fn args(max_cycles: usize) -> RunArgs {
+        RunArgs {
+            max_cycles,
+            format: Format::Text,
+            regs: true,
+            mem: vec![(0, 8)],
+            diagram: None,
+            kanata: None,
+            snapshot: None,
+            snapshot_cycles: None,
+            vcd: None,
+            commit_log: None,
+            lockstep: true,
+        }
+    }
+    #[test]
+    fn test_run_halt() {
+        // addi a0, x0, 42
+        // sw a0, 4(x0)
+        // ecall
+        let report = run_insts(&[0x02a00513, 0x00a02223, 0x00000073], 100);
+        assert!(report.halted);
+        assert_eq!(report.exit_code, Some(42));
+        assert_eq!(report.retired, 3);
+        assert_eq!(report.cycles, 6);
+        assert_eq!(report.stats.opcodes["ecall"], 1);
+        assert_eq!(report.stats.rs2_from_ex_mem, 1);
+        assert_eq!(report.regs.unwrap()[10], 42);
+        assert_eq!(report.mem[0].words, vec![0, 42]);
+    }
+    #[test]
+    fn test_run_halt_younger() {
+        // addi a0, x0, 1
+        // ecall
+        // sw a0, 0(x0)
+        let report = run_insts(&[0x00100513, 0x00000073, 0x00a02023], 100);
+        assert_eq!(report.exit_code, Some(1));
+        assert_eq!(report.retired, 2);
+        assert_eq!(report.mem[0].words, vec![0, 0]);
+        assert_eq!(report.stats.rs2_from_mem_wb, 0);
+        assert_eq!(report.stats.opcodes.values().sum::<usize>(), 2);
+    }
+    #[test]
+    fn test_run_limit() {
+        // jal x0, 0
+        let report = run_insts(&[0x0000006f], 100);
+        assert!(!report.halted);
+        assert_eq!(report.exit_code, None);
+        assert_eq!(report.cycles, 100);
+    }
+    #[test]
+    fn test_run_script() {
+        // jal x0, 0
+        let source = r#"on_cycle(|cpu| if cpu.cycle == 42 { stop("enough") });"#;
+        let report = run_script(&[0x0000006f], 100, Some(source));
+        assert!(!report.halted);
+        assert_eq!(report.cycles, 42);
+        assert_eq!(report.stopped.as_deref(), Some("enough"));
+        assert!(report
+            .to_string()
+            .starts_with("exit code: none (stopped by script: enough)"));
+    }
+    #[test]
+    fn test_run_snapshots() {
+        let dir = std::env::temp_dir().join("rv-simulator-test-snapshots");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cycle.svg").to_str().unwrap().to_string();
+        let args = RunArgs {
+            snapshot: Some(path),
+            snapshot_cycles: Some(3..5),
+            ..args(100)
+        };
+        // addi a0, x0, 42; ecall
+        run_args(&[0x02a00513, 0x00000073], &args, None);
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["cycle-3.svg", "cycle-4.svg"]);
+        let svg = std::fs::read_to_string(dir.join("cycle-4.svg")).unwrap();
+        assert!(svg.contains("Cycle 4"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_fuzz() {
+        let args = FuzzArgs {
+            seed: Some(3),
+            iterations: 10,
+            len: 32,
+            max_distance: 2,
+            dependency: 0.8,
+            branch_density: 0.2,
+            memory_density: 0.3,
+            max_cycles: 10_000,
+            out: None,
+        };
+        let report = fuzz(&args).unwrap();
+        assert_eq!(report.programs, 10);
+        assert!(report.failure.is_none(), "{}", report);
+    }
+}
@@ -1,9 +1,29 @@
 mod circuit;
 mod common;
 mod config;
+pub mod dap;
+mod debuggee;
+pub mod gdb;
+pub mod headless;
+pub mod rpc;
+pub mod script;
 mod simulator;
 pub mod tui;
 pub use common::abi;
 pub use common::build;
 pub use config::init;
+pub use config::load_elf;
+pub use config::parse_args;
+pub use config::script_path;
+pub use config::tui_config;
+pub use config::Command;
+pub use config::PredictorConfig;
+pub use config::Program;
+pub use config::PREDICTORS;
+pub use simulator::load;
+pub use simulator::save;
+pub use simulator::Diagram;
+pub use simulator::Figure;
+pub use simulator::Iss;
+pub use simulator::Rv32i;
 pub use simulator::Rv32iBuilder;
@@ -1,8 +1,63 @@
+use rv_simulator::Command;
+
 fn main() -> std::io::Result<()> {
-    let pg = rv_simulator::init().unwrap();
-    let rv = rv_simulator::Rv32iBuilder::new(pg).slf_build();
+    let args = rv_simulator::parse_args();
+    // fuzzing makes its own programs, there is nothing to compile
+    if let Some(Command::Fuzz(fuzz)) = &args.command {
+        let report = rv_simulator::headless::fuzz(fuzz)?;
+        println!("{}", report);
+        std::process::exit(if report.failure.is_some() { 2 } else { 0 });
+    }
+    let mut rv = match &args.restore {
+        Some(path) => rv_simulator::load(path)?,
+        None => {
+            let pg = rv_simulator::init(&args).map_err(std::io::Error::other)?;
+            rv_simulator::Rv32iBuilder::new(pg).slf_build()
+        }
+    };
+    let script = match rv_simulator::script_path(&args).map_err(std::io::Error::other)? {
+        Some(path) => Some(rv_simulator::script::Script::load(&path)?),
+        None => None,
+    };
+    if let Some(Command::Run(run)) = &args.command {
+        let report = rv_simulator::headless::run(&mut rv, run, script)?;
+        if let Some(path) = &run.diagram {
+            rv_simulator::Diagram::new(&rv, 0..rv.cycle + 1).write(path)?;
+        }
+        println!("{}", report.format(run.format));
+        std::process::exit(
+            match (report.exit_code, &report.mismatch, &report.stopped) {
+                (Some(0), _, _) => 0,
+                // the program failed by its own account
+                (Some(_), _, _) => 3,
+                (None, Some(_), _) => 2,
+                // the script asked for the stop, so the run did what was wanted
+                (None, None, Some(_)) => 0,
+                (None, None, None) => 1,
+            },
+        );
+    }
+    if let Some(Command::Gdb(gdb)) = &args.command {
+        return rv_simulator::gdb::serve(rv, gdb);
+    }
+    if let Some(Command::Dap(dap)) = &args.command {
+        return rv_simulator::dap::serve(rv, dap);
+    }
+    if let Some(Command::Rpc(rpc)) = &args.command {
+        return rv_simulator::rpc::serve(rv, rpc);
+    }
+    let mut app = rv_simulator::tui::App::new(rv);
+    // a bad [tui] section is reported before the terminal is taken over
+    let config = rv_simulator::tui_config().map_err(std::io::Error::other)?;
+    app.set_config(&config).map_err(std::io::Error::other)?;
     let mut backend = rv_simulator::tui::init()?;
-    rv_simulator::tui::App::new(rv).run(&mut backend)?;
+    if let Some(path) = args.restore {
+        app.set_save_path(path);
+    }
+    if let Some(script) = script {
+        app.set_script(script);
+    }
+    app.run(&mut backend)?;
     rv_simulator::tui::restore()?;
     Ok(())
 }
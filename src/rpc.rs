@@ -0,0 +1,317 @@
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+
+use crate::config::RpcArgs;
+use crate::simulator::{signals, xreg_index, Rv32i};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// cycles one step or run_until may take
+const STEP_LIMIT: u64 = 1_000_000;
+// bytes one read_mem may return
+const READ_LIMIT: u32 = 0x10000;
+
+// serve line-delimited JSON-RPC 2.0 on stdin and stdout, or to one client on a socket
+pub fn serve(rv: Rv32i, args: &RpcArgs) -> io::Result<()> {
+    let mut rpc = Rpc { rv };
+    match (&args.unix, &args.listen) {
+        (Some(path), _) => {
+            let listener = UnixListener::bind(path)?;
+            eprintln!("waiting for a client on {}", path);
+            let (stream, _) = listener.accept()?;
+            let result = rpc.run(BufReader::new(stream.try_clone()?), stream);
+            std::fs::remove_file(path)?;
+            result
+        }
+        (None, Some(addr)) => {
+            let listener = TcpListener::bind(addr)?;
+            eprintln!("waiting for a client on {}", listener.local_addr()?);
+            let (stream, _) = listener.accept()?;
+            rpc.run(BufReader::new(stream.try_clone()?), stream)
+        }
+        (None, None) => rpc.run(io::stdin().lock(), io::stdout().lock()),
+    }
+}
+
+// an error code and message
+type Failure = (i64, String);
+
+pub struct Rpc {
+    pub rv: Rv32i,
+}
+impl Rpc {
+    pub fn run(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(reply) = self.handle(&line) {
+                writeln!(out, "{}", reply)?;
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+    // the reply to a request, none for a notification
+    pub fn handle(&mut self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error(Value::Null, (PARSE_ERROR, e.to_string()))),
+        };
+        let method = request["method"].as_str().unwrap_or_default();
+        let result = self.call(method, &request["params"]);
+        let id = request.get("id")?.clone();
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(failure) => error(id, failure),
+        })
+    }
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, Failure> {
+        match method {
+            "step" => {
+                let n = param(params, 0, "n")?.unwrap_or(1u64).min(STEP_LIMIT);
+                for _ in 0..n {
+                    if self.rv.halted() {
+                        break;
+                    }
+                    self.rv.step();
+                }
+                Ok(self.status())
+            }
+            "run_until" => self.run_until(params),
+            "read_reg" => {
+                let reg: Value = param(params, 0, "reg")?.ok_or(missing("reg"))?;
+                Ok(json!(self.rv.xregs.read(reg_index(&reg)?)))
+            }
+            "read_mem" => {
+                let addr: u32 = param(params, 0, "addr")?.ok_or(missing("addr"))?;
+                if !addr.is_multiple_of(4) {
+                    return Err(invalid(format!("{:#x} is not word aligned", addr)));
+                }
+                let len = param(params, 1, "len")?.unwrap_or(4u32).min(READ_LIMIT);
+                let mem = match param::<String>(params, 2, "space")?.as_deref() {
+                    None | Some("data") => &self.rv.dmem,
+                    Some("inst") => &self.rv.imem,
+                    Some(space) => return Err(invalid(format!("no memory {}", space))),
+                };
+                let words: Vec<u32> = (addr..addr.saturating_add(len))
+                    .step_by(4)
+                    .map(|a| mem.read(a as usize))
+                    .collect();
+                Ok(json!({ "addr": addr, "words": words }))
+            }
+            "signals" => {
+                let name: String = param(params, 0, "component")?.ok_or(missing("component"))?;
+                let (_, control) = self
+                    .rv
+                    .components()
+                    .into_iter()
+                    .find(|(n, _)| *n == name)
+                    .ok_or_else(|| invalid(format!("no component {}", name)))?;
+                let signals: Map<String, Value> = signals(control)
+                    .into_iter()
+                    .map(|(name, value)| (name, json!(value)))
+                    .collect();
+                Ok(Value::Object(signals))
+            }
+            "components" => Ok(json!(self
+                .rv
+                .components()
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>())),
+            "stats" => Ok(json!({
+                "cycle": self.rv.cycle,
+                "retired": self.rv.retired(),
+                "cpi": self.rv.cpi(),
+                "stats": self.rv.stats,
+            })),
+            "reset" => {
+                self.rv = self.rv.reset();
+                Ok(self.status())
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("no method {}", method))),
+        }
+    }
+    // the cycle, what retired and the address of the instruction in each stage
+    fn status(&self) -> Value {
+        json!({
+            "cycle": self.rv.cycle,
+            "retired": self.rv.retired(),
+            "halted": self.rv.halted(),
+            "stages": self.rv.asm.stages(),
+        })
+    }
+    // step at least once, then until the instruction at `pc` is in write back,
+    // `cycle` or `retired` is reached, or the program halts
+    fn run_until(&mut self, params: &Value) -> Result<Value, Failure> {
+        let pc: Option<u32> = param(params, 0, "pc")?;
+        let cycle: Option<usize> = param(params, 1, "cycle")?;
+        let retired: Option<usize> = param(params, 2, "retired")?;
+        let max_cycles = param(params, 3, "max_cycles")?
+            .unwrap_or(STEP_LIMIT)
+            .min(STEP_LIMIT) as usize;
+        let limit = self.rv.cycle.saturating_add(max_cycles);
+        let reason = loop {
+            if self.rv.halted() {
+                break "halted";
+            }
+            if self.rv.cycle >= limit {
+                break "max_cycles";
+            }
+            self.rv.step();
+            if pc.is_some() && self.rv.asm.stages()[4] == pc {
+                break "pc";
+            }
+            if cycle.is_some_and(|cycle| self.rv.cycle >= cycle) {
+                break "cycle";
+            }
+            if retired.is_some_and(|retired| self.rv.retired() >= retired) {
+                break "retired";
+            }
+        };
+        let mut status = self.status();
+        status["reason"] = json!(reason);
+        Ok(status)
+    }
+}
+
+fn error(id: Value, (code, message): Failure) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+fn invalid(message: String) -> Failure {
+    (INVALID_PARAMS, message)
+}
+fn missing(name: &str) -> Failure {
+    invalid(format!("missing {}", name))
+}
+// a parameter by position or by name, none if left out
+fn param<T: DeserializeOwned>(
+    params: &Value,
+    index: usize,
+    name: &str,
+) -> Result<Option<T>, Failure> {
+    let value = match params {
+        Value::Array(params) => params.get(index),
+        Value::Object(params) => params.get(name),
+        _ => None,
+    };
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| invalid(format!("{}: {}", name, e))),
+    }
+}
+// x0..x31 by index, by `x5` or by ABI name
+fn reg_index(reg: &Value) -> Result<usize, Failure> {
+    let index = match reg {
+        Value::Number(n) => n.as_u64().map(|n| n as usize),
+        Value::String(name) => xreg_index(name),
+        _ => None,
+    };
+    index
+        .filter(|&i| i < 32)
+        .ok_or_else(|| invalid(format!("no register {}", reg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn rpc() -> Rpc {
+        // 0: addi a0, x0, 42
+        // 4: sw a0, 4(x0)
+        // 8: ecall
+        let insts: [u32; 3] = [0x02a00513, 0x00a02223, 0x00000073];
+        let pg = Program::from_words(&insts);
+        Rpc {
+            rv: Rv32iBuilder::new(pg).slf_build(),
+        }
+    }
+    fn call(rpc: &mut Rpc, method: &str, params: Value) -> Value {
+        let line = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let reply = rpc.handle(&line.to_string()).unwrap();
+        assert_eq!(reply["id"], 1);
+        reply
+    }
+    #[test]
+    fn test_rpc() {
+        let mut rpc = rpc();
+        let status = &call(&mut rpc, "step", json!([2]))["result"];
+        assert_eq!(status["cycle"], 2);
+        assert_eq!(status["stages"][1], 4);
+        assert_eq!(status["stages"][4], Value::Null);
+        let status = &call(&mut rpc, "run_until", json!({ "pc": 4 }))["result"];
+        assert_eq!(status["reason"], "pc");
+        assert_eq!(status["cycle"], 5);
+        assert_eq!(call(&mut rpc, "read_reg", json!(["a0"]))["result"], 42);
+        assert_eq!(
+            call(&mut rpc, "read_reg", json!({ "reg": 10 }))["result"],
+            42
+        );
+        let status = &call(&mut rpc, "run_until", json!({}))["result"];
+        assert_eq!(status["reason"], "halted");
+        let cycle = status["cycle"].clone();
+        let status = &call(&mut rpc, "step", json!([u64::MAX]))["result"];
+        assert_eq!(status["cycle"], cycle);
+        let mem = &call(&mut rpc, "read_mem", json!([0, u32::MAX]))["result"];
+        assert_eq!(mem["words"].as_array().unwrap().len(), 0x4000);
+        let mem = &call(&mut rpc, "read_mem", json!({ "addr": 0, "len": 8 }))["result"];
+        assert_eq!(mem["words"], json!([0, 42]));
+        let mem = &call(&mut rpc, "read_mem", json!([0, 4, "inst"]))["result"];
+        assert_eq!(mem["words"], json!([0x02a00513]));
+        let signals = &call(&mut rpc, "signals", json!(["mem_wb"]))["result"];
+        assert_eq!(signals["rd_out"], 0);
+        assert!(signals.get("alu_res_in").is_some());
+        let stats = &call(&mut rpc, "stats", json!(null))["result"];
+        assert_eq!(stats["retired"], 2);
+        assert_eq!(stats["stats"]["opcodes"]["addi"], 1);
+        let status = &call(&mut rpc, "reset", json!(null))["result"];
+        assert_eq!(status["cycle"], 0);
+        assert_eq!(call(&mut rpc, "read_reg", json!(["x10"]))["result"], 0);
+    }
+    #[test]
+    fn test_rpc_errors() {
+        let mut rpc = rpc();
+        assert_eq!(
+            call(&mut rpc, "jump", json!([]))["error"]["code"],
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            call(&mut rpc, "read_reg", json!(["x32"]))["error"]["code"],
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            call(&mut rpc, "step", json!(["one"]))["error"]["code"],
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            call(&mut rpc, "signals", json!({}))["error"]["code"],
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            call(&mut rpc, "read_mem", json!([4093]))["error"]["code"],
+            INVALID_PARAMS
+        );
+        assert_eq!(rpc.handle("{").unwrap()["error"]["code"], PARSE_ERROR);
+        // a notification is run but not answered
+        assert!(rpc
+            .handle(r#"{"jsonrpc": "2.0", "method": "step", "params": [3]}"#)
+            .is_none());
+        assert_eq!(rpc.rv.cycle, 3);
+        let mut out = vec![];
+        let input = "{\"jsonrpc\": \"2.0\", \"id\": 7, \"method\": \"step\"}\n\n";
+        rpc.run(input.as_bytes(), &mut out).unwrap();
+        let reply: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["result"]["cycle"], 4);
+    }
+}
@@ -0,0 +1,396 @@
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, Map, AST, INT};
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+use crate::common::abi::*;
+use crate::simulator::{signals, xreg_index, Commit, Edit, Rv32i, Stats};
+
+// what the script asked for while it ran
+#[derive(Default)]
+struct State {
+    on_cycle: Vec<FnPtr>,
+    on_retire: Vec<FnPtr>,
+    stop: Option<String>,
+    output: Vec<String>,
+    edits: Vec<Edit>,
+}
+
+// the simulator as a script sees it after a cycle
+#[derive(Clone)]
+struct Cpu {
+    xregs: IndexPortRef,
+    imem: IndexPortRef,
+    dmem: IndexPortRef,
+    components: Vec<(&'static str, ControlRef)>,
+    stages: Vec<Option<u32>>,
+    cycle: usize,
+    retired: usize,
+    halted: bool,
+    stats: Rc<Stats>,
+}
+impl Cpu {
+    fn new(rv: &Rv32i) -> Self {
+        Self {
+            xregs: rv.xregs.clone(),
+            imem: rv.imem.clone(),
+            dmem: rv.dmem.clone(),
+            components: rv
+                .components()
+                .into_iter()
+                .map(|(name, control)| (name, control.clone()))
+                .collect(),
+            stages: rv.asm.stages(),
+            cycle: rv.cycle,
+            retired: rv.retired(),
+            halted: rv.halted(),
+            stats: Rc::new(rv.stats.clone()),
+        }
+    }
+    fn component(&self, name: &str) -> Result<&ControlRef, Box<EvalAltResult>> {
+        self.components
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, control)| control)
+            .ok_or_else(|| format!("no component {}", name).into())
+    }
+}
+
+fn unit_or(value: Option<u32>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, |v| Dynamic::from(v as INT))
+}
+fn reg_index(i: INT) -> Result<usize, Box<EvalAltResult>> {
+    match i {
+        0..=31 => Ok(i as usize),
+        _ => Err(format!("no register x{}", i).into()),
+    }
+}
+fn word_addr(addr: INT) -> Result<usize, Box<EvalAltResult>> {
+    match u32::try_from(addr) {
+        Ok(addr) if addr.is_multiple_of(4) => Ok(addr as usize),
+        Ok(_) => Err(format!("{:#x} is not word aligned", addr).into()),
+        Err(_) => Err(format!("no address {}", addr).into()),
+    }
+}
+fn reg_name(name: &str) -> Result<usize, Box<EvalAltResult>> {
+    xreg_index(name).ok_or_else(|| format!("no register {}", name).into())
+}
+
+// the retired instruction as a map
+fn inst(rv: &Rv32i, commit: &Commit) -> Map {
+    let mut map = Map::new();
+    map.insert("pc".into(), (commit.pc as INT).into());
+    map.insert("inst".into(), (commit.inst as INT).into());
+    map.insert("asm".into(), rv.disasm(commit.pc).into());
+    map.insert("rd".into(), unit_or(commit.rd.map(|(rd, _)| rd)));
+    map.insert("value".into(), unit_or(commit.rd.map(|(_, value)| value)));
+    map.insert("load".into(), unit_or(commit.load));
+    map.insert(
+        "store".into(),
+        unit_or(commit.store.map(|(addr, _, _)| addr)),
+    );
+    map
+}
+
+fn engine(state: &Rc<RefCell<State>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .register_type_with_name::<Cpu>("Cpu")
+        .register_get("cycle", |cpu: &mut Cpu| cpu.cycle as INT)
+        .register_get("retired", |cpu: &mut Cpu| cpu.retired as INT)
+        .register_get("halted", |cpu: &mut Cpu| cpu.halted)
+        .register_get("stats", |cpu: &mut Cpu| {
+            rhai::serde::to_dynamic(&*cpu.stats)
+        })
+        .register_fn("stage", |cpu: &mut Cpu, i: INT| {
+            unit_or(cpu.stages.get(i as usize).copied().flatten())
+        })
+        .register_fn("reg", |cpu: &mut Cpu, i: INT| {
+            reg_index(i).map(|i| cpu.xregs.read(i) as INT)
+        })
+        .register_fn("reg", |cpu: &mut Cpu, name: &str| {
+            reg_name(name).map(|i| cpu.xregs.read(i) as INT)
+        })
+        .register_fn("mem", |cpu: &mut Cpu, addr: INT| {
+            word_addr(addr).map(|addr| cpu.dmem.read(addr) as INT)
+        })
+        .register_fn("imem", |cpu: &mut Cpu, addr: INT| {
+            word_addr(addr).map(|addr| cpu.imem.read(addr) as INT)
+        })
+        .register_fn(
+            "signal",
+            |cpu: &mut Cpu, component: &str, name: &str| -> Result<INT, Box<EvalAltResult>> {
+                signals(cpu.component(component)?)
+                    .into_iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, value)| value as INT)
+                    .ok_or_else(|| format!("no signal {} in {}", name, component).into())
+            },
+        )
+        .register_fn(
+            "signals",
+            |cpu: &mut Cpu, component: &str| -> Result<Map, Box<EvalAltResult>> {
+                Ok(signals(cpu.component(component)?)
+                    .into_iter()
+                    .map(|(name, value)| (name.into(), (value as INT).into()))
+                    .collect())
+            },
+        );
+    let s = state.clone();
+    engine.register_fn("on_cycle", move |f: FnPtr| s.borrow_mut().on_cycle.push(f));
+    let s = state.clone();
+    engine.register_fn("on_retire", move |f: FnPtr| {
+        s.borrow_mut().on_retire.push(f)
+    });
+    let s = state.clone();
+    engine.register_fn("stop", move || {
+        s.borrow_mut().stop.get_or_insert_with(|| "stop()".into());
+    });
+    let s = state.clone();
+    engine.register_fn("stop", move |reason: &str| {
+        s.borrow_mut().stop.get_or_insert_with(|| reason.into());
+    });
+    // edits are made once the callbacks of the cycle have returned
+    let s = state.clone();
+    engine.register_fn("set_reg", move |_: &mut Cpu, i: INT, value: INT| {
+        let i = reg_index(i)?;
+        s.borrow_mut().edits.push(Edit::Reg(i, value as u32));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let s = state.clone();
+    engine.register_fn("set_reg", move |_: &mut Cpu, name: &str, value: INT| {
+        let i = reg_name(name)?;
+        s.borrow_mut().edits.push(Edit::Reg(i, value as u32));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let s = state.clone();
+    engine.register_fn("set_mem", move |_: &mut Cpu, addr: INT, value: INT| {
+        let edit = Edit::Mem(word_addr(addr)? as u32, value as u32);
+        s.borrow_mut().edits.push(edit);
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let s = state.clone();
+    engine.register_fn(
+        "set_latch",
+        move |_: &mut Cpu, component: &str, name: &str, value: INT| {
+            let edit = Edit::Latch(component.into(), name.into(), value as u32);
+            s.borrow_mut().edits.push(edit);
+        },
+    );
+    let s = state.clone();
+    engine.on_print(move |text| s.borrow_mut().output.push(text.into()));
+    let s = state.clone();
+    engine.on_debug(move |text, _, pos| s.borrow_mut().output.push(format!("{}: {}", pos, text)));
+    engine
+}
+
+// a Rhai script called back after every cycle and every retirement
+pub struct Script {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    state: Rc<RefCell<State>>,
+}
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script").field("path", &self.path).finish()
+    }
+}
+impl Script {
+    // compile the script and run its top level, which registers the callbacks
+    pub fn new(path: &str, source: &str) -> Result<Self, String> {
+        let state = Rc::new(RefCell::new(State::default()));
+        let engine = engine(&state);
+        let ast = engine
+            .compile(source)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        engine
+            .run_ast(&ast)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self {
+            path: path.to_string(),
+            engine,
+            ast,
+            state,
+        })
+    }
+    pub fn load(path: &str) -> io::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::new(path, &source).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    // call back after a cycle of `rv`, with what retired in it
+    pub fn cycle(&mut self, rv: &Rv32i, retired: Option<&Commit>) -> Result<(), String> {
+        let (on_cycle, on_retire) = {
+            let state = self.state.borrow();
+            (state.on_cycle.clone(), state.on_retire.clone())
+        };
+        if on_cycle.is_empty() && (on_retire.is_empty() || retired.is_none()) {
+            return Ok(());
+        }
+        let cpu = Cpu::new(rv);
+        let fail = |e: Box<EvalAltResult>| format!("{}: {}", self.path, e);
+        for f in &on_cycle {
+            let _: Dynamic = f
+                .call(&self.engine, &self.ast, (cpu.clone(),))
+                .map_err(fail)?;
+        }
+        if let Some(commit) = retired {
+            let inst = inst(rv, commit);
+            for f in &on_retire {
+                let _: Dynamic = f
+                    .call(&self.engine, &self.ast, (cpu.clone(), inst.clone()))
+                    .map_err(fail)?;
+            }
+        }
+        Ok(())
+    }
+    // why the script asked to stop, once
+    pub fn take_stop(&mut self) -> Option<String> {
+        self.state.borrow_mut().stop.take()
+    }
+    // what the script printed since the last call
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.state.borrow_mut().output)
+    }
+    // the changes the script asked for since the last call, in order
+    pub fn take_edits(&mut self) -> Vec<Edit> {
+        std::mem::take(&mut self.state.borrow_mut().edits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn rv() -> Rv32i {
+        // 0: lw a0, 0(x0)
+        // 4: addi a1, a0, 1
+        // 8: lw a2, 0(x0)
+        // c: addi a3, a2, 1
+        // 10: ecall
+        let insts: [u32; 5] = [0x00002503, 0x00150593, 0x00002603, 0x00160693, 0x00000073];
+        let pg = Program::from_words(&insts);
+        Rv32iBuilder::new(pg).slf_build()
+    }
+    // step until the program halts or the script stops it
+    fn run(rv: &mut Rv32i, script: &mut Script) -> Result<Option<String>, String> {
+        while !rv.halted() {
+            let commit = Commit::new(rv);
+            rv.step();
+            script.cycle(rv, commit.as_ref())?;
+            if let Some(stop) = script.take_stop() {
+                return Ok(Some(stop));
+            }
+        }
+        Ok(None)
+    }
+    #[test]
+    fn test_script() {
+        let mut rv = rv();
+        let source = r#"
+            let loads = 0;
+            on_retire(|cpu, inst| {
+                if inst.load != () {
+                    loads += 1;
+                    print(`${inst.asm} read ${inst.load}`);
+                }
+            });
+            on_cycle(|cpu| {
+                if cpu.stats.load_use_stalls == 2 {
+                    stop(`second load-use stall at cycle ${cpu.cycle}, ${loads} loads`);
+                }
+            });
+        "#;
+        let mut script = Script::new("test.rhai", source).unwrap();
+        let stop = run(&mut rv, &mut script).unwrap();
+        assert_eq!(rv.stats.load_use_stalls, 2);
+        assert_eq!(
+            stop.unwrap(),
+            format!("second load-use stall at cycle {}, 1 loads", rv.cycle)
+        );
+        let output = script.take_output();
+        assert_eq!(output.len(), 1);
+        assert!(output[0].ends_with("read 0"));
+        assert!(script.take_output().is_empty());
+        assert!(!rv.halted());
+    }
+    #[test]
+    fn test_script_access() {
+        let mut rv = rv();
+        let source = r#"
+            on_retire(|cpu, inst| {
+                if inst.pc == 4 {
+                    if cpu.reg("a1") != 1 || cpu.reg(11) != 1 || inst.rd != 11 {
+                        throw "bad a1";
+                    }
+                    if cpu.signal("mem_wb", "rd_in") != 12 || cpu.stage(4) != 8 || cpu.stage(3) != () {
+                        throw "bad pipeline";
+                    }
+                    stop();
+                }
+            });
+        "#;
+        let mut script = Script::new("test.rhai", source).unwrap();
+        assert_eq!(run(&mut rv, &mut script).unwrap().unwrap(), "stop()");
+        let source = r#"on_cycle(|cpu| cpu.signal("ex", "nothing"));"#;
+        let mut script = Script::new("bad.rhai", source).unwrap();
+        let err = run(&mut rv, &mut script).unwrap_err();
+        assert!(err.starts_with("bad.rhai: "), "{}", err);
+        assert!(err.contains("no signal nothing in ex"), "{}", err);
+        assert!(Script::new("syntax.rhai", "on_cycle(|cpu| ").is_err());
+    }
+    #[test]
+    fn test_script_edits() {
+        let mut rv = rv();
+        let source = r#"
+            on_cycle(|cpu| {
+                if cpu.cycle == 1 {
+                    cpu.set_reg("a0", 7);
+                    cpu.set_mem(0, -1);
+                    cpu.set_latch("if_id", "inst", 0x13);
+                    if cpu.reg("a0") != 0 { throw "edited too early"; }
+                }
+            });
+        "#;
+        let mut script = Script::new("test.rhai", source).unwrap();
+        rv.step();
+        script.cycle(&rv, None).unwrap();
+        let edits = script.take_edits();
+        assert_eq!(
+            edits,
+            [
+                Edit::Reg(10, 7),
+                Edit::Mem(0, u32::MAX),
+                Edit::Latch("if_id".into(), "inst".into(), 0x13)
+            ]
+        );
+        assert!(script.take_edits().is_empty());
+        let source = r#"on_cycle(|cpu| cpu.set_reg(32, 0));"#;
+        let mut script = Script::new("bad.rhai", source).unwrap();
+        assert!(script
+            .cycle(&rv, None)
+            .unwrap_err()
+            .contains("no register x32"));
+        let source = r#"on_cycle(|cpu| cpu.set_mem(4095, 1));"#;
+        let mut script = Script::new("bad.rhai", source).unwrap();
+        assert!(script
+            .cycle(&rv, None)
+            .unwrap_err()
+            .contains("0xfff is not word aligned"));
+        let source = r#"on_cycle(|cpu| cpu.mem(4094));"#;
+        let mut script = Script::new("bad.rhai", source).unwrap();
+        assert!(script
+            .cycle(&rv, None)
+            .unwrap_err()
+            .contains("0xffe is not word aligned"));
+        let source = r#"on_cycle(|cpu| cpu.imem(-4));"#;
+        let mut script = Script::new("bad.rhai", source).unwrap();
+        assert!(script
+            .cycle(&rv, None)
+            .unwrap_err()
+            .contains("no address -4"));
+    }
+}
@@ -1,4 +1,38 @@
+mod commit;
+mod diagram;
+mod figure;
+pub mod fuzz;
+mod history;
+mod isa;
+mod iss;
+mod kanata;
+mod lockstep;
 mod rv32i;
+mod save;
+mod stats;
 mod utils;
+mod vcd;
+pub use commit::Commit;
+pub use diagram::Cell;
+pub use diagram::Diagram;
+pub use figure::batch_path;
+pub use figure::Figure;
+pub use history::Edit;
+pub use history::History;
+pub use history::CHECKPOINT_INTERVAL;
+pub use isa::inst_format;
+pub use iss::Iss;
+pub use kanata::Kanata;
+pub use lockstep::Lockstep;
+pub use lockstep::Mismatch;
+pub use rv32i::decode;
+pub use rv32i::xreg_index;
 pub use rv32i::Rv32i;
 pub use rv32i::Rv32iBuilder;
+pub use rv32i::STACK_TOP;
+pub use rv32i::XREG_NAMES;
+pub use save::load;
+pub use save::save;
+pub use stats::Stats;
+pub use vcd::signals;
+pub use vcd::Vcd;
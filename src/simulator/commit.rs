@@ -0,0 +1,120 @@
+use std::fmt;
+
+use super::Rv32i;
+
+// what the instruction in write back does to the architectural state
+// when it retires on the next rising edge
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub pc: u32,
+    pub inst: u32,
+    // register written and its new value, writes to x0 are left out
+    pub rd: Option<(u32, u32)>,
+    // address read by a load
+    pub load: Option<u32>,
+    // address, value and size in bytes written by a store
+    pub store: Option<(u32, u32, u32)>,
+}
+impl Commit {
+    pub fn new(rv: &Rv32i) -> Option<Self> {
+        let pc = rv.asm.stages()[4]?;
+        let inst = rv.imem.read(pc as usize);
+        let wb = rv.mem_wb.inout();
+        let get = |name: &str| wb.iter().find(|s| s.0 == name).unwrap().2;
+        let rd = get("rd");
+        let rd = (get("reg_write") == 1 && rd != 0).then(|| {
+            let value = match get("wb_sel") {
+                0 => get("npc"),
+                1 => get("alu_res"),
+                _ => get("mem_data"),
+            };
+            (rd, value)
+        });
+        let addr = get("alu_res");
+        let (load, store) = match inst & 0b111_1111 {
+            0b000_0011 => (Some(addr), None),
+            0b010_0011 => {
+                let size = 1 << ((inst >> 12) & 0b11);
+                let mask = match size {
+                    4 => u32::MAX,
+                    _ => (1 << (size * 8)) - 1,
+                };
+                // the store was done in MEM, so memory already holds it
+                let value = rv.dmem.read(addr as usize) & mask;
+                (None, Some((addr, value, size)))
+            }
+            _ => (None, None),
+        };
+        Some(Self {
+            pc,
+            inst,
+            rd,
+            load,
+            store,
+        })
+    }
+}
+// a line of spike --log-commits, as machine mode on core 0
+impl fmt::Display for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "core   0: 3 0x{:08x} (0x{:08x})", self.pc, self.inst)?;
+        if let Some((rd, value)) = self.rd {
+            write!(f, " x{:<2} 0x{:08x}", rd, value)?;
+        }
+        if let Some(addr) = self.load {
+            write!(f, " mem 0x{:08x}", addr)?;
+        }
+        if let Some((addr, value, size)) = self.store {
+            write!(
+                f,
+                " mem 0x{:08x} 0x{:0width$x}",
+                addr,
+                value,
+                width = size as usize * 2
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    #[test]
+    fn test_commit() {
+        // 0:  addi a0, x0, 42
+        // 4:  sw a0, 4(x0)
+        // 8:  lw a1, 4(x0)
+        // c:  jal x0, 4
+        // 10: ecall
+        let insts = [
+            0x02a00513u32,
+            0x00a02223,
+            0x00402583,
+            0x0040006f,
+            0x00000073,
+        ];
+        let pg = Program::from_words(&insts);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        let mut log = vec![];
+        while log.len() < 5 {
+            if let Some(commit) = Commit::new(&rv) {
+                log.push(commit.to_string());
+            }
+            rv.step();
+        }
+        assert_eq!(
+            log,
+            [
+                "core   0: 3 0x00000000 (0x02a00513) x10 0x0000002a",
+                "core   0: 3 0x00000004 (0x00a02223) mem 0x00000004 0x0000002a",
+                "core   0: 3 0x00000008 (0x00402583) x11 0x0000002a mem 0x00000004",
+                "core   0: 3 0x0000000c (0x0040006f)",
+                "core   0: 3 0x00000010 (0x00000073)",
+            ]
+        );
+    }
+}
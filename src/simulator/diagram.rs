@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::ops::Range;
+
+use super::Rv32i;
+use crate::common::build::Slot;
+
+pub const STAGE_NAMES: [&str; 5] = ["IF", "ID", "EX", "MEM", "WB"];
+// cycles in a block of the text diagram
+pub const TEXT_CYCLES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cell {
+    Stage(usize),
+    // held in the same stage as the cycle before
+    Stall(usize),
+    // cleared from the pipeline before reaching write back
+    Flush,
+}
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Stage(stage) => write!(f, "{}", STAGE_NAMES[*stage]),
+            Cell::Stall(stage) => write!(f, "{}*", STAGE_NAMES[*stage]),
+            Cell::Flush => write!(f, "X"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagramRow {
+    pub id: usize,
+    pub pc: u32,
+    pub text: String,
+    // cycle of the first cell
+    pub start: usize,
+    pub cells: Vec<Cell>,
+}
+impl DiagramRow {
+    pub fn cell(&self, cycle: usize) -> Option<Cell> {
+        self.cells.get(cycle.checked_sub(self.start)?).copied()
+    }
+    pub fn label(&self) -> String {
+        format!("{:08x}  {}", self.pc, self.text)
+    }
+}
+
+// instructions in fetch order against the cycles they spent in each stage
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagram {
+    pub cycles: Range<usize>,
+    pub rows: Vec<DiagramRow>,
+}
+impl Diagram {
+    pub fn new(rv: &Rv32i, cycles: Range<usize>) -> Self {
+        let (start, trace) = rv.asm.trace(cycles);
+        let mut rows: Vec<DiagramRow> = vec![];
+        let mut index = HashMap::new();
+        let mut prev: &[Option<Slot>] = &[];
+        for (cycle, stages) in (start..).zip(&trace) {
+            for (stage, slot) in stages.iter().enumerate() {
+                let Some(slot) = slot else {
+                    continue;
+                };
+                let row = *index.entry(slot.id).or_insert_with(|| {
+                    rows.push(DiagramRow {
+                        id: slot.id,
+                        pc: slot.pc,
+                        text: rv.disasm(slot.pc),
+                        start: cycle,
+                        cells: vec![],
+                    });
+                    rows.len() - 1
+                });
+                let row = &mut rows[row];
+                if row.start + row.cells.len() != cycle {
+                    continue;
+                }
+                let stalled = prev.get(stage).copied().flatten() == Some(*slot);
+                row.cells.push(match stalled {
+                    true => Cell::Stall(stage),
+                    false => Cell::Stage(stage),
+                });
+            }
+            // anything before write back that left without moving on was flushed
+            for slot in prev.iter().take(4).flatten() {
+                if !stages.iter().flatten().any(|s| s.id == slot.id) {
+                    rows[index[&slot.id]].cells.push(Cell::Flush);
+                }
+            }
+            prev = stages;
+        }
+        rows.sort_by_key(|row| row.id);
+        Self {
+            cycles: start..start + trace.len(),
+            rows,
+        }
+    }
+    // the grid in blocks of TEXT_CYCLES cycles, each with only the rows that have a cell
+    // in it, so a long run does not make a grid of every row against every cycle
+    pub fn to_text(&self) -> String {
+        let label = self
+            .rows
+            .iter()
+            .map(|row| row.label().len())
+            .max()
+            .unwrap_or_default();
+        let width = self.cycles.end.to_string().len().max(4);
+        let mut blocks: BTreeMap<usize, Vec<&DiagramRow>> = BTreeMap::new();
+        for row in self.rows.iter().filter(|row| !row.cells.is_empty()) {
+            let last = row.start + row.cells.len() - 1;
+            for block in row.start / TEXT_CYCLES..=last / TEXT_CYCLES {
+                blocks.entry(block).or_default().push(row);
+            }
+        }
+        let mut out = String::new();
+        for (block, rows) in blocks {
+            let cycles = (block * TEXT_CYCLES).max(self.cycles.start)
+                ..((block + 1) * TEXT_CYCLES).min(self.cycles.end);
+            write!(out, "{:label$}", "").unwrap();
+            for cycle in cycles.clone() {
+                write!(out, " {:>width$}", cycle).unwrap();
+            }
+            for row in rows {
+                write!(out, "\n{:label$}", row.label()).unwrap();
+                let end = (row.start + row.cells.len()).min(cycles.end);
+                for cycle in cycles.start..end {
+                    let cell = row.cell(cycle).map(|c| c.to_string()).unwrap_or_default();
+                    write!(out, " {:>width$}", cell).unwrap();
+                }
+            }
+            out.push_str("\n\n");
+        }
+        out.push_str("* stalled, X flushed\n");
+        out
+    }
+    // a line per instruction with the cycle of its first cell and its cells from there
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("pc,instruction,start,stages\n");
+        for row in &self.rows {
+            write!(out, "{:08x},{},{}", row.pc, csv_field(&row.text), row.start).unwrap();
+            for cell in &row.cells {
+                write!(out, ",{}", cell).unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+    // csv when the path ends in .csv, plain text otherwise
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        match path.ends_with(".csv") {
+            true => fs::write(path, self.to_csv()),
+            false => fs::write(path, self.to_text()),
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::{History, Rv32iBuilder};
+
+    // a load-use stall, a taken jump that flushes two instructions and a halt,
+    // also the program of the Kanata test
+    pub(in crate::simulator) fn program() -> Program {
+        // 0:  lw x6, 0(x0)
+        // 4:  add x7, x6, x0
+        // 8:  jal x0, 8
+        // c:  addi x0, x0, 0
+        // 10: ecall
+        Program::from_words(&[0x00002303, 0x000303b3, 0x0080006f, 0x00000013, 0x00000073])
+    }
+    #[test]
+    fn test_diagram() {
+        let mut rv = Rv32iBuilder::new(program()).slf_build();
+        let mut history = History::new(&rv, 4);
+        history.seek(&mut rv, 10).unwrap();
+        let diagram = Diagram::new(&rv, 0..rv.cycle + 1);
+        assert_eq!(diagram.cycles, 0..11);
+        let cells = |i: usize| -> String {
+            let row = &diagram.rows[i];
+            let cells = row.cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+            format!("{}:{}", row.start, cells.join(" "))
+        };
+        assert_eq!(cells(0), "0:IF ID EX MEM WB");
+        // the add waits a cycle in ID for the load
+        assert_eq!(cells(1), "1:IF ID ID* EX MEM WB");
+        assert_eq!(cells(2), "2:IF IF* ID EX MEM WB");
+        // the jal is taken in EX, flushing the two fetched after it
+        assert_eq!(cells(3), "4:IF ID X");
+        assert_eq!(cells(4), "5:IF X");
+        assert_eq!(diagram.rows[5].pc, 0x10);
+        assert_eq!(cells(5), "6:IF ID EX MEM WB");
+        let text = diagram.to_text();
+        assert!(text.contains("00000004  000303b3   add"));
+        let csv = diagram.to_csv();
+        assert_eq!(csv.lines().count(), diagram.rows.len() + 1);
+        assert!(csv.starts_with("pc,instruction,start,stages\n"));
+        assert!(csv.contains("\n00000000,00002303   lw,0,IF,ID,EX,MEM,WB\n"));
+        assert!(csv.contains("\n00000010,00000073   ecall,5,IF,X\n"));
+        // going back drops the cycles after the checkpoint and replays them
+        history.seek(&mut rv, 7).unwrap();
+        assert_eq!(Diagram::new(&rv, 0..8).rows, Diagram::new(&rv, 0..20).rows);
+        history.seek(&mut rv, 10).unwrap();
+        assert_eq!(Diagram::new(&rv, 0..rv.cycle + 1), diagram);
+        // a longer run comes in blocks of TEXT_CYCLES cycles, each with its own header
+        history.seek(&mut rv, 100).unwrap();
+        let long = Diagram::new(&rv, 0..rv.cycle + 1);
+        let label = long.rows.iter().map(|row| row.label().len()).max().unwrap();
+        let text = long.to_text();
+        let header = " ".repeat(label);
+        assert_eq!(text.lines().filter(|l| l.starts_with(&header)).count(), 4);
+        assert!(text.lines().all(|l| l.len() <= label + TEXT_CYCLES * 5));
+    }
+}
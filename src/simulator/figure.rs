@@ -0,0 +1,320 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+use super::diagram::STAGE_NAMES;
+use super::{Rv32i, XREG_NAMES};
+
+// colours of the stages in the ASM table, as in the default TUI theme
+const STAGE_COLORS: [&str; 5] = ["green", "olive", "blue", "purple", "teal"];
+// pixels of a monospace character and a line in the SVG
+const CHAR_WIDTH: usize = 8;
+const LINE_HEIGHT: usize = 16;
+// the SVG starts a new row of tables past this width
+const SVG_WIDTH: usize = 1200;
+
+// a titled table of the figure
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+impl Section {
+    fn new(title: &str, header: &[&str], rows: Vec<Vec<String>>) -> Self {
+        Self {
+            title: title.to_string(),
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows,
+        }
+    }
+    // characters of each column, header included
+    fn widths(&self) -> Vec<usize> {
+        (0..self.header.len())
+            .map(|i| {
+                std::iter::once(&self.header)
+                    .chain(&self.rows)
+                    .map(|row| row.get(i).map_or(0, |cell| cell.chars().count()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+    // the header and rows as text with the columns lined up
+    fn lines(&self) -> Vec<String> {
+        let widths = self.widths();
+        std::iter::once(&self.header)
+            .chain(&self.rows)
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, &width)| format!("{:width$}", cell))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+// one cycle of the pipeline as a standalone page: the pipeline registers, the EX and
+// hazard signals, the register file and the instruction in each stage
+#[derive(Debug, Clone, PartialEq)]
+pub struct Figure {
+    pub cycle: usize,
+    pub sections: Vec<Section>,
+}
+impl Figure {
+    pub fn new(rv: &Rv32i) -> Self {
+        let hex = |value: u32| format!("{:x}", value);
+        let mut sections: Vec<Section> = [
+            ("if_id", "IF/ID"),
+            ("id_ex", "ID/EX"),
+            ("ex_mem", "EX/MEM"),
+            ("mem_wb", "MEM/WB"),
+        ]
+        .into_iter()
+        .map(|(name, title)| {
+            let (_, control) = rv
+                .components()
+                .into_iter()
+                .find(|(n, _)| *n == name)
+                .unwrap();
+            let rows = control
+                .inout()
+                .into_iter()
+                .map(|(name, in_, out)| vec![name.to_string(), hex(in_), hex(out)])
+                .collect();
+            Section::new(title, &["Name", "In", "Out"], rows)
+        })
+        .collect();
+        let signals = |signals: Vec<(&str, u32)>| {
+            signals
+                .into_iter()
+                .map(|(name, value)| vec![name.to_string(), hex(value)])
+                .collect()
+        };
+        sections.push(Section::new(
+            "EX",
+            &["Name", "Value"],
+            signals(rv.ex.inner_signal()),
+        ));
+        sections.push(Section::new(
+            "Hazard",
+            &["Name", "Value"],
+            signals(rv.hazard.output()),
+        ));
+        let regs = XREG_NAMES
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let value = rv.xregs.read(i);
+                vec![
+                    format!("x{}", i),
+                    name.to_string(),
+                    format!("{:08x}", value),
+                ]
+            })
+            .collect();
+        sections.push(Section::new("Registers", &["", "Name", "Value"], regs));
+        let stages = rv
+            .asm
+            .stages()
+            .into_iter()
+            .zip(STAGE_NAMES)
+            .map(|(pc, stage)| match pc {
+                Some(pc) => vec![stage.to_string(), format!("{:08x}", pc), rv.disasm(pc)],
+                None => vec![stage.to_string(), "-".to_string(), "bubble".to_string()],
+            })
+            .collect();
+        sections.push(Section::new("ASM", &["Stage", "PC", "Instruction"], stages));
+        Figure {
+            cycle: rv.cycle,
+            sections,
+        }
+    }
+    pub fn to_html(&self) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        writeln!(out, "<title>rv-simulator cycle {}</title>", self.cycle).unwrap();
+        out.push_str(
+            "<style>\n\
+             body { font-family: monospace; }\n\
+             main { display: flex; flex-wrap: wrap; gap: 1em; align-items: flex-start; }\n\
+             table { border: 1px solid; border-collapse: collapse; }\n\
+             caption { font-weight: bold; }\n\
+             th, td { padding: 0 0.5em; text-align: left; }\n",
+        );
+        for (stage, color) in STAGE_NAMES.iter().zip(STAGE_COLORS) {
+            writeln!(out, "tr.{} {{ color: {}; }}", stage, color).unwrap();
+        }
+        writeln!(
+            out,
+            "</style>\n</head>\n<body>\n<h1>Cycle {}</h1>\n<main>",
+            self.cycle
+        )
+        .unwrap();
+        for section in &self.sections {
+            writeln!(
+                out,
+                "<table>\n<caption>{}</caption>",
+                escape(&section.title)
+            )
+            .unwrap();
+            out.push_str("<tr>");
+            for cell in &section.header {
+                write!(out, "<th>{}</th>", escape(cell)).unwrap();
+            }
+            out.push_str("</tr>\n");
+            for row in &section.rows {
+                // the ASM rows are coloured by their stage
+                match section.title == "ASM" {
+                    true => write!(out, "<tr class=\"{}\">", escape(&row[0])).unwrap(),
+                    false => out.push_str("<tr>"),
+                }
+                for cell in row {
+                    write!(out, "<td>{}</td>", escape(cell)).unwrap();
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</main>\n</body>\n</html>\n");
+        out
+    }
+    // the tables as boxes of monospace text, left to right and wrapped at SVG_WIDTH
+    pub fn to_svg(&self) -> String {
+        let mut body = String::new();
+        let (mut x, mut y, mut row_height, mut width) = (0, 2 * LINE_HEIGHT, 0, 0);
+        for section in &self.sections {
+            let lines = section.lines();
+            let chars = lines
+                .iter()
+                .map(|line| line.chars().count())
+                .chain([section.title.len()])
+                .max()
+                .unwrap_or_default();
+            let (w, h) = ((chars + 2) * CHAR_WIDTH, (lines.len() + 2) * LINE_HEIGHT);
+            if x > 0 && x + w > SVG_WIDTH {
+                (x, y, row_height) = (0, y + row_height + LINE_HEIGHT, 0);
+            }
+            writeln!(
+                body,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+                x, y, w, h
+            )
+            .unwrap();
+            writeln!(
+                body,
+                "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">{}</text>",
+                x + CHAR_WIDTH,
+                y + LINE_HEIGHT,
+                escape(&section.title)
+            )
+            .unwrap();
+            for (i, line) in lines.iter().enumerate() {
+                let fill = match (section.title == "ASM", i) {
+                    (true, 1..) => STAGE_COLORS[i - 1],
+                    _ => "black",
+                };
+                writeln!(
+                    body,
+                    "<text x=\"{}\" y=\"{}\" fill=\"{}\" xml:space=\"preserve\">{}</text>",
+                    x + CHAR_WIDTH,
+                    y + (i + 2) * LINE_HEIGHT,
+                    fill,
+                    escape(line)
+                )
+                .unwrap();
+            }
+            x += w + CHAR_WIDTH;
+            width = width.max(x);
+            row_height = row_height.max(h);
+        }
+        let height = y + row_height + LINE_HEIGHT;
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             font-family=\"monospace\" font-size=\"13\">\n",
+            width, height
+        );
+        out.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+        writeln!(
+            out,
+            "<text x=\"0\" y=\"{}\" font-weight=\"bold\">Cycle {}</text>",
+            LINE_HEIGHT, self.cycle
+        )
+        .unwrap();
+        out.push_str(&body);
+        out.push_str("</svg>\n");
+        out
+    }
+    // svg when the path ends in .svg, html otherwise
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        match path.ends_with(".svg") {
+            true => fs::write(path, self.to_svg()),
+            false => fs::write(path, self.to_html()),
+        }
+    }
+}
+
+// the path of the figure of `cycle` in a batch, numbered before the extension
+pub fn batch_path(path: &str, cycle: usize) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.contains('/') => {
+            format!("{}-{}.{}", stem, cycle, ext)
+        }
+        _ => format!("{}-{}", path, cycle),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    #[test]
+    fn test_figure() {
+        // addi x1, x0, 1; add x5, x5, x1
+        let pg = Program::from_words(&[0x00100093, 0x001282b3]);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        for _ in 0..5 {
+            rv.step();
+        }
+        let figure = Figure::new(&rv);
+        let titles: Vec<_> = figure.sections.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "IF/ID",
+                "ID/EX",
+                "EX/MEM",
+                "MEM/WB",
+                "EX",
+                "Hazard",
+                "Registers",
+                "ASM"
+            ]
+        );
+        let section = |title| figure.sections.iter().find(|s| s.title == title).unwrap();
+        assert_eq!(section("Registers").rows[1], ["x1", "ra", "00000001"]);
+        assert_eq!(section("ASM").rows[4][..2], ["WB", "00000004"]);
+        assert_eq!(section("ASM").rows[3][..2], ["MEM", "00000008"]);
+        let html = figure.to_html();
+        assert!(html.contains("<h1>Cycle 5</h1>"));
+        assert!(html.contains("<tr class=\"WB\"><td>WB</td><td>00000004</td>"));
+        let svg = figure.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">x1   ra    00000001</text>"));
+        assert_eq!(batch_path("out/cycle.svg", 12), "out/cycle-12.svg");
+        assert_eq!(batch_path("./cycle", 3), "./cycle-3");
+        assert_eq!(escape("a<b & \"c\">"), "a&lt;b &amp; &quot;c&quot;&gt;");
+    }
+}
@@ -0,0 +1,366 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::fmt;
+
+use super::isa::mnemonic;
+use super::{Iss, Lockstep, Mismatch, Rv32iBuilder};
+use crate::common::abi::*;
+use crate::config::Program;
+
+// base of the data the generated loads and stores use, kept in x31
+const DATA_BASE: u32 = 0x0100_0000;
+const DATA_REG: u32 = 31;
+// holds the pc for the jalr that follows it
+const LINK_REG: u32 = 30;
+// registers the generated instructions write
+const REGS: u32 = 30;
+const ECALL: u32 = 0x0000_0073;
+const NOP: u32 = 0x0000_0013;
+
+fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | 0b011_0011
+}
+fn i_type(imm: i32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    ((imm as u32 & 0xFFF) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+fn s_type(imm: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 5) & 0x7F) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1F) << 7)
+        | 0b010_0011
+}
+fn b_type(imm: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 12) & 1) << 31)
+        | (((imm >> 5) & 0x3F) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | (((imm >> 1) & 0xF) << 8)
+        | (((imm >> 11) & 1) << 7)
+        | 0b110_0011
+}
+fn j_type(imm: i32, rd: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 20) & 1) << 31)
+        | (((imm >> 1) & 0x3FF) << 21)
+        | (((imm >> 11) & 1) << 20)
+        | (((imm >> 12) & 0xFF) << 12)
+        | (rd << 7)
+        | 0b110_1111
+}
+
+// shape of the generated programs
+#[derive(Debug, Clone)]
+pub struct FuzzConfig {
+    // instructions between the register setup and the final ecall
+    pub len: usize,
+    // how far back a dependent source register reaches, in instructions
+    pub max_distance: usize,
+    // chance that a source register is the destination of a recent instruction
+    pub dependency: f64,
+    // chance that an instruction is a branch or a jump
+    pub branch_density: f64,
+    // chance that an instruction is a load or a store
+    pub memory_density: f64,
+}
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            len: 64,
+            max_distance: 3,
+            dependency: 0.5,
+            branch_density: 0.1,
+            memory_density: 0.2,
+        }
+    }
+}
+
+// random valid rv32i programs: control flow only goes forward so every
+// program reaches the ecall at the end, memory accesses are aligned
+pub struct Generator<'a> {
+    rng: &'a mut StdRng,
+    config: &'a FuzzConfig,
+    body: Vec<u32>,
+    // destination register of every body instruction so far
+    rds: Vec<u32>,
+    // where the branches and jumps so far land
+    targets: Vec<usize>,
+}
+impl<'a> Generator<'a> {
+    pub fn new(rng: &'a mut StdRng, config: &'a FuzzConfig) -> Self {
+        Self {
+            rng,
+            config,
+            body: vec![],
+            rds: vec![],
+            targets: vec![],
+        }
+    }
+    pub fn generate(mut self) -> Vec<u32> {
+        let mut insts = vec![];
+        for rd in 1..REGS {
+            let imm = self.rng.gen_range(-2048..2048);
+            insts.push(i_type(imm, 0, 0b000, rd, 0b001_0011));
+        }
+        insts.push((DATA_BASE & 0xFFFF_F000) | (DATA_REG << 7) | 0b011_0111);
+        while self.body.len() < self.config.len {
+            let roll: f64 = self.rng.gen();
+            let inst = if roll < self.config.branch_density {
+                self.control()
+            } else if roll < self.config.branch_density + self.config.memory_density {
+                self.memory()
+            } else {
+                self.alu()
+            };
+            self.body.push(inst);
+        }
+        insts.extend(self.body);
+        insts.push(ECALL);
+        insts
+    }
+    fn rd(&mut self) -> u32 {
+        // x0 now and then, its writes must vanish
+        let rd = match self.rng.gen_bool(0.05) {
+            true => 0,
+            false => self.rng.gen_range(1..REGS),
+        };
+        self.rds.push(rd);
+        rd
+    }
+    fn rs(&mut self) -> u32 {
+        if self.rng.gen_bool(self.config.dependency) {
+            let distance = self.rng.gen_range(1..=self.config.max_distance.max(1));
+            if let Some(&rd) = self.rds.len().checked_sub(distance).map(|i| &self.rds[i]) {
+                return rd;
+            }
+        }
+        self.rng.gen_range(0..REGS)
+    }
+    fn alu(&mut self) -> u32 {
+        let (rs1, rs2) = (self.rs(), self.rs());
+        let rd = self.rd();
+        match self.rng.gen_range(0..4) {
+            0 => {
+                let (funct3, funct7) = [
+                    (0b000, 0),
+                    (0b000, 0b010_0000),
+                    (0b001, 0),
+                    (0b010, 0),
+                    (0b011, 0),
+                    (0b100, 0),
+                    (0b101, 0),
+                    (0b101, 0b010_0000),
+                    (0b110, 0),
+                    (0b111, 0),
+                ][self.rng.gen_range(0..10)];
+                r_type(funct7, rs2, rs1, funct3, rd)
+            }
+            1 => {
+                let shamt = self.rng.gen_range(0..32);
+                let (funct3, imm) = [(0b001, shamt), (0b101, shamt), (0b101, shamt | 0x400)]
+                    [self.rng.gen_range(0..3)];
+                i_type(imm, rs1, funct3, rd, 0b001_0011)
+            }
+            2 => {
+                let funct3 = [0b000, 0b010, 0b011, 0b100, 0b110, 0b111][self.rng.gen_range(0..6)];
+                i_type(self.rng.gen_range(-2048..2048), rs1, funct3, rd, 0b001_0011)
+            }
+            _ => {
+                let opcode = [0b011_0111, 0b001_0111][self.rng.gen_range(0..2)];
+                (self.rng.gen::<u32>() & 0xFFFF_F000) | (rd << 7) | opcode
+            }
+        }
+    }
+    fn memory(&mut self) -> u32 {
+        // lb, lh, lw, lbu and lhu, or sb, sh and sw
+        let funct3 = [0b000, 0b001, 0b010, 0b100, 0b101][self.rng.gen_range(0..5)];
+        let size = 1 << (funct3 & 0b11);
+        let offset = self.rng.gen_range(0..256 / size) * size;
+        if self.rng.gen_bool(0.5) {
+            let rd = self.rd();
+            i_type(offset, DATA_REG, funct3, rd, 0b000_0011)
+        } else {
+            let rs2 = self.rs();
+            self.rds.push(0);
+            s_type(offset, rs2, DATA_REG, funct3 & 0b11)
+        }
+    }
+    // a branch or jump forward to at most 8 instructions ahead, or the ecall
+    fn target(&mut self) -> i32 {
+        let left = (self.config.len - self.body.len()) as i32;
+        let offset = self.rng.gen_range(1..=left.min(8));
+        self.targets.push(self.body.len() + offset as usize);
+        offset * 4
+    }
+    fn control(&mut self) -> u32 {
+        let left = self.config.len - self.body.len();
+        match self.rng.gen_range(0..4) {
+            0 => {
+                let rd = self.rd();
+                j_type(self.target(), rd)
+            }
+            // auipc then jalr relative to it, the jalr needs the auipc forwarded,
+            // nothing may jump between the two
+            1 if left >= 3 && !self.targets.contains(&(self.body.len() + 1)) => {
+                self.rds.push(LINK_REG);
+                self.body.push((LINK_REG << 7) | 0b001_0111);
+                let rd = self.rd();
+                let target = self.target() + 4;
+                i_type(target, LINK_REG, 0b000, rd, 0b110_0111)
+            }
+            _ => {
+                let (rs1, rs2) = (self.rs(), self.rs());
+                self.rds.push(0);
+                let funct3 = [0b000, 0b001, 0b100, 0b101, 0b110, 0b111][self.rng.gen_range(0..6)];
+                b_type(self.target(), rs2, rs1, funct3)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Failure {
+    Mismatch(Box<Mismatch>),
+    // the pipeline did not reach the ecall within the cycles
+    Timeout(usize),
+}
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Mismatch(mismatch) => write!(f, "{}", mismatch),
+            Failure::Timeout(cycles) => write!(f, "did not halt within {} cycles", cycles),
+        }
+    }
+}
+
+// run `insts` on the pipeline in lockstep with the functional model
+pub fn check(insts: &[u32], max_cycles: usize) -> Result<(), Failure> {
+    let mut rv = Rv32iBuilder::new(Program::from_words(insts)).slf_build();
+    let mut lockstep = Lockstep::new(&rv);
+    while !rv.halted() {
+        if rv.cycle >= max_cycles {
+            // shrinking can leave a loop behind, a program that never ends is no test
+            return match halts(insts, max_cycles) {
+                true => Err(Failure::Timeout(max_cycles)),
+                false => Ok(()),
+            };
+        }
+        lockstep.check(&rv).map_err(Failure::Mismatch)?;
+        rv.step();
+    }
+    lockstep.check(&rv).map_err(Failure::Mismatch)
+}
+
+// whether the functional model reaches a halting instruction within `steps`
+fn halts(insts: &[u32], steps: usize) -> bool {
+    let mut iss = Iss::new(&Program::from_words(insts));
+    for _ in 0..steps {
+        if matches!(iss.imem.read(iss.pc as usize), 0 | ECALL | 0x0010_0073) {
+            return true;
+        }
+        iss.step();
+    }
+    false
+}
+
+// the smallest program found that still `fails`, by dropping ever smaller
+// chunks of instructions and then turning single ones into nops; the final
+// ecall always stays
+pub fn shrink(insts: &[u32], fails: impl Fn(&[u32]) -> bool) -> Vec<u32> {
+    let mut insts = insts.to_vec();
+    let mut chunk = (insts.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start + 1 < insts.len() {
+            let end = (start + chunk).min(insts.len() - 1);
+            let candidate = [&insts[..start], &insts[end..]].concat();
+            if fails(&candidate) {
+                insts = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        if chunk == 1 {
+            break;
+        }
+        chunk /= 2;
+    }
+    for i in 0..insts.len().saturating_sub(1) {
+        if insts[i] != NOP {
+            let mut candidate = insts.clone();
+            candidate[i] = NOP;
+            if fails(&candidate) {
+                insts = candidate;
+            }
+        }
+    }
+    insts
+}
+
+// the program as assembler input, so a reproducer can be rebuilt as is
+pub fn listing(insts: &[u32]) -> String {
+    insts
+        .iter()
+        .enumerate()
+        .map(|(i, inst)| {
+            format!(
+                "    .word 0x{:08x} # {:4x}: {}\n",
+                inst,
+                i * 4,
+                mnemonic(*inst)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_generate() {
+        let config = FuzzConfig::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        let insts = Generator::new(&mut rng, &config).generate();
+        assert_eq!(*insts.last().unwrap(), ECALL);
+        assert!(insts.len() >= config.len + REGS as usize);
+        assert!(insts.iter().all(|inst| mnemonic(*inst) != "unknown"));
+        let mut again = StdRng::seed_from_u64(1);
+        assert_eq!(Generator::new(&mut again, &config).generate(), insts);
+    }
+    #[test]
+    fn test_check() {
+        let config = FuzzConfig {
+            dependency: 0.9,
+            branch_density: 0.3,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..20 {
+            let insts = Generator::new(&mut rng, &config).generate();
+            if let Err(failure) = check(&insts, 10_000) {
+                panic!("{}\n{}", failure, listing(&insts));
+            }
+        }
+    }
+    #[test]
+    fn test_shrink() {
+        // fails whenever a sub follows an add somewhere later
+        let add = r_type(0, 2, 1, 0b000, 3);
+        let sub = r_type(0b010_0000, 2, 1, 0b000, 3);
+        let mut insts = vec![NOP; 40];
+        insts[7] = add;
+        insts[30] = sub;
+        insts.push(ECALL);
+        let fails = |insts: &[u32]| {
+            let add = insts.iter().position(|i| *i == add);
+            add.is_some_and(|a| insts[a..].contains(&sub))
+        };
+        assert_eq!(shrink(&insts, fails), vec![add, sub, ECALL]);
+    }
+}
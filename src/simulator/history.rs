@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::Rv32i;
+use crate::common::abi::*;
+
+pub const CHECKPOINT_INTERVAL: usize = 64;
+
+// a change made by hand between cycles
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Reg(usize, u32),
+    // a word of data memory
+    Mem(u32, u32),
+    // a field of a pipeline register, e.g. id_ex and rd
+    Latch(String, String, u32),
+}
+// fields of the pipeline registers that select a mux input or a register,
+// and how many there are to select from
+const LIMITS: [(&str, u32); 4] = [("pc_sel", 2), ("imm_sel", 2), ("wb_sel", 3), ("rd", 32)];
+
+impl Edit {
+    pub fn apply(&self, rv: &Rv32i) -> Result<(), String> {
+        match self {
+            Edit::Reg(i, value) if *i < 32 => rv.xregs.write(*i, *value),
+            Edit::Reg(i, _) => return Err(format!("no register x{}", i)),
+            Edit::Mem(addr, _) if !addr.is_multiple_of(4) => {
+                return Err(format!("{:#x} is not word aligned", addr))
+            }
+            Edit::Mem(addr, value) => rv.dmem.write(*addr as usize, *value),
+            Edit::Latch(component, name, value) => {
+                let (_, control) = rv
+                    .components()
+                    .into_iter()
+                    .find(|(n, _)| n == component)
+                    .ok_or_else(|| format!("no component {}", component))?;
+                if !control.inout().iter().any(|s| s.0 == name) {
+                    return Err(format!("no latch {} in {}", name, component));
+                }
+                if let Some((_, limit)) = LIMITS.iter().find(|(n, _)| n == name) {
+                    if *value >= *limit {
+                        return Err(format!(
+                            "{}.{} = {:#x} selects nothing, it takes 0 to {}",
+                            component,
+                            name,
+                            value,
+                            limit - 1
+                        ));
+                    }
+                }
+                // a branch in EX compares by br_type, which funct3 2 and 3 of
+                // slt and sltu leave without a comparison
+                if component == "id_ex" && (name == "br_type" || name == "npc_sel") {
+                    let latch = |n: &str| control.inout().into_iter().find(|s| s.0 == n);
+                    let (br_type, npc_sel) = match name.as_str() {
+                        "br_type" => (*value, latch("npc_sel").unwrap().2),
+                        _ => (latch("br_type").unwrap().2, *value),
+                    };
+                    if npc_sel != 0 && matches!(br_type & 0b111, 0b010 | 0b011) {
+                        return Err(format!(
+                            "{}.{} = {:#x} makes a branch of br_type {:#b}, which compares nothing",
+                            component, name, value, br_type
+                        ));
+                    }
+                }
+                if !control.set(name, *value) {
+                    return Err(format!("{}.{} cannot be set", component, name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Reg(i, value) => write!(f, "x{} = {:#x}", i, value),
+            Edit::Mem(addr, value) => write!(f, "*{:#x} = {:#x}", addr, value),
+            Edit::Latch(component, name, value) => {
+                write!(f, "{}.{} = {:#x}", component, name, value)
+            }
+        }
+    }
+}
+
+// keeps a snapshot of the simulator every `interval` cycles,
+// so that stepping backwards only replays from the nearest checkpoint.
+// edits are made again whenever a replay passes the cycle they were made at
+#[derive(Debug)]
+pub struct History {
+    interval: usize,
+    checkpoints: BTreeMap<usize, Snapshot>,
+    edits: BTreeMap<usize, Vec<Edit>>,
+}
+impl History {
+    pub fn new(rv: &Rv32i, interval: usize) -> Self {
+        let mut history = Self {
+            interval: interval.max(1),
+            checkpoints: BTreeMap::new(),
+            edits: BTreeMap::new(),
+        };
+        history.checkpoints.insert(rv.cycle, rv.snapshot());
+        history
+    }
+    pub fn record(&mut self, rv: &Rv32i) {
+        if rv.cycle.is_multiple_of(self.interval) {
+            self.checkpoints
+                .entry(rv.cycle)
+                .or_insert_with(|| rv.snapshot());
+        }
+    }
+    pub fn step(&mut self, rv: &mut Rv32i) {
+        rv.step();
+        self.replay(rv);
+        self.record(rv);
+    }
+    // change the state at the current cycle. what was recorded after it is
+    // dropped, since it happened without the edit
+    pub fn edit(&mut self, rv: &mut Rv32i, edit: Edit) -> Result<(), String> {
+        edit.apply(rv)?;
+        self.checkpoints.retain(|&cycle, _| cycle <= rv.cycle);
+        self.edits.retain(|&cycle, _| cycle <= rv.cycle);
+        self.edits.entry(rv.cycle).or_default().push(edit);
+        Ok(())
+    }
+    fn replay(&self, rv: &Rv32i) {
+        for edit in self.edits.get(&rv.cycle).into_iter().flatten() {
+            edit.apply(rv).expect("history: edit applied before");
+        }
+    }
+    // move the simulator to `cycle`, going back to the nearest checkpoint if needed.
+    // a restored simulator has no history before the cycle it was saved at
+    pub fn seek(&mut self, rv: &mut Rv32i, cycle: usize) -> Result<(), String> {
+        if cycle < rv.cycle {
+            let (_, snapshot) = self
+                .checkpoints
+                .range(..=cycle)
+                .next_back()
+                .ok_or_else(|| format!("no history before cycle {}", self.first()))?;
+            rv.restore(snapshot);
+            self.replay(rv);
+        }
+        while rv.cycle < cycle {
+            self.step(rv);
+        }
+        Ok(())
+    }
+    fn first(&self) -> usize {
+        *self
+            .checkpoints
+            .keys()
+            .next()
+            .expect("history: no checkpoint")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn program() -> Program {
+        // 0:  addi x1, x0, 1
+        // 4:  add x5, x5, x1
+        // 8:  sw x5, 0(x0)
+        // c:  lw x6, 0(x0)
+        // 10: add x7, x6, x5
+        // 14: jal x0, -16
+        let insts = [
+            0x00100093u32,
+            0x001282b3,
+            0x00502023,
+            0x00002303,
+            0x005303b3,
+            0xff1ff06f,
+        ];
+        Program::from_words(&insts)
+    }
+    #[test]
+    fn test_seek() {
+        let mut rv = Rv32iBuilder::new(program()).slf_build();
+        let mut history = History::new(&rv, 16);
+        history.seek(&mut rv, 200).unwrap();
+        let end = rv.snapshot();
+        history.seek(&mut rv, 37).unwrap();
+        let mut expect = rv.reset();
+        for _ in 0..37 {
+            expect.step();
+        }
+        assert_eq!(rv.cycle, 37);
+        assert_eq!(rv.snapshot(), expect.snapshot());
+        history.seek(&mut rv, 200).unwrap();
+        assert_eq!(rv.snapshot(), end);
+    }
+    #[test]
+    fn test_edit() {
+        let mut rv = Rv32iBuilder::new(program()).slf_build();
+        let mut history = History::new(&rv, 16);
+        history.seek(&mut rv, 100).unwrap();
+        let unedited = rv.snapshot();
+        history.seek(&mut rv, 40).unwrap();
+        history.edit(&mut rv, Edit::Reg(1, 3)).unwrap();
+        history.edit(&mut rv, Edit::Mem(0x100, 7)).unwrap();
+        history
+            .edit(&mut rv, Edit::Latch("id_ex".into(), "imm".into(), 5))
+            .unwrap();
+        assert_eq!(rv.xregs.read(1), 3);
+        assert_eq!(rv.dmem.read(0x100), 7);
+        let imm = rv.id_ex.inout().into_iter().find(|s| s.0 == "imm").unwrap();
+        assert_eq!(imm.2, 5);
+        history.seek(&mut rv, 100).unwrap();
+        let edited = rv.snapshot();
+        assert_ne!(edited, unedited);
+        // going back past the edits and forward again makes them again
+        history.seek(&mut rv, 10).unwrap();
+        assert_eq!(rv.xregs.read(1), 1);
+        history.seek(&mut rv, 40).unwrap();
+        assert_eq!(rv.xregs.read(1), 3);
+        history.seek(&mut rv, 100).unwrap();
+        assert_eq!(rv.snapshot(), edited);
+        // every field of every pipeline register can be set
+        for component in ["if_id", "id_ex", "ex_mem", "mem_wb"] {
+            let control = rv.components().into_iter().find(|c| c.0 == component);
+            let control = control.unwrap().1.clone();
+            let names: Vec<_> = control.inout().into_iter().map(|s| s.0).collect();
+            for (i, name) in names.iter().filter(|n| **n != "clr").enumerate() {
+                let edit = Edit::Latch(component.into(), name.to_string(), i as u32 % 2);
+                history.edit(&mut rv, edit).unwrap();
+            }
+            for (i, name) in names.iter().filter(|n| **n != "clr").enumerate() {
+                let field = control.inout().into_iter().find(|s| s.0 == *name);
+                assert_eq!(field.unwrap().2, i as u32 % 2, "{}.{}", component, name);
+            }
+        }
+        rv.step();
+        // a select signal out of range is refused, after the latches it depends on are set
+        for (component, name, value, setup) in [
+            ("mem_wb", "wb_sel", 3, &[][..]),
+            ("id_ex", "pc_sel", 2, &[]),
+            ("id_ex", "imm_sel", 100, &[]),
+            ("mem_wb", "rd", 32, &[]),
+            // a branch made to compare like slt, and slt made a branch
+            ("id_ex", "br_type", 2, &[("br_type", 0), ("npc_sel", 1)]),
+            ("id_ex", "npc_sel", 1, &[("npc_sel", 0), ("br_type", 3)]),
+        ] {
+            for (name, value) in setup {
+                let edit = Edit::Latch(component.into(), name.to_string(), *value);
+                history.edit(&mut rv, edit).unwrap();
+            }
+            let edit = Edit::Latch(component.into(), name.into(), value);
+            assert!(
+                history.edit(&mut rv, edit).is_err(),
+                "{}.{}",
+                component,
+                name
+            );
+        }
+        rv.step();
+        let clr = Edit::Latch("id_ex".into(), "clr".into(), 1);
+        assert_eq!(
+            history.edit(&mut rv, clr),
+            Err("id_ex.clr cannot be set".to_string())
+        );
+        rv.step();
+        assert!(history.edit(&mut rv, Edit::Reg(32, 0)).is_err());
+        assert_eq!(
+            history.edit(&mut rv, Edit::Mem(0xfff, 1)),
+            Err("0xfff is not word aligned".to_string())
+        );
+        assert!(history
+            .edit(&mut rv, Edit::Latch("id_ex".into(), "nothing".into(), 0))
+            .is_err());
+    }
+    #[test]
+    fn test_seek_restored() {
+        let mut rv = Rv32iBuilder::new(program()).slf_build();
+        for _ in 0..20 {
+            rv.step();
+        }
+        let mut history = History::new(&rv, 16);
+        assert_eq!(
+            history.seek(&mut rv, 10),
+            Err("no history before cycle 20".to_string())
+        );
+        assert_eq!(rv.cycle, 20);
+        history.seek(&mut rv, 40).unwrap();
+        history.seek(&mut rv, 20).unwrap();
+        assert_eq!(rv.cycle, 20);
+    }
+}
@@ -0,0 +1,103 @@
+// rv32i instruction set helpers, independent of the pipeline components
+
+pub fn mnemonic(inst: u32) -> &'static str {
+    let funct3 = (inst >> 12) & 0b111;
+    let funct7 = inst >> 25;
+    match inst & 0b111_1111 {
+        0b011_0111 => "lui",
+        0b001_0111 => "auipc",
+        0b110_1111 => "jal",
+        0b110_0111 => "jalr",
+        0b110_0011 => match funct3 {
+            0b000 => "beq",
+            0b001 => "bne",
+            0b100 => "blt",
+            0b101 => "bge",
+            0b110 => "bltu",
+            0b111 => "bgeu",
+            _ => "unknown",
+        },
+        0b000_0011 => match funct3 {
+            0b000 => "lb",
+            0b001 => "lh",
+            0b010 => "lw",
+            0b100 => "lbu",
+            0b101 => "lhu",
+            _ => "unknown",
+        },
+        0b010_0011 => match funct3 {
+            0b000 => "sb",
+            0b001 => "sh",
+            0b010 => "sw",
+            _ => "unknown",
+        },
+        0b001_0011 => match (funct3, funct7) {
+            (0b000, _) => "addi",
+            (0b010, _) => "slti",
+            (0b011, _) => "sltiu",
+            (0b100, _) => "xori",
+            (0b110, _) => "ori",
+            (0b111, _) => "andi",
+            (0b001, 0b000_0000) => "slli",
+            (0b101, 0b000_0000) => "srli",
+            (0b101, 0b010_0000) => "srai",
+            _ => "unknown",
+        },
+        0b011_0011 => match (funct3, funct7) {
+            (0b000, 0b000_0000) => "add",
+            (0b000, 0b010_0000) => "sub",
+            (0b001, 0b000_0000) => "sll",
+            (0b010, 0b000_0000) => "slt",
+            (0b011, 0b000_0000) => "sltu",
+            (0b100, 0b000_0000) => "xor",
+            (0b101, 0b000_0000) => "srl",
+            (0b101, 0b010_0000) => "sra",
+            (0b110, 0b000_0000) => "or",
+            (0b111, 0b000_0000) => "and",
+            _ => "unknown",
+        },
+        0b000_1111 => "fence",
+        0b111_0011 => match inst {
+            0x0000_0073 => "ecall",
+            0x0010_0073 => "ebreak",
+            _ => "unknown",
+        },
+        _ => "unknown",
+    }
+}
+
+// the encoding format, R I S B U or J
+pub fn inst_format(inst: u32) -> Option<char> {
+    match inst & 0b111_1111 {
+        0b011_0011 => Some('R'),
+        0b001_0011 | 0b000_0011 | 0b110_0111 | 0b111_0011 | 0b000_1111 => Some('I'),
+        0b010_0011 => Some('S'),
+        0b110_0011 => Some('B'),
+        0b011_0111 | 0b001_0111 => Some('U'),
+        0b110_1111 => Some('J'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mnemonic() {
+        assert_eq!(mnemonic(0xe5010113), "addi");
+        assert_eq!(mnemonic(0x1a812623), "sw");
+        assert_eq!(mnemonic(0x0280006f), "jal");
+        assert_eq!(mnemonic(0x40b50533), "sub");
+        assert_eq!(mnemonic(0x00000073), "ecall");
+        assert_eq!(mnemonic(0x00000000), "unknown");
+    }
+    #[test]
+    fn test_inst_format() {
+        assert_eq!(inst_format(0x40b50533), Some('R'));
+        assert_eq!(inst_format(0xe5010113), Some('I'));
+        assert_eq!(inst_format(0x1a812623), Some('S'));
+        assert_eq!(inst_format(0x0280006f), Some('J'));
+        assert_eq!(inst_format(0x00000000), None);
+    }
+}
@@ -0,0 +1,224 @@
+use super::{Commit, STACK_TOP};
+use crate::common::abi::*;
+use crate::common::build::Mem;
+use crate::config::Program;
+
+fn sext(value: u32, bits: u32) -> u32 {
+    (((value << (32 - bits)) as i32) >> (32 - bits)) as u32
+}
+
+// instruction level model of rv32i, the reference the pipeline is checked against
+#[derive(Debug)]
+pub struct Iss {
+    pub pc: u32,
+    pub x: [u32; 32],
+    pub imem: Mem,
+    pub dmem: Mem,
+    pub retired: usize,
+}
+impl Iss {
+    // loads the program like the pipeline does, text into imem and the data sections into dmem
+    pub fn new(pg: &Program) -> Self {
+        let mut x = [0; 32];
+        x[2] = STACK_TOP;
+        let mut dmem = Mem::with_data(0, vec![]);
+        for (addr, data) in &pg.data {
+            dmem.load(*addr, data);
+        }
+        Self {
+            pc: pg.entry as u32,
+            x,
+            imem: Mem::with_data(pg.start, pg.insts.clone()),
+            dmem,
+            retired: 0,
+        }
+    }
+    // execute the instruction at pc and report what it changed
+    pub fn step(&mut self) -> Commit {
+        let pc = self.pc;
+        let inst = self.imem.read(pc as usize);
+        let rd = (inst >> 7) & 0b11111;
+        let funct3 = (inst >> 12) & 0b111;
+        let rs1 = self.x[((inst >> 15) & 0b11111) as usize];
+        let rs2 = self.x[((inst >> 20) & 0b11111) as usize];
+        let funct7 = inst >> 25;
+        let imm_i = sext(inst >> 20, 12);
+        let imm_s = sext(((inst >> 25) << 5) | ((inst >> 7) & 0b11111), 12);
+        let imm_b = sext(
+            ((inst >> 31) << 12)
+                | (((inst >> 7) & 0b1) << 11)
+                | (((inst >> 25) & 0b11_1111) << 5)
+                | (((inst >> 8) & 0b1111) << 1),
+            13,
+        );
+        let imm_u = inst & 0xFFFF_F000;
+        let imm_j = sext(
+            ((inst >> 31) << 20)
+                | (((inst >> 12) & 0xFF) << 12)
+                | (((inst >> 20) & 0b1) << 11)
+                | (((inst >> 21) & 0x3FF) << 1),
+            21,
+        );
+        let mut npc = pc.wrapping_add(4);
+        let mut write = None;
+        let mut load = None;
+        let mut store = None;
+        match inst & 0b111_1111 {
+            0b011_0111 => write = Some(imm_u),
+            0b001_0111 => write = Some(pc.wrapping_add(imm_u)),
+            0b110_1111 => {
+                write = Some(npc);
+                npc = pc.wrapping_add(imm_j);
+            }
+            0b110_0111 => {
+                write = Some(npc);
+                npc = rs1.wrapping_add(imm_i) & !1;
+            }
+            0b110_0011 => {
+                let taken = match funct3 {
+                    0b000 => rs1 == rs2,
+                    0b001 => rs1 != rs2,
+                    0b100 => (rs1 as i32) < (rs2 as i32),
+                    0b101 => (rs1 as i32) >= (rs2 as i32),
+                    0b110 => rs1 < rs2,
+                    0b111 => rs1 >= rs2,
+                    _ => false,
+                };
+                if taken {
+                    npc = pc.wrapping_add(imm_b);
+                }
+            }
+            0b000_0011 => {
+                let addr = rs1.wrapping_add(imm_i);
+                let word = self.dmem.read(addr as usize);
+                write = match funct3 {
+                    0b000 => Some(sext(word & 0xFF, 8)),
+                    0b001 => Some(sext(word & 0xFFFF, 16)),
+                    0b010 => Some(word),
+                    0b100 => Some(word & 0xFF),
+                    0b101 => Some(word & 0xFFFF),
+                    _ => None,
+                };
+                load = Some(addr);
+            }
+            0b010_0011 => {
+                let addr = rs1.wrapping_add(imm_s);
+                let size = 1 << (funct3 & 0b11);
+                let mask = match size {
+                    4 => u32::MAX,
+                    _ => (1 << (size * 8)) - 1,
+                };
+                let word = self.dmem.read(addr as usize);
+                self.dmem
+                    .write(addr as usize, (word & !mask) | (rs2 & mask));
+                store = Some((addr, rs2 & mask, size));
+            }
+            op @ (0b001_0011 | 0b011_0011) => {
+                let imm = op == 0b001_0011;
+                let op2 = if imm { imm_i } else { rs2 };
+                let shamt = op2 & 0b11111;
+                write = match (funct3, funct7) {
+                    (0b000, 0b010_0000) if !imm => Some(rs1.wrapping_sub(op2)),
+                    (0b000, _) => Some(rs1.wrapping_add(op2)),
+                    (0b001, _) => Some(rs1 << shamt),
+                    (0b010, _) => Some(((rs1 as i32) < (op2 as i32)) as u32),
+                    (0b011, _) => Some((rs1 < op2) as u32),
+                    (0b100, _) => Some(rs1 ^ op2),
+                    (0b101, 0b010_0000) => Some(((rs1 as i32) >> shamt) as u32),
+                    (0b101, _) => Some(rs1 >> shamt),
+                    (0b110, _) => Some(rs1 | op2),
+                    (0b111, _) => Some(rs1 & op2),
+                    _ => None,
+                };
+            }
+            // fence, ecall and ebreak don't change any state here
+            _ => {}
+        }
+        let rd = write.filter(|_| rd != 0).map(|value| {
+            self.x[rd as usize] = value;
+            (rd, value)
+        });
+        self.pc = npc;
+        self.retired += 1;
+        Commit {
+            pc,
+            inst,
+            rd,
+            load,
+            store,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(insts: &[u32], steps: usize) -> Iss {
+        let pg = Program::from_words(insts);
+        let mut iss = Iss::new(&pg);
+        for _ in 0..steps {
+            iss.step();
+        }
+        iss
+    }
+    #[test]
+    fn test_alu() {
+        // addi x1, x0, -8
+        // addi x2, x0, 3
+        // sub x3, x1, x2
+        // sra x4, x1, x2
+        // srl x5, x1, x2
+        // slt x6, x1, x2
+        // sltu x7, x1, x2
+        // lui x8, 0x12345
+        let iss = run(
+            &[
+                0xff800093, 0x00300113, 0x402081b3, 0x4020d233, 0x0020d2b3, 0x0020a333, 0x0020b3b3,
+                0x12345437,
+            ],
+            8,
+        );
+        assert_eq!(iss.x[3] as i32, -11);
+        assert_eq!(iss.x[4] as i32, -1);
+        assert_eq!(iss.x[5], 0xffff_fff8 >> 3);
+        assert_eq!(iss.x[6], 1);
+        assert_eq!(iss.x[7], 0);
+        assert_eq!(iss.x[8], 0x1234_5000);
+    }
+    #[test]
+    fn test_mem() {
+        // addi x1, x0, -2
+        // sw x1, 8(x0)
+        // sb x0, 9(x0)
+        // lb x2, 8(x0)
+        // lhu x3, 8(x0)
+        // lw x4, 8(x0)
+        let mut iss = run(
+            &[
+                0xffe00093, 0x00102423, 0x000004a3, 0x00800103, 0x00805183, 0x00802203,
+            ],
+            2,
+        );
+        let commit = iss.step();
+        assert_eq!(commit.store, Some((9, 0, 1)));
+        for _ in 0..3 {
+            iss.step();
+        }
+        assert_eq!(iss.x[2] as i32, -2);
+        assert_eq!(iss.x[3], 0x00fe);
+        assert_eq!(iss.x[4], 0xffff_00fe);
+    }
+    #[test]
+    fn test_jump() {
+        // 0: jal x1, 8
+        // 4: addi x0, x0, 0
+        // 8: beq x0, x0, -8
+        let mut iss = run(&[0x008000ef, 0x00000013, 0xfe000ce3], 2);
+        assert_eq!(iss.x[1], 4);
+        assert_eq!(iss.pc, 0);
+        let commit = iss.step();
+        assert_eq!(commit.rd, Some((1, 4)));
+        assert_eq!(iss.pc, 8);
+    }
+}
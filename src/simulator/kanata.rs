@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use super::Rv32i;
+use crate::common::build::{Slot, Stage};
+
+// writes the Kanata log read by the Konata pipeline visualizer,
+// one `record` after every cycle
+pub struct Kanata<W: Write> {
+    out: W,
+    retired: usize,
+}
+impl Kanata<BufWriter<File>> {
+    pub fn create(path: &str, rv: &Rv32i) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), rv)
+    }
+}
+impl<W: Write> Kanata<W> {
+    // starts the log at the current cycle with the instructions already in flight
+    pub fn new(mut out: W, rv: &Rv32i) -> io::Result<Self> {
+        writeln!(out, "Kanata\t0004")?;
+        writeln!(out, "C=\t{}", rv.cycle)?;
+        let mut kanata = Self { out, retired: 0 };
+        let (_, trace) = rv.asm.trace(rv.cycle..rv.cycle + 1);
+        for (stage, slot) in trace[0].iter().enumerate() {
+            if let Some(slot) = slot {
+                kanata.fetch(rv, slot)?;
+                kanata.stage(slot, stage)?;
+            }
+        }
+        Ok(kanata)
+    }
+    // log what happened on the last step: stage transitions, stalls, flushes and retires
+    pub fn record(&mut self, rv: &Rv32i) -> io::Result<()> {
+        let (_, trace) = rv.asm.trace(rv.cycle.saturating_sub(1)..rv.cycle + 1);
+        let [prev, cur] = trace.as_slice() else {
+            return Ok(());
+        };
+        writeln!(self.out, "C\t1")?;
+        let find =
+            |stages: &[Option<Slot>], id| stages.iter().position(|s| s.is_some_and(|s| s.id == id));
+        for (stage, slot) in prev.iter().enumerate() {
+            let Some(slot) = slot else {
+                continue;
+            };
+            if stage == 4 {
+                writeln!(self.out, "R\t{}\t{}\t0", slot.id, self.retired)?;
+                self.retired += 1;
+            } else if find(cur, slot.id).is_none() {
+                writeln!(self.out, "R\t{}\t{}\t1", slot.id, slot.id)?;
+            }
+        }
+        for (stage, slot) in cur.iter().enumerate() {
+            let Some(slot) = slot else {
+                continue;
+            };
+            match find(prev, slot.id) {
+                None => {
+                    self.fetch(rv, slot)?;
+                    self.stage(slot, stage)?;
+                }
+                Some(before) if before == stage => writeln!(
+                    self.out,
+                    "L\t{}\t1\tstalled in {} at cycle {}\\n",
+                    slot.id,
+                    Stage::from(stage),
+                    rv.cycle
+                )?,
+                Some(_) => self.stage(slot, stage)?,
+            }
+        }
+        Ok(())
+    }
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+    fn fetch(&mut self, rv: &Rv32i, slot: &Slot) -> io::Result<()> {
+        writeln!(self.out, "I\t{}\t{}\t0", slot.id, slot.id)?;
+        writeln!(
+            self.out,
+            "L\t{}\t0\t{:08x}: {}",
+            slot.id,
+            slot.pc,
+            rv.disasm(slot.pc)
+        )
+    }
+    fn stage(&mut self, slot: &Slot, stage: usize) -> io::Result<()> {
+        writeln!(self.out, "S\t{}\t0\t{}", slot.id, Stage::from(stage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::diagram::tests::program;
+    use crate::simulator::Rv32iBuilder;
+
+    #[test]
+    fn test_kanata() {
+        let mut rv = Rv32iBuilder::new(program()).slf_build();
+        let mut kanata = Kanata::new(vec![], &rv).unwrap();
+        for _ in 0..10 {
+            rv.step();
+            kanata.record(&rv).unwrap();
+        }
+        let log = String::from_utf8(kanata.out).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..5],
+            [
+                "Kanata\t0004",
+                "C=\t0",
+                "I\t0\t0\t0",
+                "L\t0\t0\t00000000: 00002303   lw",
+                "S\t0\t0\tFetch",
+            ]
+        );
+        assert_eq!(lines.iter().filter(|l| l.starts_with("C\t")).count(), 10);
+        // the load retires first, the two fetched after the jal are flushed
+        let retires = lines
+            .iter()
+            .filter(|l| l.starts_with("R\t"))
+            .collect::<Vec<_>>();
+        assert_eq!(retires[0], &"R\t0\t0\t0");
+        assert!(retires.contains(&&"R\t3\t3\t1"));
+        assert!(retires.contains(&&"R\t4\t4\t1"));
+        assert!(lines.contains(&"L\t1\t1\tstalled in Decode at cycle 3\\n"));
+    }
+}
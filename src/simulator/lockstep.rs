@@ -0,0 +1,134 @@
+use std::fmt;
+
+use super::{Commit, Iss, Rv32i, XREG_NAMES};
+
+// the first retirement where the pipeline and the reference model disagree
+#[derive(Debug)]
+pub struct Mismatch {
+    pub cycle: usize,
+    // instructions retired by both before this one
+    pub retired: usize,
+    pub expected: Commit,
+    pub found: Commit,
+    // registers before the instruction, as (index, expected, found) where they differ
+    pub regs: Vec<(usize, u32, u32)>,
+}
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "lockstep mismatch at cycle {} after {} retired instructions",
+            self.cycle, self.retired
+        )?;
+        writeln!(f, "expected: {}", self.expected)?;
+        write!(f, "found:    {}", self.found)?;
+        let (expected, found) = (&self.expected, &self.found);
+        if expected.pc != found.pc {
+            write!(f, "\npc differs")?;
+        }
+        if expected.rd != found.rd {
+            write!(f, "\nregister write differs")?;
+        }
+        if expected.store != found.store {
+            write!(f, "\nmemory write differs")?;
+        }
+        for (i, expected, found) in &self.regs {
+            write!(
+                f,
+                "\nx{}/{}: expected {:08x}, found {:08x}",
+                i, XREG_NAMES[*i], expected, found
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// runs the reference model one instruction per retirement of the pipeline
+#[derive(Debug)]
+pub struct Lockstep {
+    pub iss: Iss,
+}
+impl Lockstep {
+    // both have to start from the loaded program
+    pub fn new(rv: &Rv32i) -> Self {
+        Self {
+            iss: Iss::new(&rv.pgbak),
+        }
+    }
+    // call before every step of the pipeline, checks the instruction that is about to retire
+    pub fn check(&mut self, rv: &Rv32i) -> Result<(), Box<Mismatch>> {
+        let Some(found) = Commit::new(rv) else {
+            return Ok(());
+        };
+        let regs = (0..32)
+            .map(|i| (i, self.iss.x[i], rv.xregs.read(i)))
+            .filter(|(_, expected, found)| expected != found)
+            .collect();
+        let retired = self.iss.retired;
+        let expected = self.iss.step();
+        if expected.pc == found.pc && expected.rd == found.rd && expected.store == found.store {
+            return Ok(());
+        }
+        Err(Box::new(Mismatch {
+            cycle: rv.cycle,
+            retired,
+            expected,
+            found,
+            regs,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn rv32i(insts: &[u32]) -> Rv32i {
+        let pg = Program::from_words(insts);
+        Rv32iBuilder::new(pg).slf_build()
+    }
+    fn run(rv: &mut Rv32i, lockstep: &mut Lockstep) -> Result<(), Box<Mismatch>> {
+        while !rv.halted() {
+            lockstep.check(rv)?;
+            rv.step();
+        }
+        lockstep.check(rv)
+    }
+    #[test]
+    fn test_lockstep() {
+        // 0:  addi x1, x0, 1
+        // 4:  add x5, x5, x1
+        // 8:  sw x5, 0(x0)
+        // c:  lw x6, 0(x0)
+        // 10: add x7, x6, x5
+        // 14: jal x1, 8
+        // 18: addi x0, x0, 0
+        // 1c: ecall
+        let insts = [
+            0x00100093, 0x001282b3, 0x00502023, 0x00002303, 0x005303b3, 0x008000ef, 0x00000013,
+            0x00000073,
+        ];
+        let mut rv = rv32i(&insts);
+        let mut lockstep = Lockstep::new(&rv);
+        assert!(run(&mut rv, &mut lockstep).is_ok());
+        assert_eq!(lockstep.iss.x[7], 2);
+        assert_eq!(lockstep.iss.x[1], 0x18);
+        assert_eq!(lockstep.iss.retired, 7);
+
+        // the same program against a model that starts with x5 = 1
+        let mut rv = rv32i(&insts);
+        let mut lockstep = Lockstep::new(&rv);
+        lockstep.iss.x[5] = 1;
+        let mismatch = run(&mut rv, &mut lockstep).unwrap_err();
+        assert_eq!(mismatch.retired, 1);
+        assert_eq!(mismatch.expected.pc, 4);
+        assert_eq!(mismatch.expected.rd, Some((5, 2)));
+        assert_eq!(mismatch.found.rd, Some((5, 1)));
+        assert_eq!(mismatch.regs, vec![(5, 1, 0)]);
+        let report = mismatch.to_string();
+        assert!(report.contains("register write differs"));
+        assert!(report.contains("x5/t0: expected 00000001, found 00000000"));
+    }
+}
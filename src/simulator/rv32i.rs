@@ -1,6 +1,8 @@
 use crate::common::abi::*;
 use crate::common::build::*;
 use crate::config::Program;
+use crate::simulator::isa::mnemonic;
+use crate::simulator::Stats;
 mod ex_stage;
 mod hazard;
 mod id_stage;
@@ -14,9 +16,11 @@ use ex_stage::ExStageBuilder;
 use hazard::Alloc as HazardAlloc;
 use hazard::Connect as HazardConnect;
 use hazard::HazardBuilder;
+pub use id_stage::decode;
 use id_stage::Alloc as IdAlloc;
 use id_stage::Connect as IdConnect;
 use id_stage::IdStageBuilder;
+use id_stage::IndexAlloc as IdIndexAlloc;
 use if_stage::Alloc as IfAlloc;
 use if_stage::Connect as IfConnect;
 use if_stage::IfStageBuilder;
@@ -25,6 +29,23 @@ use sep_reg::build::*;
 use wb_stage::Alloc as WbAlloc;
 use wb_stage::Connect as WbConnect;
 use wb_stage::WbStageBuilder;
+
+// initial stack pointer
+pub const STACK_TOP: u32 = 0x7FFF_FFF0;
+
+pub const XREG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+// x0..x31 by `x5` or by ABI name
+pub fn xreg_index(name: &str) -> Option<usize> {
+    name.strip_prefix('x')
+        .and_then(|n| n.parse().ok())
+        .or_else(|| XREG_NAMES.iter().position(|n| *n == name))
+        .or((name == "fp").then_some(8))
+        .filter(|&i| i < 32)
+}
 pub struct Rv32iBuilder {
     pub if_stage: IfStageBuilder,
     pub id_stage: IdStageBuilder,
@@ -44,12 +65,20 @@ impl Rv32iBuilder {
     fn connect(pg: crate::config::Program) -> Self {
         // let inst_mem = inst_mem.into_iter().flat_map(|x| x.to_ne_bytes()).collect();
         let mut consts = ConstsBuilder::default();
-        let mut if_stage = IfStageBuilder::new(pg.entry as u32, pg.start as u32, pg.insts.clone());
+        let mut if_stage = IfStageBuilder::new(
+            pg.entry as u32,
+            pg.start as u32,
+            pg.insts.clone(),
+            &pg.predictor,
+        );
         let mut if_id = IfIdBuilder::default();
-        let mut id_stage = IdStageBuilder::new(0x7FFFFFF0);
+        let mut id_stage = IdStageBuilder::new(STACK_TOP);
         let mut id_ex = IdExBuilder::default();
         let mut ex_stage = ExStageBuilder::new();
         let mut mem_stage = MemStageBuilder::default();
+        for (addr, data) in &pg.data {
+            mem_stage.load(*addr, data);
+        }
         let mut wb_stage = WbStageBuilder::default();
         let mut hazard = HazardBuilder::new();
         let mut ex_mem = ExMemBuilder::default();
@@ -60,6 +89,7 @@ impl Rv32iBuilder {
         //set up id-ex register
         id_ex.connect(if_id.alloc(IfIdAlloc::Npc), IdExConnect::Npc);
         id_ex.connect(if_id.alloc(IfIdAlloc::Pc), IdExConnect::Pc);
+        id_ex.connect(if_id.alloc(IfIdAlloc::Pred), IdExConnect::Pred);
         //set up ex stage
         ex_stage.connect(id_ex.alloc(IdExAlloc::Jal_), ExConnect::Jal_);
         ex_stage.connect(id_ex.alloc(IdExAlloc::BranchEn), ExConnect::BranchEn);
@@ -73,6 +103,8 @@ impl Rv32iBuilder {
         ex_stage.connect(id_ex.alloc(IdExAlloc::Imm), ExConnect::Imm);
         ex_stage.connect(id_ex.alloc(IdExAlloc::Rs1), ExConnect::Rs1);
         ex_stage.connect(id_ex.alloc(IdExAlloc::Rs2), ExConnect::Rs2);
+        ex_stage.connect(id_ex.alloc(IdExAlloc::Pred), ExConnect::Pred);
+        ex_stage.connect(id_ex.alloc(IdExAlloc::Npc), ExConnect::Npc);
         //set up ex-mem register
         ex_mem.connect(id_ex.alloc(IdExAlloc::RegWrite), ExMemConnect::RegWrite);
         ex_mem.connect(id_ex.alloc(IdExAlloc::WbSel), ExMemConnect::WbSel);
@@ -80,11 +112,13 @@ impl Rv32iBuilder {
         ex_mem.connect(id_ex.alloc(IdExAlloc::Npc), ExMemConnect::Npc);
         ex_mem.connect(id_ex.alloc(IdExAlloc::Rd), ExMemConnect::Rd);
         ex_mem.connect(id_ex.alloc(IdExAlloc::LoadSignal), ExMemConnect::MemRead);
+        ex_mem.connect(id_ex.alloc(IdExAlloc::BranchType), ExMemConnect::Funct3);
         //set up mem stage
         mem_stage.connect(ex_mem.alloc(ExMemAlloc::MemWrite), MemStageConnect::WriteEn);
         mem_stage.connect(ex_mem.alloc(ExMemAlloc::AluRes), MemStageConnect::Addr);
         mem_stage.connect(ex_mem.alloc(ExMemAlloc::Rs2Data), MemStageConnect::Data);
         mem_stage.connect(ex_mem.alloc(ExMemAlloc::MemRead), MemStageConnect::ReadEn);
+        mem_stage.connect(ex_mem.alloc(ExMemAlloc::Funct3), MemStageConnect::Width);
         //set up mem-wb register
         mem_wb.connect(ex_mem.alloc(ExMemAlloc::RegWrite), MemWbConnect::RegWrite);
         mem_wb.connect(ex_mem.alloc(ExMemAlloc::WbSel), MemWbConnect::WbSel);
@@ -144,17 +178,21 @@ impl Rv32iBuilder {
         //set up mem-wb register
         mem_wb.connect(mem_stage.alloc(MemStageAlloc::Out), MemWbConnect::MemData);
         //set up hazard unit
-        hazard.connect(ex_stage.alloc(ExAlloc::BranchSel), HazardConnect::NpcSel);
+        hazard.connect(ex_stage.alloc(ExAlloc::Redirect), HazardConnect::NpcSel);
         //third try connect
         //set up if stage
-        if_stage.connect(ex_stage.alloc(ExAlloc::BranchSel), IfConnect::NpcSel);
-        if_stage.connect(ex_stage.alloc(ExAlloc::AluRes), IfConnect::Npc);
+        if_stage.connect(ex_stage.alloc(ExAlloc::Redirect), IfConnect::NpcSel);
+        if_stage.connect(ex_stage.alloc(ExAlloc::Target), IfConnect::Npc);
+        if_stage.connect(id_ex.alloc(IdExAlloc::Pc), IfConnect::ExPc);
+        if_stage.connect(id_ex.alloc(IdExAlloc::BranchEn), IfConnect::ExBranch);
+        if_stage.connect(ex_stage.alloc(ExAlloc::BranchSel), IfConnect::ExTaken);
         //set up if-id register
         if_id.connect(if_stage.alloc(IfAlloc::Npc), IfIdConnect::Npc);
         if_id.connect(if_stage.alloc(IfAlloc::Pc), IfIdConnect::Pc);
         if_id.connect(if_stage.alloc(IfAlloc::Imem), IfIdConnect::Instruction);
+        if_id.connect(if_stage.alloc(IfAlloc::Pred), IfIdConnect::Pred);
         //set up consts
-        if_id.connect(ex_stage.alloc(ExAlloc::BranchSel), IfIdConnect::Clear);
+        if_id.connect(ex_stage.alloc(ExAlloc::Redirect), IfIdConnect::Clear);
         id_ex.connect(consts.alloc(ConstsAlloc::Out(1)), IdExConnect::Enable);
         id_ex.connect(hazard.alloc(HazardAlloc::IdExClear), IdExConnect::Clear);
         ex_mem.connect(consts.alloc(ConstsAlloc::Out(1)), ExMemConnect::Ebable);
@@ -167,7 +205,7 @@ impl Rv32iBuilder {
         asm.connect(hazard.alloc(HazardAlloc::PcEnable), AsmConnect::IfEn);
         asm.connect(hazard.alloc(HazardAlloc::IfIdEnable), AsmConnect::IdEn);
         asm.connect(hazard.alloc(HazardAlloc::IdExClear), AsmConnect::ExClr);
-        asm.connect(ex_stage.alloc(ExAlloc::BranchSel), AsmConnect::IdClr);
+        asm.connect(ex_stage.alloc(ExAlloc::Redirect), AsmConnect::IdClr);
         //build
         Self {
             if_stage,
@@ -187,8 +225,11 @@ impl Rv32iBuilder {
     pub fn new(pg: Program) -> Self {
         Self::connect(pg)
     }
-    pub fn slf_build(self) -> Rv32i {
+    pub fn slf_build(mut self) -> Rv32i {
         Rv32i {
+            xregs: self.id_stage.index_alloc(IdIndexAlloc::Xregs),
+            imem: self.if_stage.imem.index_alloc(MemIndexAlloc::Mem),
+            dmem: self.mem_stage.index_alloc(MemIndexAlloc::Mem),
             if_stage: self.if_stage.build(),
             id_stage: self.id_stage.build(),
             mem_stage: self.mem_stage.build(),
@@ -200,6 +241,8 @@ impl Rv32iBuilder {
             hazard: self.hazard.build(),
             asm: self.asm.build(),
             pgbak: self.pgbak,
+            cycle: 0,
+            stats: Stats::default(),
         }
     }
 }
@@ -217,14 +260,108 @@ pub struct Rv32i {
     pub mem_wb: ControlRef,
     pub hazard: ControlRef,
     pub asm: AsmPortRef,
+    pub xregs: IndexPortRef,
+    pub imem: IndexPortRef,
+    pub dmem: IndexPortRef,
+    pub cycle: usize,
+    pub stats: Stats,
 }
 impl Rv32i {
     pub fn reset(&self) -> Rv32i {
         Rv32iBuilder::connect(self.pgbak.clone()).slf_build()
     }
+    // the top level components, by the name of their field
+    pub fn components(&self) -> [(&'static str, &ControlRef); 9] {
+        [
+            ("if_stage", &self.if_stage),
+            ("if_id", &self.if_id),
+            ("id_stage", &self.id_stage),
+            ("id_ex", &self.id_ex),
+            ("ex", &self.ex),
+            ("ex_mem", &self.ex_mem),
+            ("mem_stage", &self.mem_stage),
+            ("mem_wb", &self.mem_wb),
+            ("hazard", &self.hazard),
+        ]
+    }
+    // squash every instruction in flight and fetch from `pc` next, e.g. after the
+    // registers were changed under instructions that already read them
+    pub fn redirect(&mut self, pc: u32) {
+        let empty = Rv32iBuilder::connect(Program {
+            entry: pc as usize,
+            data: vec![],
+            ..self.pgbak.clone()
+        })
+        .slf_build();
+        for (reg, empty) in [
+            (&self.if_id, &empty.if_id),
+            (&self.id_ex, &empty.id_ex),
+            (&self.ex_mem, &empty.ex_mem),
+            (&self.mem_wb, &empty.mem_wb),
+            (&self.hazard, &empty.hazard),
+        ] {
+            reg.restore(&empty.snapshot());
+        }
+        // keep the instruction memory, only the pc register starts over
+        let mut if_stage = self.if_stage.snapshot().group().to_vec();
+        if_stage[0] = empty.if_stage.snapshot().group()[0].clone();
+        self.if_stage.restore(&Snapshot::Group(if_stage));
+        let Snapshot::Asm {
+            retired,
+            next_id,
+            cycle,
+            ..
+        } = self.asm.snapshot()
+        else {
+            unreachable!()
+        };
+        self.asm.restore(&Snapshot::Asm {
+            stages: vec![Some((next_id, pc)), None, None, None, None],
+            set: vec![pc],
+            retired,
+            next_id: next_id + 1,
+            cycle,
+        });
+    }
+    pub fn step(&mut self) {
+        self.rasing_edge();
+        self.falling_edge();
+    }
+    pub fn retired(&self) -> usize {
+        self.asm.retired()
+    }
+    // objdump line of the instruction at `pc`, or its raw word and mnemonic
+    pub fn disasm(&self, pc: u32) -> String {
+        match self.asm.disasm(pc) {
+            Some(asm) => asm.trim().to_string(),
+            None => {
+                let inst = self.imem.read(pc as usize);
+                format!("{:08x}   {}", inst, mnemonic(inst))
+            }
+        }
+    }
+    // cycles per retired instruction, zero until the first one retires
+    pub fn cpi(&self) -> f64 {
+        match self.retired() {
+            0 => 0.0,
+            retired => self.cycle as f64 / retired as f64,
+        }
+    }
+    // the instruction in write back is ecall, ebreak or the all-zero illegal
+    // instruction, which is what a program returning from its entry runs into
+    pub fn halted(&self) -> bool {
+        self.asm.stages()[4].is_some_and(|pc| self.halts(pc))
+    }
+    // whether the instruction at `pc` halts the program once it reaches write back
+    pub fn halts(&self, pc: u32) -> bool {
+        matches!(self.imem.read(pc as usize), 0 | 0x0000_0073 | 0x0010_0073)
+    }
 }
 impl Control for Rv32i {
     fn rasing_edge(&mut self) {
+        let mut stats = std::mem::take(&mut self.stats);
+        stats.observe(self);
+        self.stats = stats;
         self.if_stage.rasing_edge();
         self.if_id.rasing_edge();
         self.id_stage.rasing_edge();
@@ -245,5 +382,38 @@ impl Control for Rv32i {
         self.mem_wb.falling_edge();
         self.asm.falling_edge();
         self.hazard.falling_edge();
+        self.cycle += 1;
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.if_stage.snapshot(),
+            self.id_stage.snapshot(),
+            self.mem_stage.snapshot(),
+            self.if_id.snapshot(),
+            self.id_ex.snapshot(),
+            self.ex_mem.snapshot(),
+            self.mem_wb.snapshot(),
+            self.hazard.snapshot(),
+            self.asm.snapshot(),
+            Snapshot::Counter(self.cycle),
+            self.stats.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.if_stage.restore(&group[0]);
+        self.id_stage.restore(&group[1]);
+        self.mem_stage.restore(&group[2]);
+        self.if_id.restore(&group[3]);
+        self.id_ex.restore(&group[4]);
+        self.ex_mem.restore(&group[5]);
+        self.mem_wb.restore(&group[6]);
+        self.hazard.restore(&group[7]);
+        self.asm.restore(&group[8]);
+        let Snapshot::Counter(cycle) = group[9] else {
+            panic!("Rv32i: invalid snapshot {:?}", group[9]);
+        };
+        self.cycle = cycle;
+        self.stats.restore(&group[10]);
     }
 }
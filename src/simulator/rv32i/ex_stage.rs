@@ -16,6 +16,10 @@ pub enum Alloc {
     BranchSel = 0,
     AluRes = 1,
     Rs2Data = 2,
+    // the branch or jump went the other way than fetch predicted
+    Redirect = 3,
+    // where fetch goes on a redirect: the target if taken, npc if not
+    Target = 4,
 }
 pub enum Connect {
     Jal_ = 0,
@@ -36,12 +40,15 @@ pub enum Connect {
     RdWb = 15,
     RdWbWrite = 16,
     RdWbData = 17,
+    Pred = 18,
+    Npc = 19,
 }
 pub struct ExStageBuilder {
     pub fwd_mux_1: MuxBuilder,
     pub fwd_mux_2: MuxBuilder,
     pub pc_sel: MuxBuilder,
     pub imm_sel: MuxBuilder,
+    pub target: MuxBuilder,
     pub branch: BranchBuilder,
     pub forward: ForwardBuilder,
     pub alu: AluBuilder,
@@ -52,6 +59,7 @@ impl ExStageBuilder {
         let mut fwd_mux_2 = MuxBuilder::default();
         let mut pc_sel = MuxBuilder::default();
         let mut imm_sel = MuxBuilder::default();
+        let mut target = MuxBuilder::default();
         let mut branch = BranchBuilder::default();
         let mut forward = ForwardBuilder::default();
         let mut alu = AluBuilder::default();
@@ -63,11 +71,14 @@ impl ExStageBuilder {
         alu.connect(imm_sel.alloc(MuxAlloc::Out), AluConnect::Op2);
         imm_sel.connect(fwd_mux_2.alloc(MuxAlloc::Out), MuxConnect::In(0));
         fwd_mux_2.connect(forward.alloc(ForwardAlloc::Forward2), MuxConnect::Select);
+        target.connect(branch.alloc(BranchAlloc::BK), MuxConnect::Select);
+        target.connect(alu.alloc(AluAlloc::Res), MuxConnect::In(1));
         ExStageBuilder {
             fwd_mux_1,
             fwd_mux_2,
             pc_sel,
             imm_sel,
+            target,
             branch,
             forward,
             alu,
@@ -86,6 +97,7 @@ impl ControlBuilder for ExStageBuilder {
             fwd_mux_2: self.fwd_mux_2.build(),
             pc_sel: self.pc_sel.build(),
             imm_sel: self.imm_sel.build(),
+            target: self.target.build(),
             branch: self.branch.build(),
             forward: self.forward.build(),
             alu: self.alu.build(),
@@ -101,6 +113,8 @@ impl PortBuilder for ExStageBuilder {
             Alloc::BranchSel => self.branch.alloc(BranchAlloc::BK),
             Alloc::AluRes => self.alu.alloc(AluAlloc::Res),
             Alloc::Rs2Data => self.fwd_mux_2.alloc(MuxAlloc::Out),
+            Alloc::Redirect => self.branch.alloc(BranchAlloc::Miss),
+            Alloc::Target => self.target.alloc(MuxAlloc::Out),
         }
     }
     fn connect(&mut self, pin: PortRef, id: Connect) {
@@ -129,6 +143,8 @@ impl PortBuilder for ExStageBuilder {
                 self.fwd_mux_1.connect(pin.clone(), MuxConnect::In(2));
                 self.fwd_mux_2.connect(pin, MuxConnect::In(2));
             }
+            Connect::Pred => self.branch.connect(pin, BranchConnect::Pred),
+            Connect::Npc => self.target.connect(pin, MuxConnect::In(0)),
         }
     }
 }
@@ -138,6 +154,7 @@ pub struct Exstage {
     pub fwd_mux_2: ControlRef,
     pub pc_sel: ControlRef,
     pub imm_sel: ControlRef,
+    pub target: ControlRef,
     pub branch: ControlRef,
     pub forward: ControlRef,
     pub alu: ControlRef,
@@ -158,7 +175,10 @@ impl Control for Exstage {
             ("fwd2_data", self.fwd_mux_2.output()[0].1),
             ("op1", self.pc_sel.output()[0].1),
             ("op2", self.imm_sel.output()[0].1),
-            ("npc_en", self.branch.output()[0].1),
+            ("taken", self.branch.output()[0].1),
+            ("pred", self.branch.output()[1].1),
+            ("npc_en", self.branch.output()[2].1),
+            ("target", self.target.output()[0].1),
         ];
         res.extend(self.forward.output());
         res
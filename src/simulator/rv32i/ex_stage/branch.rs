@@ -1,6 +1,8 @@
 use crate::common::abi::*;
 pub enum Alloc {
     BK = 0,
+    // taken but predicted not taken, or the other way round
+    Miss = 1,
 }
 pub enum Connect {
     BranchType = 0,
@@ -8,6 +10,7 @@ pub enum Connect {
     Op2 = 2,
     Jal_ = 3,
     BranchSel = 4,
+    Pred = 5,
 }
 #[derive(Default)]
 pub struct BranchBuilder {
@@ -24,6 +27,7 @@ impl PortBuilder for BranchBuilder {
     fn alloc(&mut self, id: Alloc) -> PortRef {
         match id {
             Alloc::BK => self.inner.clone().into_shared().into(),
+            Alloc::Miss => BranchMiss(self.inner.clone().into_shared()).into(),
         }
     }
     fn connect(&mut self, pin: PortRef, id: Connect) {
@@ -33,6 +37,7 @@ impl PortBuilder for BranchBuilder {
             Connect::Op2 => self.inner.borrow_mut().op2 = Some(pin.clone()),
             Connect::Jal_ => self.inner.borrow_mut().jal_ = Some(pin.clone()),
             Connect::BranchSel => self.inner.borrow_mut().branchsel = Some(pin.clone()),
+            Connect::Pred => self.inner.borrow_mut().pred = Some(pin.clone()),
         }
     }
 }
@@ -44,10 +49,13 @@ pub struct Branch {
     pub jal_: Option<PortRef>,
     pub branchsel: Option<PortRef>,
     pub branchtype: Option<PortRef>,
+    pub pred: Option<PortRef>,
 }
 impl Control for Branch {
     fn output(&self) -> Vec<(&'static str, u32)> {
-        vec![("br", self.read())]
+        let br = self.read();
+        let pred = self.pred.as_ref().map_or(0, |pred| pred.read());
+        vec![("br", br), ("pred", pred), ("miss", br ^ pred)]
     }
 }
 impl Port for Branch {
@@ -74,6 +82,14 @@ impl Port for Branch {
         }
     }
 }
+#[derive(Debug)]
+struct BranchMiss(Shared<Branch>);
+impl Port for BranchMiss {
+    fn read(&self) -> u32 {
+        let branch = self.0.borrow();
+        branch.read() ^ branch.pred.as_ref().unwrap().read()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -91,5 +107,10 @@ mod tests {
         alub.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::BranchSel);
         let alu = alub.alloc(Alloc::BK);
         assert_eq!(alu.read(), 1);
+        let miss = alub.alloc(Alloc::Miss);
+        alub.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::Pred);
+        assert_eq!(miss.read(), 0);
+        alub.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Pred);
+        assert_eq!(miss.read(), 1);
     }
 }
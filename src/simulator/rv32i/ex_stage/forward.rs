@@ -125,7 +125,10 @@ impl Port for Forward {
                 unimplemented!()
             }
         };
-        if rs == rd_mem && rd_mem_write == 1 {
+        //x0 is never forwarded, a jal x0 still carries its npc down the pipeline
+        if rs == 0 {
+            0
+        } else if rs == rd_mem && rd_mem_write == 1 {
             1
         } else if rs == rd_wb && rd_wb_write == 1 {
             2
@@ -173,5 +176,7 @@ mod tests {
     #[test]
     fn test_forward() {
         build_test(0, 8, 0xc, 0, 8, 1, 0, 2);
+        // a jal x0 ahead still has reg_write set, x0 reads as 0 anyway
+        build_test(0, 0, 0, 1, 0, 1, 0, 0);
     }
 }
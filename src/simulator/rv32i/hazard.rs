@@ -92,6 +92,9 @@ impl Control for Hazard {
         self.not.falling_edge();
         self.or.falling_edge();
     }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.raw.restore(snapshot);
+    }
     fn output(&self) -> Vec<(&'static str, u32)> {
         let mut res = vec![("en", self.not.output()[0].1)];
         res.extend(self.raw.output());
@@ -62,6 +62,9 @@ impl Control for RAW {
     fn rasing_edge(&mut self) {
         self.out_cache = Cell::new(None);
     }
+    fn restore(&mut self, _snapshot: &Snapshot) {
+        self.out_cache = Cell::new(None);
+    }
     fn output(&self) -> Vec<(&'static str, u32)> {
         vec![("raw", self.read())]
     }
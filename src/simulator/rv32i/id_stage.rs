@@ -1,4 +1,5 @@
 use crate::common::abi::*;
+use crate::common::build::*;
 
 use control::Alloc as CtrlAlloc;
 use control::Connect as CtrlConnect;
@@ -11,6 +12,7 @@ use imm::Connect as ImmConnect;
 use imm::ImmBuilder;
 use xregs::Alloc as XregsAlloc;
 use xregs::Connect as XregsConnect;
+use xregs::IndexAlloc as XregsIndexAlloc;
 use xregs::XregsBuilder;
 mod control;
 mod decode;
@@ -52,6 +54,9 @@ impl From<Connect> for usize {
         }
     }
 }
+pub enum IndexAlloc {
+    Xregs,
+}
 pub struct IdStageBuilder {
     pub control: CtrlSigBuilder,
     pub decode: DecodeBuilder,
@@ -115,15 +120,52 @@ impl PortBuilder for IdStageBuilder {
         }
     }
 }
+impl IndexPortBuilder for IdStageBuilder {
+    type IndexAlloc = IndexAlloc;
+    type IndexConnect = ();
+    fn index_connect(&mut self, _pin: IndexPortRef, _id: ()) {
+        unreachable!("IdStage has no index input")
+    }
+    fn index_alloc(&mut self, id: IndexAlloc) -> IndexPortRef {
+        match id {
+            IndexAlloc::Xregs => self.xregs.index_alloc(XregsIndexAlloc::X),
+        }
+    }
+}
 impl ControlBuilder for IdStageBuilder {
     fn build(self) -> ControlRef {
         self.xregs.build()
     }
 }
+// what the decoder, ImmBuilder and CtrlSigBuilder make of an instruction, named like
+// the ID/EX fields they go to
+pub fn decode(inst: u32) -> Vec<(&'static str, u32)> {
+    let mut idb = IdStageBuilder::new(0);
+    let mut constb = ConstsBuilder::default();
+    idb.connect(constb.alloc(ConstsAlloc::Out(inst)), Connect::Inst);
+    [
+        ("rs1", Alloc::Rs1),
+        ("rs2", Alloc::Rs2),
+        ("rd", Alloc::Rd),
+        ("imm", Alloc::Imm),
+        ("br_type", Alloc::BranchType),
+        ("alu_ctrl", Alloc::AluCtrl),
+        ("imm_sel", Alloc::ImmSel),
+        ("pc_sel", Alloc::PcSel),
+        ("npc_sel", Alloc::BranchEn),
+        ("jal_", Alloc::Jal_),
+        ("mem_write", Alloc::MemWrite),
+        ("wb_sel", Alloc::WbSel),
+        ("reg_write", Alloc::RegWrite),
+        ("load", Alloc::Load),
+    ]
+    .into_iter()
+    .map(|(name, id)| (name, idb.alloc(id).read()))
+    .collect()
+}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::build::*;
 
     struct TestConnect {
         pub inst: u32,
@@ -260,4 +302,13 @@ mod tests {
         };
         run_test(test_alloc, test_connect);
     }
+    #[test]
+    fn test_decode() {
+        // sw x8, 428(x2)
+        let signals = decode(0x1a812623);
+        let get = |name| signals.iter().find(|(n, _)| *n == name).unwrap().1;
+        assert_eq!((get("rs1"), get("rs2"), get("imm")), (2, 8, 0x1ac));
+        assert_eq!((get("mem_write"), get("reg_write")), (1, 0));
+        assert_eq!(signals.len(), 14);
+    }
 }
@@ -83,13 +83,6 @@ impl PortBuilder for CtrlSigBuilder {
         self.branch_type.connect(pin, BitConnect::In);
     }
 }
-#[derive(Debug)]
-pub struct Control {}
-impl Port for Control {
-    fn read(&self) -> u32 {
-        unimplemented!()
-    }
-}
 #[derive(Default, Debug)]
 pub struct Jal_ {
     pub input: Option<PortRef>,
@@ -148,7 +141,12 @@ impl Port for AluCtl {
         };
         let opcode = 0b111_1111 & input;
         match opcode {
-            0b011_0011 | 0b001_0011 => ((input << 1) >> 31) | ((input << 17) >> 28) | 1, //alu
+            //funct3 in bits 1-3, bit30 selects sub/sra in bit4, addi has no sub so only srai keeps it
+            0b011_0011 => (((input >> 30) & 1) << 4) | (((input >> 12) & 0b111) << 1) | 1, //alu
+            0b001_0011 if (input >> 12) & 0b111 == 0b101 => {
+                (((input >> 30) & 1) << 4) | (0b101 << 1) | 1 //srli, srai
+            }
+            0b001_0011 => (((input >> 12) & 0b111) << 1) | 1, //imm
             0b000_0011 | 0b010_0011 | 0b110_0011 | 0b110_1111 | 0b110_0111 | 0b001_0111 => 0b00001, //load, store, branch, jal, jalr, auipc
             0b011_0111 => 0b10011, //lui, mix with slli
             _ => {
@@ -196,7 +194,7 @@ impl Port for PcSel {
         };
         let opcode = 0b111_1111 & input;
         match opcode {
-            0b110_0011 | 0b110_1111 | 0b110_0111 | 0b001_0111 => 1, //branch, jal, jalr, auipc
+            0b110_0011 | 0b110_1111 | 0b001_0111 => 1, //branch, jal, auipc; jalr adds to rs1
             _ => 0,
         }
     }
@@ -260,8 +258,8 @@ impl Port for RegWrite {
         };
         let opcode = 0b111_1111 & input;
         match opcode {
-            0b011_0011 | 0b000_0011 | 0b001_0011 | 0b110_0011 | 0b110_1111 | 0b110_0111
-            | 0b001_0111 | 0b011_0111 => 1, //alu, load, imm, branch, jal, jalr, lui, auipc
+            0b011_0011 | 0b000_0011 | 0b001_0011 | 0b110_1111 | 0b110_0111 | 0b001_0111
+            | 0b011_0111 => 1, //alu, load, imm, jal, jalr, auipc, lui
             _ => 0, //todo: add more
         }
     }
@@ -287,3 +285,36 @@ impl Port for LoadSiganl {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the control signal `id` decoded from `inst`
+    fn signal(inst: u32, id: Alloc) -> u32 {
+        let mut ctrl = CtrlSigBuilder::new();
+        let mut consts = ConstsBuilder::default();
+        ctrl.connect(consts.alloc(ConstsAlloc::Out(inst)), Connect::Opcode);
+        ctrl.alloc(id).read()
+    }
+    #[test]
+    fn test_alu_ctl() {
+        // sub x1, x2, x3: bit 30 picks sub
+        assert_eq!(signal(0x403100b3, Alloc::AluCtrl), 0b10001);
+        // srai x5, x5, 3: bit 30 picks the arithmetic shift
+        assert_eq!(signal(0x4032d293, Alloc::AluCtrl), 0b11011);
+        // addi x1, x0, -1024: bit 30 is part of the immediate
+        assert_eq!(signal(0xc0000093, Alloc::AluCtrl), 0b00001);
+        // xori x1, x1, 1
+        assert_eq!(signal(0x0010c093, Alloc::AluCtrl), 0b01001);
+    }
+    #[test]
+    fn test_jalr_branch() {
+        // jalr x1, 8(x5): the target is rs1 + imm, not pc + imm
+        assert_eq!(signal(0x008280e7, Alloc::PcSel), 0);
+        assert_eq!(signal(0x008280e7, Alloc::RegWrite), 1);
+        // beq x1, x2, 8: pc + imm, and no register written
+        assert_eq!(signal(0x00208463, Alloc::PcSel), 1);
+        assert_eq!(signal(0x00208463, Alloc::RegWrite), 0);
+    }
+}
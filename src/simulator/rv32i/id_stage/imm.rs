@@ -58,7 +58,7 @@ impl Port for Imm {
                 ((input >> 20) & 0b11111111110)
                     | ((input >> 9) & 0b100000000000)
                     | ((input) & 0b11111111000000000000)
-                    | ((input >> 10) & 0b100000000000000000000)
+                    | ((input >> 31) << 20)
             }
             //u-type
             //don't need to shift, just leave it to alu to handle
@@ -107,5 +107,11 @@ mod tests {
         tb.connect(constant.alloc(ConstsAlloc::Out(opcode)), Connect::Opcode);
         tb.connect(constant.alloc(ConstsAlloc::Out(inst)), Connect::Inst);
         assert_eq!(imm.read(), 0x2346);
+        // J-type: jal x0, -12
+        opcode = 0b110_1111;
+        inst = 0xff5ff06f;
+        tb.connect(constant.alloc(ConstsAlloc::Out(opcode)), Connect::Opcode);
+        tb.connect(constant.alloc(ConstsAlloc::Out(inst)), Connect::Inst);
+        assert_eq!(imm.read() as i32, -12);
     }
 }
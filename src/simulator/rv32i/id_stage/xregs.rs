@@ -40,6 +40,9 @@ impl From<Connect> for usize {
         }
     }
 }
+pub enum IndexAlloc {
+    X,
+}
 pub struct XregsBuilder {
     x: RegGroupBuilder,
     mux_rs1: RegMuxBuilder,
@@ -90,6 +93,18 @@ impl PortBuilder for XregsBuilder {
         }
     }
 }
+impl IndexPortBuilder for XregsBuilder {
+    type IndexAlloc = IndexAlloc;
+    type IndexConnect = ();
+    fn index_connect(&mut self, _pin: IndexPortRef, _id: ()) {
+        unreachable!("Xregs has no index input")
+    }
+    fn index_alloc(&mut self, id: IndexAlloc) -> IndexPortRef {
+        match id {
+            IndexAlloc::X => self.x.index_alloc(RegGroupIndexAlloc::X),
+        }
+    }
+}
 impl ControlBuilder for XregsBuilder {
     fn build(self) -> ControlRef {
         self.x.build()
@@ -107,17 +122,34 @@ mod tests {
         let mut consts = ConstsBuilder::default();
         rsb.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Rs1);
         rsb.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::Rs2);
-        rsb.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Rd);
+        rsb.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::Rd);
         rsb.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::Write);
         rsb.connect(consts.alloc(ConstsAlloc::Out(4)), Connect::RdData);
         let r1 = rsb.alloc(Alloc::R1Data);
         let r2 = rsb.alloc(Alloc::R2Data);
         let rs = rsb.build();
-        assert_eq!(r1.read(), 4);
-        assert_eq!(r2.read(), 0);
+        // the write is bypassed before the edge
+        assert_eq!(r1.read(), 0);
+        assert_eq!(r2.read(), 4);
+        rs.rasing_edge();
+        rs.falling_edge();
+        assert_eq!(r1.read(), 0);
+        assert_eq!(r2.read(), 4);
+    }
+    #[test]
+    fn test_regs_x0() {
+        let mut rsb = XregsBuilder::new(0);
+        let mut consts = ConstsBuilder::default();
+        rsb.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Rs1);
+        rsb.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Rs2);
+        rsb.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Rd);
+        rsb.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::Write);
+        rsb.connect(consts.alloc(ConstsAlloc::Out(4)), Connect::RdData);
+        let r1 = rsb.alloc(Alloc::R1Data);
+        let rs = rsb.build();
+        assert_eq!(r1.read(), 0);
         rs.rasing_edge();
         rs.falling_edge();
-        assert_eq!(r1.read(), 4);
-        assert_eq!(r2.read(), 0);
+        assert_eq!(r1.read(), 0);
     }
 }
@@ -72,7 +72,8 @@ impl Port for RegMux {
         let rs = self.rs.read();
         let rd = self.rd.read();
         let write = self.write.read();
-        if write == 1 && rd == rs {
+        //write-through bypass, x0 always reads as 0
+        if write == 1 && rd == rs && rs != 0 {
             self.rd_data.read()
         } else {
             self.x.read(rs as usize)
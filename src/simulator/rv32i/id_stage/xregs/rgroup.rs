@@ -125,6 +125,16 @@ impl Control for RegGroupControl {
     fn output(&self) -> Vec<(&'static str, u32)> {
         vec![]
     }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Words(self.x.borrow().x.to_vec())
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let Snapshot::Words(x) = snapshot else {
+            panic!("RegGroup: invalid snapshot {:?}", snapshot);
+        };
+        self.x.borrow_mut().x.copy_from_slice(x);
+        self.rd_cache = 0;
+    }
 }
 
 #[derive(Debug)]
@@ -142,4 +152,9 @@ impl IndexPort for RegGroup {
     fn read(&self, index: usize) -> u32 {
         self.x[index]
     }
+    fn write(&mut self, index: usize, data: u32) {
+        if index != 0 {
+            self.x[index] = data;
+        }
+    }
 }
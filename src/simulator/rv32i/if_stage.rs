@@ -1,25 +1,37 @@
 use crate::common::abi::*;
 use crate::common::build::*;
+use crate::config::PredictorConfig;
+
+use predictor::Alloc as PredictorAlloc;
+use predictor::Connect as PredictorConnect;
+use predictor::PredictorBuilder;
+mod predictor;
 
 pub enum Alloc {
     Pc = 0,
     Npc = 1,
     Imem = 2,
+    Pred = 3,
 }
 
 pub enum Connect {
     PcEnable = 0,
+    // the prediction in EX was wrong, fetch from Npc instead
     NpcSel = 1,
     Npc = 2,
+    ExPc = 3,
+    ExBranch = 4,
+    ExTaken = 5,
 }
 pub struct IfStageBuilder {
     pub npc_mux: MuxBuilder,
     pub pc: RegBuilder,
     pub add: AddBuilder,
     pub imem: MemBuilder,
+    pub predictor: PredictorBuilder,
 }
 impl IfStageBuilder {
-    pub fn new(entry: u32, start: u32, inst_mem: Vec<u8>) -> Self {
+    pub fn new(entry: u32, start: u32, inst_mem: Vec<u8>, config: &PredictorConfig) -> Self {
         // add if stage
         let mut consts = ConstsBuilder::default();
         let mut if_pc_inc = MuxBuilder::default();
@@ -37,12 +49,19 @@ impl IfStageBuilder {
         let mut npc_mux = MuxBuilder::default();
         npc_mux.connect(add.alloc(AddAlloc::Out), MuxConnect::In(0));
         pc.connect(npc_mux.alloc(MuxAlloc::Out), RegConnect::In);
+        // the predicted target of a branch or jal, taken at once
+        let mut predictor = PredictorBuilder::new(predictor::new(config));
+        predictor.connect(pc.alloc(RegAlloc::Out), PredictorConnect::Pc);
+        predictor.connect(imem.alloc(MemAlloc::Out), PredictorConnect::Inst);
+        npc_mux.connect(predictor.alloc(PredictorAlloc::Target), MuxConnect::In(2));
+        npc_mux.connect(predictor.alloc(PredictorAlloc::NpcSel), MuxConnect::Select);
 
         IfStageBuilder {
             npc_mux,
             pc,
             add,
             imem,
+            predictor,
         }
     }
 }
@@ -52,6 +71,7 @@ impl ControlBuilder for IfStageBuilder {
             pc: self.pc.build(),
             npc_mux: self.npc_mux.build(),
             imem: self.imem.build(),
+            predictor: self.predictor.build(),
         }
         .into()
     }
@@ -64,6 +84,7 @@ impl PortBuilder for IfStageBuilder {
             Alloc::Pc => self.pc.alloc(RegAlloc::Out),
             Alloc::Npc => self.add.alloc(AddAlloc::Out),
             Alloc::Imem => self.imem.alloc(MemAlloc::Out),
+            Alloc::Pred => self.predictor.alloc(PredictorAlloc::Taken),
         }
     }
     fn connect(&mut self, pin: PortRef, id: Connect) {
@@ -75,8 +96,11 @@ impl PortBuilder for IfStageBuilder {
                 self.imem
                     .connect(self.pc.alloc(RegAlloc::Out), MemConnect::Addr);
             }
-            Connect::NpcSel => self.npc_mux.connect(pin, MuxConnect::Select),
+            Connect::NpcSel => self.predictor.connect(pin, PredictorConnect::Redirect),
             Connect::Npc => self.npc_mux.connect(pin, MuxConnect::In(1)),
+            Connect::ExPc => self.predictor.connect(pin, PredictorConnect::ExPc),
+            Connect::ExBranch => self.predictor.connect(pin, PredictorConnect::ExBranch),
+            Connect::ExTaken => self.predictor.connect(pin, PredictorConnect::ExTaken),
         }
     }
 }
@@ -85,19 +109,36 @@ pub struct IfStage {
     pub pc: ControlRef,
     pub npc_mux: ControlRef,
     pub imem: ControlRef,
+    pub predictor: ControlRef,
 }
 impl Control for IfStage {
     fn rasing_edge(&mut self) {
         self.pc.rasing_edge();
         self.imem.rasing_edge();
+        self.predictor.rasing_edge();
     }
     fn falling_edge(&mut self) {
         self.pc.falling_edge();
         self.imem.falling_edge();
+        self.predictor.falling_edge();
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.pc.snapshot(),
+            self.imem.snapshot(),
+            self.predictor.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.pc.restore(&group[0]);
+        self.imem.restore(&group[1]);
+        self.predictor.restore(&group[2]);
     }
     fn inner_signal(&self) -> Vec<(&'static str, u32)> {
         let mut res = vec![];
         res.extend(self.npc_mux.output());
+        res.extend(self.predictor.output());
         res
     }
 }
@@ -108,13 +149,14 @@ mod tests {
     #[test]
     fn test_generate_if() {
         let text = b"abcdefgh".to_vec();
-        let mut ifb = IfStageBuilder::new(0, 0, text.to_vec());
+        let mut ifb = IfStageBuilder::new(0, 0, text.to_vec(), &PredictorConfig::default());
         let mut consts = ConstsBuilder::default();
         ifb.npc_mux
             .connect(consts.alloc(ConstsAlloc::Out(0)), MuxConnect::Select);
         ifb.imem
             .connect(consts.alloc(ConstsAlloc::Out(0)), MemConnect::WriteEn);
         ifb.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::PcEnable);
+        ifb.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::ExBranch);
         let pc = ifb.pc.alloc(RegAlloc::Out);
         let npc = ifb.add.alloc(AddAlloc::Out);
         let imem = ifb.imem.alloc(MemAlloc::Out);
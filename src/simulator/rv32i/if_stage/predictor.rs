@@ -0,0 +1,467 @@
+use std::fmt::Debug;
+
+use crate::common::abi::*;
+use crate::common::build::*;
+use crate::config::PredictorConfig;
+
+pub enum Alloc {
+    // 1 when the instruction being fetched is predicted taken
+    Taken = 0,
+    Target = 1,
+    // select of the npc mux: pc + 4, the target from EX or the predicted target
+    NpcSel = 2,
+}
+pub enum Connect {
+    Pc = 0,
+    Inst = 1,
+    // EX found the prediction wrong and sends the right pc
+    Redirect = 2,
+    // the conditional branch in EX and where it went, to learn from
+    ExPc = 3,
+    ExBranch = 4,
+    ExTaken = 5,
+}
+
+// guesses the way of the conditional branches, the targets are known from the
+// instruction already
+pub trait Predict: Debug {
+    fn predict(&self, pc: u32, target: u32) -> bool;
+    // the outcome of the branch at `pc`, resolved in EX
+    fn update(&mut self, _pc: u32, _taken: bool) {}
+    // jal is predicted taken too, except by the plain fetch of not-taken
+    fn jumps(&self) -> bool {
+        true
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::None
+    }
+    fn restore(&mut self, _snapshot: &Snapshot) {}
+}
+
+// always pc + 4, the fetch unit without a predictor
+#[derive(Debug)]
+pub struct NotTaken;
+impl Predict for NotTaken {
+    fn predict(&self, _pc: u32, _target: u32) -> bool {
+        false
+    }
+    fn jumps(&self) -> bool {
+        false
+    }
+}
+#[derive(Debug)]
+pub struct AlwaysTaken;
+impl Predict for AlwaysTaken {
+    fn predict(&self, _pc: u32, _target: u32) -> bool {
+        true
+    }
+}
+// backward taken, forward not taken: loops branch back
+#[derive(Debug)]
+pub struct Btfn;
+impl Predict for Btfn {
+    fn predict(&self, pc: u32, target: u32) -> bool {
+        target < pc
+    }
+}
+
+// 2-bit saturating counters, taken from 2 up
+#[derive(Debug, Clone)]
+pub struct Counters {
+    counters: Vec<u32>,
+    max: u32,
+}
+impl Counters {
+    fn new(bits: u32, max: u32) -> Self {
+        // weakly not taken
+        Self {
+            counters: vec![max / 2; 1 << bits],
+            max,
+        }
+    }
+    fn index(&self, key: u32) -> usize {
+        key as usize & (self.counters.len() - 1)
+    }
+    fn taken(&self, key: u32) -> bool {
+        self.counters[self.index(key)] > self.max / 2
+    }
+    fn update(&mut self, key: u32, taken: bool) {
+        let (index, max) = (self.index(key), self.max);
+        let counter = &mut self.counters[index];
+        *counter = match taken {
+            true => (*counter + 1).min(max),
+            false => counter.saturating_sub(1),
+        };
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Words(self.counters.clone())
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let Snapshot::Words(counters) = snapshot else {
+            panic!("Counters: invalid snapshot {:?}", snapshot);
+        };
+        // the index masks by the length, a power of two
+        assert_eq!(
+            counters.len(),
+            self.counters.len(),
+            "Counters: snapshot of another size"
+        );
+        self.counters = counters.clone();
+    }
+}
+
+// a table of counters indexed by the pc, 1-bit remembers the last outcome and 2-bit
+// needs two wrong guesses in a row to change its mind
+#[derive(Debug)]
+pub struct Bimodal(Counters);
+impl Predict for Bimodal {
+    fn predict(&self, pc: u32, _target: u32) -> bool {
+        self.0.taken(pc >> 2)
+    }
+    fn update(&mut self, pc: u32, taken: bool) {
+        self.0.update(pc >> 2, taken);
+    }
+    fn snapshot(&self) -> Snapshot {
+        self.0.snapshot()
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.0.restore(snapshot);
+    }
+}
+
+// 2-bit counters indexed by the pc xor the outcomes of the last branches
+#[derive(Debug)]
+pub struct Gshare {
+    counters: Counters,
+    history: u32,
+    mask: u32,
+}
+impl Gshare {
+    fn new(bits: u32, history: u32) -> Self {
+        Self {
+            counters: Counters::new(bits, 3),
+            history: 0,
+            mask: (1 << history) - 1,
+        }
+    }
+    fn key(&self, pc: u32) -> u32 {
+        (pc >> 2) ^ self.history
+    }
+}
+impl Predict for Gshare {
+    fn predict(&self, pc: u32, _target: u32) -> bool {
+        self.counters.taken(self.key(pc))
+    }
+    // the history moves on when the branch resolves, not when it is fetched
+    fn update(&mut self, pc: u32, taken: bool) {
+        self.counters.update(self.key(pc), taken);
+        self.history = ((self.history << 1) | taken as u32) & self.mask;
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![self.counters.snapshot(), Snapshot::Word(self.history)])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.counters.restore(&group[0]);
+        let Snapshot::Word(history) = group[1] else {
+            panic!("Gshare: invalid snapshot {:?}", snapshot);
+        };
+        self.history = history;
+    }
+}
+
+// 2-bit and gshare side by side, with a 2-bit chooser per pc for the one that has been
+// right more often
+#[derive(Debug)]
+pub struct Tournament {
+    local: Bimodal,
+    global: Gshare,
+    chooser: Counters,
+}
+impl Predict for Tournament {
+    fn predict(&self, pc: u32, target: u32) -> bool {
+        match self.chooser.taken(pc >> 2) {
+            true => self.global.predict(pc, target),
+            false => self.local.predict(pc, target),
+        }
+    }
+    fn update(&mut self, pc: u32, taken: bool) {
+        let local = self.local.predict(pc, 0) == taken;
+        let global = self.global.predict(pc, 0) == taken;
+        if local != global {
+            self.chooser.update(pc >> 2, global);
+        }
+        self.local.update(pc, taken);
+        self.global.update(pc, taken);
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.local.snapshot(),
+            self.global.snapshot(),
+            self.chooser.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.local.restore(&group[0]);
+        self.global.restore(&group[1]);
+        self.chooser.restore(&group[2]);
+    }
+}
+
+// the predictor of the [predictor] section, validated where the config or a save file was read
+pub fn new(config: &PredictorConfig) -> Box<dyn Predict> {
+    if let Err(e) = config.validate() {
+        panic!("predictor: {}", e);
+    }
+    let bits = config.bits;
+    match config.kind.as_str() {
+        "not-taken" => Box::new(NotTaken),
+        "always-taken" => Box::new(AlwaysTaken),
+        "btfn" => Box::new(Btfn),
+        "1-bit" => Box::new(Bimodal(Counters::new(bits, 1))),
+        "2-bit" => Box::new(Bimodal(Counters::new(bits, 3))),
+        "gshare" => Box::new(Gshare::new(bits, config.history)),
+        "tournament" => Box::new(Tournament {
+            local: Bimodal(Counters::new(bits, 3)),
+            global: Gshare::new(bits, config.history),
+            chooser: Counters::new(bits, 3),
+        }),
+        _ => unreachable!(),
+    }
+}
+
+fn sext(value: u32, bits: u32) -> u32 {
+    (((value << (32 - bits)) as i32) >> (32 - bits)) as u32
+}
+// where a branch or jal goes, and whether it is a jal
+fn target(pc: u32, inst: u32) -> Option<(u32, bool)> {
+    match inst & 0b111_1111 {
+        0b110_0011 => {
+            let imm = sext(
+                ((inst >> 31) << 12)
+                    | (((inst >> 7) & 0b1) << 11)
+                    | (((inst >> 25) & 0b11_1111) << 5)
+                    | (((inst >> 8) & 0b1111) << 1),
+                13,
+            );
+            Some((pc.wrapping_add(imm), false))
+        }
+        0b110_1111 => {
+            let imm = sext(
+                ((inst >> 31) << 20)
+                    | (((inst >> 12) & 0xFF) << 12)
+                    | (((inst >> 20) & 0b1) << 11)
+                    | (((inst >> 21) & 0x3FF) << 1),
+                21,
+            );
+            Some((pc.wrapping_add(imm), true))
+        }
+        _ => None,
+    }
+}
+
+pub struct PredictorBuilder {
+    inner: ControlShared<Predictor>,
+}
+impl PredictorBuilder {
+    pub fn new(predict: Box<dyn Predict>) -> Self {
+        Self {
+            inner: ControlShared::new(Predictor {
+                predict,
+                pc: bomb().into(),
+                inst: bomb().into(),
+                redirect: bomb().into(),
+                ex_pc: bomb().into(),
+                ex_branch: bomb().into(),
+                ex_taken: bomb().into(),
+                outcome: None,
+            }),
+        }
+    }
+}
+impl ControlBuilder for PredictorBuilder {
+    fn build(self) -> ControlRef {
+        self.inner.into_shared().into()
+    }
+}
+impl PortBuilder for PredictorBuilder {
+    type Alloc = Alloc;
+    type Connect = Connect;
+    fn alloc(&mut self, id: Alloc) -> PortRef {
+        let inner = self.inner.clone().into_shared();
+        match id {
+            Alloc::Taken => inner.into(),
+            Alloc::Target => PredictorTarget(inner).into(),
+            Alloc::NpcSel => PredictorNpcSel(inner).into(),
+        }
+    }
+    fn connect(&mut self, pin: PortRef, id: Connect) {
+        let mut inner = self.inner.borrow_mut();
+        match id {
+            Connect::Pc => inner.pc = pin,
+            Connect::Inst => inner.inst = pin,
+            Connect::Redirect => inner.redirect = pin,
+            Connect::ExPc => inner.ex_pc = pin,
+            Connect::ExBranch => inner.ex_branch = pin,
+            Connect::ExTaken => inner.ex_taken = pin,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Predictor {
+    predict: Box<dyn Predict>,
+    pc: PortRef,
+    inst: PortRef,
+    redirect: PortRef,
+    ex_pc: PortRef,
+    ex_branch: PortRef,
+    ex_taken: PortRef,
+    // the branch resolved in EX this cycle, learnt on the falling edge so that IF/ID
+    // latches the prediction made before it
+    outcome: Option<(u32, bool)>,
+}
+impl Predictor {
+    fn guess(&self) -> Option<u32> {
+        let pc = self.pc.read();
+        let (target, jump) = target(pc, self.inst.read())?;
+        let taken = match jump {
+            true => self.predict.jumps(),
+            false => self.predict.predict(pc, target),
+        };
+        taken.then_some(target)
+    }
+}
+impl Port for Predictor {
+    fn read(&self) -> u32 {
+        self.guess().is_some() as u32
+    }
+}
+impl Control for Predictor {
+    fn rasing_edge(&mut self) {
+        self.outcome = match self.ex_branch.read() {
+            1 => Some((self.ex_pc.read(), self.ex_taken.read() == 1)),
+            _ => None,
+        };
+    }
+    fn falling_edge(&mut self) {
+        if let Some((pc, taken)) = self.outcome.take() {
+            self.predict.update(pc, taken);
+        }
+    }
+    fn output(&self) -> Vec<(&'static str, u32)> {
+        vec![
+            ("pred", self.read()),
+            ("pred_target", self.guess().unwrap_or_default()),
+        ]
+    }
+    fn snapshot(&self) -> Snapshot {
+        self.predict.snapshot()
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.predict.restore(snapshot);
+        self.outcome = None;
+    }
+}
+#[derive(Debug)]
+struct PredictorTarget(Shared<Predictor>);
+impl Port for PredictorTarget {
+    fn read(&self) -> u32 {
+        self.0.borrow().guess().unwrap_or_default()
+    }
+}
+#[derive(Debug)]
+struct PredictorNpcSel(Shared<Predictor>);
+impl Port for PredictorNpcSel {
+    fn read(&self) -> u32 {
+        let predictor = self.0.borrow();
+        match (predictor.redirect.read(), predictor.read()) {
+            (1, _) => 1,
+            (_, 1) => 2,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(kind: &str) -> PredictorConfig {
+        PredictorConfig {
+            kind: kind.to_string(),
+            bits: 4,
+            history: 2,
+        }
+    }
+    // right guesses of the predictor on the outcomes of the branch at 0x40 to 0x20
+    fn right(kind: &str, outcomes: &[bool]) -> usize {
+        let mut predict = new(&config(kind));
+        outcomes
+            .iter()
+            .filter(|&&taken| {
+                let right = predict.predict(0x40, 0x20) == taken;
+                predict.update(0x40, taken);
+                right
+            })
+            .count()
+    }
+    #[test]
+    fn test_predict() {
+        // a loop of four, run three times
+        let lp = [true, true, true, false].repeat(3);
+        assert_eq!(right("not-taken", &lp), 3);
+        assert_eq!(right("always-taken", &lp), 9);
+        assert_eq!(right("btfn", &lp), 9);
+        assert_eq!(right("1-bit", &lp), 6);
+        assert_eq!(right("2-bit", &lp), 8);
+        // alternating is learnt from the history
+        let alternate = [true, false].repeat(8);
+        assert_eq!(right("2-bit", &alternate), 0);
+        assert!(right("gshare", &alternate) >= 13);
+        assert!(right("tournament", &alternate) >= 11);
+        assert!(!Btfn.predict(0x20, 0x40));
+    }
+    #[test]
+    fn test_predictor() {
+        // 4: jal x0, 8; 8: beq x0, x0, -4
+        let mut consts = ConstsBuilder::default();
+        for (kind, beq, jal) in [("not-taken", 0, 0), ("btfn", 1, 1)] {
+            let mut builder = PredictorBuilder::new(new(&config(kind)));
+            builder.connect(consts.alloc(ConstsAlloc::Out(0)), Connect::Redirect);
+            let taken = builder.alloc(Alloc::Taken);
+            let target = builder.alloc(Alloc::Target);
+            let npc_sel = builder.alloc(Alloc::NpcSel);
+            builder.connect(consts.alloc(ConstsAlloc::Out(8)), Connect::Pc);
+            builder.connect(consts.alloc(ConstsAlloc::Out(0xfe000ee3)), Connect::Inst);
+            assert_eq!(taken.read(), beq);
+            assert_eq!(npc_sel.read(), beq * 2);
+            assert_eq!(target.read(), beq * 4);
+            builder.connect(consts.alloc(ConstsAlloc::Out(4)), Connect::Pc);
+            builder.connect(consts.alloc(ConstsAlloc::Out(0x0080006f)), Connect::Inst);
+            assert_eq!((taken.read(), target.read()), (jal, jal * 12));
+            // EX sends the pc back whatever was guessed
+            builder.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::Redirect);
+            assert_eq!(npc_sel.read(), 1);
+        }
+        // the 2-bit counter learns when the branch leaves EX
+        let mut builder = PredictorBuilder::new(new(&config("2-bit")));
+        builder.connect(consts.alloc(ConstsAlloc::Out(8)), Connect::Pc);
+        builder.connect(consts.alloc(ConstsAlloc::Out(0xfe000ee3)), Connect::Inst);
+        builder.connect(consts.alloc(ConstsAlloc::Out(8)), Connect::ExPc);
+        builder.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::ExBranch);
+        builder.connect(consts.alloc(ConstsAlloc::Out(1)), Connect::ExTaken);
+        let taken = builder.alloc(Alloc::Taken);
+        let predictor = builder.build();
+        predictor.rasing_edge();
+        assert_eq!(taken.read(), 0);
+        predictor.falling_edge();
+        assert_eq!(taken.read(), 1);
+        let snapshot = predictor.snapshot();
+        predictor.rasing_edge();
+        predictor.falling_edge();
+        predictor.restore(&snapshot);
+        assert_eq!(predictor.snapshot(), snapshot);
+    }
+}
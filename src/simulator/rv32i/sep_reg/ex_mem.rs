@@ -11,6 +11,7 @@ pub enum Alloc {
     Rs2Data = 6,
     Rd = 7,
     MemRead = 8,
+    Funct3 = 9,
 }
 
 pub enum Connect {
@@ -25,6 +26,7 @@ pub enum Connect {
     Ebable = 8,
     Clear = 9,
     MemRead = 10,
+    Funct3 = 11,
 }
 
 #[derive(Default)]
@@ -37,6 +39,7 @@ pub struct ExMemBuilder {
     pub rs2_data: RegBuilder,
     pub rd: RegBuilder,
     pub mem_read: RegBuilder,
+    pub funct3: RegBuilder,
 }
 impl ControlBuilder for ExMemBuilder {
     fn build(self) -> ControlRef {
@@ -49,6 +52,7 @@ impl ControlBuilder for ExMemBuilder {
             rs2_data: self.rs2_data.build(),
             rd: self.rd.build(),
             mem_read: self.mem_read.build(),
+            funct3: self.funct3.build(),
         }
         .into()
     }
@@ -66,6 +70,7 @@ impl PortBuilder for ExMemBuilder {
             Alloc::Rs2Data => self.rs2_data.alloc(RegAlloc::Out),
             Alloc::Rd => self.rd.alloc(RegAlloc::Out),
             Alloc::MemRead => self.mem_read.alloc(RegAlloc::Out),
+            Alloc::Funct3 => self.funct3.alloc(RegAlloc::Out),
         }
     }
     fn connect(&mut self, pin: PortRef, id: Connect) {
@@ -85,7 +90,8 @@ impl PortBuilder for ExMemBuilder {
                 self.alu_res.connect(pin.clone(), RegConnect::Enable);
                 self.rs2_data.connect(pin.clone(), RegConnect::Enable);
                 self.rd.connect(pin.clone(), RegConnect::Enable);
-                self.mem_read.connect(pin, RegConnect::Enable);
+                self.mem_read.connect(pin.clone(), RegConnect::Enable);
+                self.funct3.connect(pin, RegConnect::Enable);
             }
             Connect::Clear => {
                 self.reg_write.connect(pin.clone(), RegConnect::Clear);
@@ -95,9 +101,11 @@ impl PortBuilder for ExMemBuilder {
                 self.alu_res.connect(pin.clone(), RegConnect::Clear);
                 self.rs2_data.connect(pin.clone(), RegConnect::Clear);
                 self.rd.connect(pin.clone(), RegConnect::Clear);
-                self.mem_read.connect(pin, RegConnect::Clear);
+                self.mem_read.connect(pin.clone(), RegConnect::Clear);
+                self.funct3.connect(pin, RegConnect::Clear);
             }
             Connect::MemRead => self.mem_read.connect(pin, RegConnect::In),
+            Connect::Funct3 => self.funct3.connect(pin, RegConnect::In),
         }
     }
 }
@@ -112,6 +120,8 @@ pub struct ExMem {
     pub alu_res: ControlRef,
     pub rs2_data: ControlRef,
     pub rd: ControlRef,
+    // width and signedness of the load or store
+    pub funct3: ControlRef,
 }
 impl Control for ExMem {
     fn rasing_edge(&mut self) {
@@ -123,6 +133,7 @@ impl Control for ExMem {
         self.rs2_data.rasing_edge();
         self.rd.rasing_edge();
         self.mem_read.rasing_edge();
+        self.funct3.rasing_edge();
     }
     fn falling_edge(&mut self) {
         self.reg_write.falling_edge();
@@ -133,6 +144,47 @@ impl Control for ExMem {
         self.rs2_data.falling_edge();
         self.rd.falling_edge();
         self.mem_read.falling_edge();
+        self.funct3.falling_edge();
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.reg_write.snapshot(),
+            self.wb_sel.snapshot(),
+            self.mem_write.snapshot(),
+            self.npc.snapshot(),
+            self.alu_res.snapshot(),
+            self.rs2_data.snapshot(),
+            self.rd.snapshot(),
+            self.mem_read.snapshot(),
+            self.funct3.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.reg_write.restore(&group[0]);
+        self.wb_sel.restore(&group[1]);
+        self.mem_write.restore(&group[2]);
+        self.npc.restore(&group[3]);
+        self.alu_res.restore(&group[4]);
+        self.rs2_data.restore(&group[5]);
+        self.rd.restore(&group[6]);
+        self.mem_read.restore(&group[7]);
+        self.funct3.restore(&group[8]);
+    }
+    fn set(&mut self, name: &str, value: u32) -> bool {
+        let reg = match name {
+            "reg_write" => &self.reg_write,
+            "wb_sel" => &self.wb_sel,
+            "mem_write" => &self.mem_write,
+            "mem_read" => &self.mem_read,
+            "npc" => &self.npc,
+            "alu_res" => &self.alu_res,
+            "rs2_data" => &self.rs2_data,
+            "rd" => &self.rd,
+            "funct3" => &self.funct3,
+            _ => return false,
+        };
+        reg.set("out", value)
     }
     fn inout(&self) -> Vec<(&'static str, u32, u32)> {
         vec![
@@ -168,6 +220,11 @@ impl Control for ExMem {
                 self.rs2_data.output()[0].1,
             ),
             ("rd", self.rd.input()[0].1, self.rd.output()[0].1),
+            (
+                "funct3",
+                self.funct3.input()[0].1,
+                self.funct3.output()[0].1,
+            ),
         ]
     }
 }
@@ -22,6 +22,7 @@ pub enum Alloc {
     Rs2 = 17,
     Opco = 18,
     LoadSignal = 19,
+    Pred = 20,
 }
 
 pub enum Connect {
@@ -47,6 +48,7 @@ pub enum Connect {
     Enable = 19,
     Clear = 20,
     LoadSignal = 21,
+    Pred = 22,
 }
 
 #[derive(Default)]
@@ -70,6 +72,7 @@ pub struct IdExBuilder {
     pub rs2: RegBuilder,
     pub opco: RegBuilder,
     pub load_signal: RegBuilder,
+    pub pred: RegBuilder,
 }
 impl ControlBuilder for IdExBuilder {
     fn build(self) -> ControlRef {
@@ -93,6 +96,7 @@ impl ControlBuilder for IdExBuilder {
             rs2: self.rs2.build(),
             opco: self.opco.build(),
             load_signal: self.load_signal.build(),
+            pred: self.pred.build(),
         }
         .into()
     }
@@ -121,6 +125,7 @@ impl PortBuilder for IdExBuilder {
             Alloc::Rs2 => self.rs2.alloc(RegAlloc::Out),
             Alloc::Opco => self.opco.alloc(RegAlloc::Out),
             Alloc::LoadSignal => self.load_signal.alloc(RegAlloc::Out),
+            Alloc::Pred => self.pred.alloc(RegAlloc::Out),
         }
     }
     fn connect(&mut self, pin: PortRef, id: Connect) {
@@ -162,7 +167,8 @@ impl PortBuilder for IdExBuilder {
                 self.rd.connect(pin.clone(), RegConnect::Enable);
                 self.rs2.connect(pin.clone(), RegConnect::Enable);
                 self.opco.connect(pin.clone(), RegConnect::Enable);
-                self.load_signal.connect(pin, RegConnect::Enable);
+                self.load_signal.connect(pin.clone(), RegConnect::Enable);
+                self.pred.connect(pin, RegConnect::Enable);
             }
             Connect::Clear => {
                 self.reg_write.connect(pin.clone(), RegConnect::Clear);
@@ -183,9 +189,11 @@ impl PortBuilder for IdExBuilder {
                 self.rd.connect(pin.clone(), RegConnect::Clear);
                 self.rs2.connect(pin.clone(), RegConnect::Clear);
                 self.opco.connect(pin.clone(), RegConnect::Clear);
-                self.load_signal.connect(pin, RegConnect::Clear);
+                self.load_signal.connect(pin.clone(), RegConnect::Clear);
+                self.pred.connect(pin, RegConnect::Clear);
             }
             Connect::LoadSignal => self.load_signal.connect(pin, RegConnect::In),
+            Connect::Pred => self.pred.connect(pin, RegConnect::In),
         }
     }
 }
@@ -210,6 +218,7 @@ pub struct IdEx {
     pub rs2: ControlRef,
     pub opco: ControlRef,
     pub load_signal: ControlRef,
+    pub pred: ControlRef,
 }
 
 impl Control for IdEx {
@@ -233,6 +242,7 @@ impl Control for IdEx {
         self.rs2.rasing_edge();
         self.opco.rasing_edge();
         self.load_signal.rasing_edge();
+        self.pred.rasing_edge();
     }
     fn falling_edge(&mut self) {
         self.reg_write.falling_edge();
@@ -254,6 +264,80 @@ impl Control for IdEx {
         self.rs2.falling_edge();
         self.opco.falling_edge();
         self.load_signal.falling_edge();
+        self.pred.falling_edge();
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.reg_write.snapshot(),
+            self.wb_sel.snapshot(),
+            self.mem_write.snapshot(),
+            self.jal_.snapshot(),
+            self.branch_sel.snapshot(),
+            self.pc_sel.snapshot(),
+            self.imm_sel.snapshot(),
+            self.alu_ctrl.snapshot(),
+            self.branch_type.snapshot(),
+            self.npc.snapshot(),
+            self.pc.snapshot(),
+            self.rs1_data.snapshot(),
+            self.rs2_data.snapshot(),
+            self.imm.snapshot(),
+            self.rs1.snapshot(),
+            self.rd.snapshot(),
+            self.rs2.snapshot(),
+            self.opco.snapshot(),
+            self.load_signal.snapshot(),
+            self.pred.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.reg_write.restore(&group[0]);
+        self.wb_sel.restore(&group[1]);
+        self.mem_write.restore(&group[2]);
+        self.jal_.restore(&group[3]);
+        self.branch_sel.restore(&group[4]);
+        self.pc_sel.restore(&group[5]);
+        self.imm_sel.restore(&group[6]);
+        self.alu_ctrl.restore(&group[7]);
+        self.branch_type.restore(&group[8]);
+        self.npc.restore(&group[9]);
+        self.pc.restore(&group[10]);
+        self.rs1_data.restore(&group[11]);
+        self.rs2_data.restore(&group[12]);
+        self.imm.restore(&group[13]);
+        self.rs1.restore(&group[14]);
+        self.rd.restore(&group[15]);
+        self.rs2.restore(&group[16]);
+        self.opco.restore(&group[17]);
+        self.load_signal.restore(&group[18]);
+        self.pred.restore(&group[19]);
+    }
+    fn set(&mut self, name: &str, value: u32) -> bool {
+        let reg = match name {
+            "reg_write" => &self.reg_write,
+            "wb_sel" => &self.wb_sel,
+            "mem_write" => &self.mem_write,
+            "jal_" => &self.jal_,
+            "npc_sel" => &self.branch_sel,
+            "pc_sel" => &self.pc_sel,
+            "imm_sel" => &self.imm_sel,
+            "alu_ctrl" => &self.alu_ctrl,
+            "br_type" => &self.branch_type,
+            "npc" => &self.npc,
+            "pc" => &self.pc,
+            "rs1_data" => &self.rs1_data,
+            "rs2_data" => &self.rs2_data,
+            "imm" => &self.imm,
+            "rs1" => &self.rs1,
+            "rd" => &self.rd,
+            "rs2" => &self.rs2,
+            "opco" => &self.opco,
+            "load" => &self.load_signal,
+            "pred" => &self.pred,
+            _ => return false,
+        };
+        reg.set("out", value)
     }
     fn inout(&self) -> Vec<(&'static str, u32, u32)> {
         vec![
@@ -320,6 +404,7 @@ impl Control for IdEx {
                 self.load_signal.input()[0].1,
                 self.load_signal.output()[0].1,
             ),
+            ("pred", self.pred.input()[0].1, self.pred.output()[0].1),
             ("clr", self.reg_write.input()[2].1, 0),
         ]
     }
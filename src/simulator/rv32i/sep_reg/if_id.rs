@@ -5,6 +5,7 @@ pub enum Alloc {
     Npc,
     Pc,
     Instruction,
+    Pred,
 }
 pub enum Connect {
     Npc = 0,
@@ -12,6 +13,7 @@ pub enum Connect {
     Instruction = 2,
     Enable = 3,
     Clear = 4,
+    Pred = 5,
 }
 
 #[derive(Default)]
@@ -19,6 +21,7 @@ pub struct IfIdBuilder {
     pub npc: RegBuilder,
     pub pc: RegBuilder,
     pub instruction: RegBuilder,
+    pub pred: RegBuilder,
 }
 impl ControlBuilder for IfIdBuilder {
     fn build(self) -> ControlRef {
@@ -26,6 +29,7 @@ impl ControlBuilder for IfIdBuilder {
             npc: self.npc.build(),
             pc: self.pc.build(),
             instruction: self.instruction.build(),
+            pred: self.pred.build(),
         }
         .into()
     }
@@ -38,6 +42,7 @@ impl PortBuilder for IfIdBuilder {
             Alloc::Npc => self.npc.alloc(RegAlloc::Out),
             Alloc::Pc => self.pc.alloc(RegAlloc::Out),
             Alloc::Instruction => self.instruction.alloc(RegAlloc::Out),
+            Alloc::Pred => self.pred.alloc(RegAlloc::Out),
         }
     }
     fn connect(&mut self, pin: PortRef, id: Connect) {
@@ -45,15 +50,18 @@ impl PortBuilder for IfIdBuilder {
             Connect::Npc => self.npc.connect(pin, RegConnect::In),
             Connect::Pc => self.pc.connect(pin, RegConnect::In),
             Connect::Instruction => self.instruction.connect(pin, RegConnect::In),
+            Connect::Pred => self.pred.connect(pin, RegConnect::In),
             Connect::Enable => {
                 self.npc.connect(pin.clone(), RegConnect::Enable);
                 self.pc.connect(pin.clone(), RegConnect::Enable);
-                self.instruction.connect(pin, RegConnect::Enable);
+                self.instruction.connect(pin.clone(), RegConnect::Enable);
+                self.pred.connect(pin, RegConnect::Enable);
             }
             Connect::Clear => {
                 self.npc.connect(pin.clone(), RegConnect::Clear);
                 self.pc.connect(pin.clone(), RegConnect::Clear);
-                self.instruction.connect(pin, RegConnect::Clear);
+                self.instruction.connect(pin.clone(), RegConnect::Clear);
+                self.pred.connect(pin, RegConnect::Clear);
             }
         }
     }
@@ -64,17 +72,45 @@ pub struct IfId {
     pub npc: ControlRef,
     pub pc: ControlRef,
     pub instruction: ControlRef,
+    pub pred: ControlRef,
 }
 impl Control for IfId {
     fn rasing_edge(&mut self) {
         self.npc.rasing_edge();
         self.pc.rasing_edge();
         self.instruction.rasing_edge();
+        self.pred.rasing_edge();
     }
     fn falling_edge(&mut self) {
         self.npc.falling_edge();
         self.pc.falling_edge();
         self.instruction.falling_edge();
+        self.pred.falling_edge();
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.npc.snapshot(),
+            self.pc.snapshot(),
+            self.instruction.snapshot(),
+            self.pred.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.npc.restore(&group[0]);
+        self.pc.restore(&group[1]);
+        self.instruction.restore(&group[2]);
+        self.pred.restore(&group[3]);
+    }
+    fn set(&mut self, name: &str, value: u32) -> bool {
+        let reg = match name {
+            "npc" => &self.npc,
+            "pc" => &self.pc,
+            "inst" => &self.instruction,
+            "pred" => &self.pred,
+            _ => return false,
+        };
+        reg.set("out", value)
     }
     fn inout(&self) -> Vec<(&'static str, u32, u32)> {
         vec![
@@ -85,6 +121,7 @@ impl Control for IfId {
                 self.instruction.input()[0].1,
                 self.instruction.output()[0].1,
             ),
+            ("pred", self.pred.input()[0].1, self.pred.output()[0].1),
         ]
     }
 }
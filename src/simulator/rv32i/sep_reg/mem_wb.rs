@@ -111,6 +111,37 @@ impl Control for MemWb {
         self.mem_data.falling_edge();
         self.rd.falling_edge();
     }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::Group(vec![
+            self.reg_write.snapshot(),
+            self.wb_sel.snapshot(),
+            self.npc.snapshot(),
+            self.alu_res.snapshot(),
+            self.mem_data.snapshot(),
+            self.rd.snapshot(),
+        ])
+    }
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let group = snapshot.group();
+        self.reg_write.restore(&group[0]);
+        self.wb_sel.restore(&group[1]);
+        self.npc.restore(&group[2]);
+        self.alu_res.restore(&group[3]);
+        self.mem_data.restore(&group[4]);
+        self.rd.restore(&group[5]);
+    }
+    fn set(&mut self, name: &str, value: u32) -> bool {
+        let reg = match name {
+            "reg_write" => &self.reg_write,
+            "wb_sel" => &self.wb_sel,
+            "npc" => &self.npc,
+            "alu_res" => &self.alu_res,
+            "mem_data" => &self.mem_data,
+            "rd" => &self.rd,
+            _ => return false,
+        };
+        reg.set("out", value)
+    }
     fn inout(&self) -> Vec<(&'static str, u32, u32)> {
         vec![
             (
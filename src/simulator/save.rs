@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+
+use super::{Rv32i, Rv32iBuilder};
+use crate::common::abi::*;
+use crate::common::build::MEM_PAGE_SIZE;
+use crate::config::Program;
+
+pub const SAVE_VERSION: u32 = 4;
+
+// file format of a saved simulator: the program it was built from
+// and the snapshot of every stateful component, cycle count included
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub program: Program,
+    pub state: Snapshot,
+}
+
+pub fn save(rv: &Rv32i, path: &str) -> io::Result<()> {
+    let file = SaveFile {
+        version: SAVE_VERSION,
+        program: rv.pgbak.clone(),
+        state: rv.snapshot(),
+    };
+    let data = serde_json::to_string(&file).map_err(io::Error::other)?;
+    fs::write(path, data)
+}
+
+pub fn load(path: &str) -> io::Result<Rv32i> {
+    let data = fs::read_to_string(path)?;
+    let file: SaveFile =
+        serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if file.version != SAVE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "unsupported save version {}, expected {}",
+                file.version, SAVE_VERSION
+            ),
+        ));
+    }
+    file.program
+        .predictor
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut rv = Rv32iBuilder::new(file.program).slf_build();
+    fits(&file.state, &rv.snapshot())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("state: {}", e)))?;
+    rv.restore(&file.state);
+    Ok(rv)
+}
+
+// whether `state` has the shape of `like`, the snapshot of a simulator built from the
+// same program, so that restoring it finds every group, register and page it indexes
+fn fits(state: &Snapshot, like: &Snapshot) -> Result<(), String> {
+    match (state, like) {
+        (Snapshot::Group(group), Snapshot::Group(like)) if group.len() == like.len() => group
+            .iter()
+            .zip(like)
+            .try_for_each(|(state, like)| fits(state, like)),
+        (Snapshot::Words(words), Snapshot::Words(like)) if words.len() == like.len() => Ok(()),
+        (Snapshot::Pages(pages), Snapshot::Pages(_)) => {
+            match pages.iter().find(|(_, page)| page.len() != MEM_PAGE_SIZE) {
+                Some((page, data)) => Err(format!("page {:#x} has {} bytes", page, data.len())),
+                None => Ok(()),
+            }
+        }
+        (Snapshot::Asm { stages, .. }, Snapshot::Asm { stages: like, .. })
+            if stages.len() == like.len() =>
+        {
+            Ok(())
+        }
+        (Snapshot::Asm { .. }, _) | (Snapshot::Group(_), _) | (Snapshot::Words(_), _) => {
+            Err(format!("expected {}, found {}", shape(like), shape(state)))
+        }
+        _ if std::mem::discriminant(state) == std::mem::discriminant(like) => Ok(()),
+        _ => Err(format!("expected {}, found {}", shape(like), shape(state))),
+    }
+}
+fn shape(snapshot: &Snapshot) -> String {
+    match snapshot {
+        Snapshot::None => "nothing".into(),
+        Snapshot::Word(_) => "a word".into(),
+        Snapshot::Words(words) => format!("{} words", words.len()),
+        Snapshot::Counter(_) => "a counter".into(),
+        Snapshot::Counters(_) => "counters".into(),
+        Snapshot::Pages(_) => "pages".into(),
+        Snapshot::Asm { stages, .. } => format!("{} stages", stages.len()),
+        Snapshot::Group(group) => format!("a group of {}", group.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        // 0: addi x1, x0, 1
+        // 4: add x5, x5, x1
+        // 8: sw x5, 0(x0)
+        // c: jal x0, -8
+        let insts = [0x00100093u32, 0x001282b3, 0x00502023, 0xff9ff06f];
+        let pg = Program::from_words(&insts);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        for _ in 0..50 {
+            rv.step();
+        }
+        let path = std::env::temp_dir().join("rv-simulator-test-save.json");
+        let path = path.to_str().unwrap();
+        save(&rv, path).unwrap();
+        let mut restored = load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(restored.cycle, 50);
+        assert_eq!(restored.snapshot(), rv.snapshot());
+        rv.step();
+        restored.step();
+        assert_eq!(restored.snapshot(), rv.snapshot());
+    }
+    #[test]
+    fn test_load_invalid() {
+        let rv = Rv32iBuilder::new(Program::default()).slf_build();
+        let mut registers = rv.snapshot();
+        let Snapshot::Group(group) = &mut registers else {
+            unreachable!()
+        };
+        group[1] = Snapshot::Words(vec![0; 3]);
+        let mut pages = rv.snapshot();
+        let Snapshot::Group(group) = &mut pages else {
+            unreachable!()
+        };
+        group[2] = Snapshot::Pages([(0, vec![0; 3])].into());
+        let path = std::env::temp_dir().join("rv-simulator-test-load-invalid.json");
+        let path = path.to_str().unwrap();
+        let mut neural = Program::default();
+        neural.predictor.kind = "neural".into();
+        let mut huge = Program::default();
+        huge.predictor.bits = 31;
+        let state = rv.snapshot();
+        for (program, state) in [
+            (Program::default(), Snapshot::Word(0)),
+            (Program::default(), registers),
+            (Program::default(), pages),
+            (neural, state.clone()),
+            (huge, state),
+        ] {
+            let file = SaveFile {
+                version: SAVE_VERSION,
+                program,
+                state,
+            };
+            std::fs::write(path, serde_json::to_string(&file).unwrap()).unwrap();
+            let err = load(path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", err);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+    #[test]
+    fn test_load_old() {
+        // a save of version 3, from before the predictor latches and the funct3 of EX/MEM
+        let rv = Rv32iBuilder::new(Program::default()).slf_build();
+        let mut state = rv.snapshot();
+        let Snapshot::Group(group) = &mut state else {
+            unreachable!()
+        };
+        for (i, len) in [(0, 2), (3, 3), (4, 19), (5, 8)] {
+            let Snapshot::Group(component) = &mut group[i] else {
+                unreachable!()
+            };
+            component.truncate(len);
+        }
+        let path = std::env::temp_dir().join("rv-simulator-test-load-old.json");
+        let path = path.to_str().unwrap();
+        for (version, message) in [
+            (3, "unsupported save version 3, expected 4"),
+            (SAVE_VERSION, "state: "),
+        ] {
+            let file = SaveFile {
+                version,
+                program: Program::default(),
+                state: state.clone(),
+            };
+            std::fs::write(path, serde_json::to_string(&file).unwrap()).unwrap();
+            let err = load(path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", err);
+            assert!(err.to_string().starts_with(message), "{}", err);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::isa::mnemonic;
+use super::Rv32i;
+use crate::common::abi::*;
+
+fn signal(signals: &[(&'static str, u32)], name: &str) -> u32 {
+    signals
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| *v)
+        .unwrap_or_else(|| panic!("stats: no signal {}", name))
+}
+
+// counts what the pipeline does every cycle, sampled before the rising edge
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    // cycles the hazard unit stalled IF/ID for a load-use dependency
+    pub load_use_stalls: usize,
+    // conditional branches resolved in EX
+    pub branches: usize,
+    // branches and jumps fetch predicted wrong, each flushing IF/ID and ID/EX
+    pub branch_flushes: usize,
+    pub jump_flushes: usize,
+    // operands taken from the bypass paths instead of ID/EX
+    pub rs1_from_ex_mem: usize,
+    pub rs1_from_mem_wb: usize,
+    pub rs2_from_ex_mem: usize,
+    pub rs2_from_mem_wb: usize,
+    // retired instructions per opcode
+    pub opcodes: BTreeMap<String, usize>,
+}
+impl Stats {
+    pub fn observe(&mut self, rv: &Rv32i) {
+        let stages = rv.asm.stages();
+        // nothing younger than a halting instruction retires
+        let halts = |stages: &[Option<u32>]| stages.iter().flatten().any(|&pc| rv.halts(pc));
+        if signal(&rv.hazard.output(), "raw") == 1 && !halts(&stages[2..]) {
+            self.load_use_stalls += 1;
+        }
+        if stages[2].is_some() && !halts(&stages[3..]) {
+            let ex = rv.ex.inner_signal();
+            let id_ex = rv.id_ex.inout();
+            let latch = |name| id_ex.iter().find(|s| s.0 == name).unwrap().2;
+            if latch("npc_sel") == 1 {
+                self.branches += 1;
+            }
+            if signal(&ex, "npc_en") == 1 {
+                if latch("jal_") == 1 {
+                    self.jump_flushes += 1;
+                } else {
+                    self.branch_flushes += 1;
+                }
+            }
+            match signal(&ex, "fwd1") {
+                1 => self.rs1_from_ex_mem += 1,
+                2 => self.rs1_from_mem_wb += 1,
+                _ => {}
+            }
+            match signal(&ex, "fwd2") {
+                1 => self.rs2_from_ex_mem += 1,
+                2 => self.rs2_from_mem_wb += 1,
+                _ => {}
+            }
+        }
+        self.retire(rv);
+    }
+    // count the instruction in write back as retired
+    pub fn retire(&mut self, rv: &Rv32i) {
+        if let Some(pc) = rv.asm.stages()[4] {
+            let op = mnemonic(rv.imem.read(pc as usize));
+            *self.opcodes.entry(op.to_string()).or_default() += 1;
+        }
+    }
+    pub fn counters(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("load_use_stalls", self.load_use_stalls),
+            ("branches", self.branches),
+            ("branch_flushes", self.branch_flushes),
+            ("jump_flushes", self.jump_flushes),
+            ("rs1_from_ex_mem", self.rs1_from_ex_mem),
+            ("rs1_from_mem_wb", self.rs1_from_mem_wb),
+            ("rs2_from_ex_mem", self.rs2_from_ex_mem),
+            ("rs2_from_mem_wb", self.rs2_from_mem_wb),
+        ]
+    }
+    // share of the conditional branches fetch predicted right, none before the first
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.branches > 0).then(|| 1.0 - self.branch_flushes as f64 / self.branches as f64)
+    }
+    pub fn snapshot(&self) -> Snapshot {
+        let mut counters = self
+            .counters()
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect::<BTreeMap<_, _>>();
+        counters.extend(
+            self.opcodes
+                .iter()
+                .map(|(op, count)| (format!("op.{}", op), *count)),
+        );
+        Snapshot::Counters(counters)
+    }
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let Snapshot::Counters(counters) = snapshot else {
+            panic!("Stats: invalid snapshot {:?}", snapshot);
+        };
+        let get = |name: &str| counters.get(name).copied().unwrap_or_default();
+        *self = Stats {
+            load_use_stalls: get("load_use_stalls"),
+            branches: get("branches"),
+            branch_flushes: get("branch_flushes"),
+            jump_flushes: get("jump_flushes"),
+            rs1_from_ex_mem: get("rs1_from_ex_mem"),
+            rs1_from_mem_wb: get("rs1_from_mem_wb"),
+            rs2_from_ex_mem: get("rs2_from_ex_mem"),
+            rs2_from_mem_wb: get("rs2_from_mem_wb"),
+            opcodes: counters
+                .iter()
+                .filter_map(|(name, count)| Some((name.strip_prefix("op.")?.to_string(), *count)))
+                .collect(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    #[test]
+    fn test_stats() {
+        // 0:  addi x1, x0, 1
+        // 4:  add x5, x5, x1
+        // 8:  sw x5, 0(x0)
+        // c:  lw x6, 0(x0)
+        // 10: add x7, x6, x5
+        // 14: jal x0, 8
+        // 18: addi x0, x0, 0
+        // 1c: beq x0, x0, -24
+        let insts = [
+            0x00100093u32,
+            0x001282b3,
+            0x00502023,
+            0x00002303,
+            0x005303b3,
+            0x0080006f,
+            0x00000013,
+            0xfe0004e3,
+        ];
+        let pg = Program::from_words(&insts);
+        let mut rv = Rv32iBuilder::new(pg.clone()).slf_build();
+        // four passes of the loop body
+        while rv.retired() < 1 + 6 * 4 {
+            rv.step();
+        }
+        let stats = &rv.stats;
+        // pipeline fill, one cycle per instruction, a stall per pass and two
+        // bubbles per taken jump or branch, but the last branch's are still in flight
+        assert_eq!(rv.cycle, 4 + 25 + 4 + 2 * 7);
+        assert_eq!(stats.load_use_stalls, 4);
+        assert_eq!(stats.jump_flushes, 4);
+        assert_eq!(stats.branch_flushes, 4);
+        assert_eq!((stats.branches, stats.accuracy()), (4, Some(0.0)));
+        // lw -> add x7 through MEM/WB, add x5 -> sw through EX/MEM
+        // and addi x1 -> add x5 once through EX/MEM
+        assert_eq!(stats.rs1_from_mem_wb, 4);
+        assert_eq!(stats.rs2_from_ex_mem, 5);
+        assert_eq!(stats.rs1_from_ex_mem + stats.rs2_from_mem_wb, 0);
+        assert_eq!(stats.opcodes["add"], 8);
+        assert_eq!(stats.opcodes["beq"], 4);
+        assert_eq!(stats.opcodes.values().sum::<usize>(), rv.retired());
+        let mut restored = Stats::default();
+        restored.restore(&stats.snapshot());
+        assert_eq!(&restored, stats);
+        // the jal and the backward beq are fetched from their targets at once
+        let mut pg = pg;
+        pg.predictor.kind = "btfn".to_string();
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        while rv.retired() < 1 + 6 * 4 {
+            rv.step();
+        }
+        assert_eq!(rv.cycle, 4 + 25 + 4);
+        assert_eq!((rv.stats.jump_flushes, rv.stats.branch_flushes), (0, 0));
+        assert_eq!(rv.stats.accuracy(), Some(1.0));
+        assert_eq!(rv.stats.opcodes["beq"], 4);
+    }
+}
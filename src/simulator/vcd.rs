@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use super::Rv32i;
+use crate::common::abi::*;
+
+// half of a cycle in the VCD timescale
+const HALF_PERIOD: usize = 5;
+
+// every named signal of a component: inputs, outputs, both sides of
+// the pipeline register fields and the inner signals
+pub fn signals(control: &ControlRef) -> Vec<(String, u32)> {
+    let mut signals = vec![];
+    signals.extend(control.input().into_iter().map(|(n, v)| (n.to_string(), v)));
+    signals.extend(
+        control
+            .output()
+            .into_iter()
+            .map(|(n, v)| (n.to_string(), v)),
+    );
+    for (n, in_, out) in control.inout() {
+        signals.push((format!("{}_in", n), in_));
+        signals.push((format!("{}_out", n), out));
+    }
+    signals.extend(
+        control
+            .inner_signal()
+            .into_iter()
+            .map(|(n, v)| (n.to_string(), v)),
+    );
+    // sub-components listed together may share a name, e.g. the `out` of two muxes
+    let mut seen = HashMap::new();
+    for (name, _) in signals.iter_mut() {
+        let count = seen.entry(name.clone()).or_insert(0);
+        if *count > 0 {
+            *name = format!("{}_{}", name, count);
+        }
+        *count += 1;
+    }
+    signals
+}
+
+// short printable identifier of the `index`th variable
+fn code(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+// writes a VCD waveform of all the named signals of `Rv32i`, one `record` after every cycle
+pub struct Vcd<W: Write> {
+    out: W,
+    // last value dumped of every signal, in header order
+    last: Vec<Option<u32>>,
+}
+impl Vcd<BufWriter<File>> {
+    pub fn create(path: &str, rv: &Rv32i) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), rv)
+    }
+}
+impl<W: Write> Vcd<W> {
+    pub fn new(mut out: W, rv: &Rv32i) -> io::Result<Self> {
+        writeln!(out, "$version rv-simulator $end")?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module rv32i $end")?;
+        writeln!(out, "$var wire 1 {} clk $end", code(0))?;
+        let mut last = vec![];
+        for (name, control) in rv.components() {
+            writeln!(out, "$scope module {} $end", name)?;
+            for (signal, _) in signals(control) {
+                last.push(None);
+                writeln!(out, "$var wire 32 {} {} $end", code(last.len()), signal)?;
+            }
+            writeln!(out, "$upscope $end")?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        let mut vcd = Self { out, last };
+        vcd.record(rv)?;
+        Ok(vcd)
+    }
+    // dump the signals that changed since the last record
+    pub fn record(&mut self, rv: &Rv32i) -> io::Result<()> {
+        let time = rv.cycle * 2 * HALF_PERIOD;
+        writeln!(self.out, "#{}", time)?;
+        writeln!(self.out, "1{}", code(0))?;
+        let values = rv
+            .components()
+            .into_iter()
+            .flat_map(|(_, control)| signals(control));
+        for (i, ((_, value), last)) in values.zip(&mut self.last).enumerate() {
+            if *last != Some(value) {
+                writeln!(self.out, "b{:b} {}", value, code(i + 1))?;
+                *last = Some(value);
+            }
+        }
+        writeln!(self.out, "#{}", time + HALF_PERIOD)?;
+        writeln!(self.out, "0{}", code(0))
+    }
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    #[test]
+    fn test_code() {
+        assert_eq!(code(0), "!");
+        assert_eq!(code(93), "~");
+        assert_eq!(code(94), "!!");
+        assert_eq!(code(94 + 94 * 94), "!!!");
+    }
+    #[test]
+    fn test_vcd() {
+        // addi a0, x0, 42
+        // ecall
+        let insts = [0x02a00513u32, 0x00000073];
+        let pg = Program::from_words(&insts);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        let mut vcd = Vcd::new(vec![], &rv).unwrap();
+        for _ in 0..4 {
+            rv.step();
+            vcd.record(&rv).unwrap();
+        }
+        let dump = String::from_utf8(vcd.out).unwrap();
+        let (header, body) = dump.split_once("$enddefinitions $end\n").unwrap();
+        assert!(header.contains("$scope module id_ex $end"));
+        // names are unique within each scope
+        for scope in header.split("$scope").skip(2) {
+            let names = scope
+                .lines()
+                .filter_map(|l| l.strip_prefix("$var wire 32 "))
+                .map(|l| l.split(' ').nth(1).unwrap())
+                .collect::<Vec<_>>();
+            let mut unique = names.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(names.len(), unique.len());
+        }
+        assert!(body.starts_with("#0\n1!\n"));
+        assert!(body.contains("#40\n1!\n"));
+        assert!(body.ends_with("#45\n0!\n"));
+        // the immediate of the addi reaches ID/EX in the second cycle
+        let (_, imm) = header
+            .split_once("$scope module id_ex $end")
+            .unwrap()
+            .1
+            .lines()
+            .find_map(|l| l.strip_suffix(" imm_out $end"))
+            .unwrap()
+            .rsplit_once(' ')
+            .unwrap();
+        assert!(body.contains(&format!("b101010 {}\n", imm)));
+    }
+}
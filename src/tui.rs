@@ -1,7 +1,18 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{self, stdout, Stdout};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+mod command;
+mod datapath;
+mod hazards;
+mod inspect;
 mod signal;
+mod theme;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::*,
 };
@@ -10,21 +21,28 @@ use ratatui::{
     widgets::{block::Title, *},
 };
 
-use crate::{abi::Control, simulator::Rv32i};
+use crate::common::abi::ControlRef;
+use crate::config::{LayoutConfig, TuiConfig};
+use crate::script::Script;
+use crate::simulator::{
+    self, batch_path, Cell, Commit, Diagram, Figure, History, Rv32i, CHECKPOINT_INTERVAL,
+};
+use command::Command;
+use theme::{Action, Keys, Theme};
 
 /// A type alias for the terminal type used in this application
 pub type Backend = Terminal<CrosstermBackend<Stdout>>;
 
 /// Initialize the terminal
 pub fn init() -> io::Result<Backend> {
-    execute!(stdout(), EnterAlternateScreen)?;
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
     enable_raw_mode()?;
     Terminal::new(CrosstermBackend::new(stdout()))
 }
 
 /// Restore the terminal to its original state
 pub fn restore() -> io::Result<()> {
-    execute!(stdout(), LeaveAlternateScreen)?;
+    execute!(stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
     disable_raw_mode()?;
     Ok(())
 }
@@ -32,210 +50,639 @@ pub fn restore() -> io::Result<()> {
 #[derive(Debug)]
 pub struct App {
     simulator: Rv32i,
+    history: History,
     exit: bool,
-    cycle: usize,
     tab: usize,
+    goto: Option<String>,
+    save_path: String,
+    message: Option<String>,
+    // pipeline diagram rows scrolled up from the newest, and cycles back from this one
+    pipeline_scroll: usize,
+    pipeline_offset: usize,
+    script: Option<Script>,
+    // the `:` prompt and the lines entered before, walked with Up and Down
+    command: Option<String>,
+    commands: Vec<String>,
+    command_index: usize,
+    breakpoints: BTreeSet<u32>,
+    // word addresses
+    watchpoints: BTreeSet<u32>,
+    // address and length in bytes of the data memory in the Memory tab
+    mem_view: (u32, u32),
+    keys: Keys,
+    theme: Theme,
+    layout: LayoutConfig,
+    // terminal size at the last draw, where the mouse looks for the panes
+    area: Rect,
+    // rows each table is scrolled down by
+    scroll: BTreeMap<Pane, usize>,
+    // ASM rows moved away from the instructions in flight
+    asm_scroll: isize,
+    // the row clicked last, the address for the ASM pane
+    selected: Option<(Pane, usize)>,
+    // stepping on its own at `speed` cycles per second, from `tick` on
+    playing: bool,
+    speed: u32,
+    tick: Instant,
+}
+
+// a part of the screen, for the mouse to find
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Pane {
+    Tabs,
+    // a pipeline register in the Sep Reg tab
+    Sep(usize),
+    Datapath,
+    Ex,
+    Hazard,
+    Stats(usize),
+    Pipeline,
+    Memory,
+    Asm,
+    Decode,
+    Footer,
 }
+const SEPS: [&str; 4] = ["if_id", "id_ex", "ex_mem", "mem_wb"];
+
+// cycles a run goes on for before it gives up waiting for a stop
+const RUN_LIMIT: usize = 1_000_000;
+const SPEED: u32 = 4;
+const MAX_SPEED: u32 = 1024;
+// the screen is redrawn at most this often while playing, faster speeds step
+// several cycles a frame
+const FRAME: Duration = Duration::from_millis(33);
+const TABS: [&str; 5] = ["Sep Reg", "Signal", "Stats", "Pipeline", "Memory"];
+const PIPELINE_TEXT: &str = "pipeline.txt";
+const PIPELINE_CSV: &str = "pipeline.csv";
+const SNAPSHOT: &str = "snapshot.html";
 
 impl App {
     pub fn new(sm: Rv32i) -> Self {
         Self {
+            history: History::new(&sm, CHECKPOINT_INTERVAL),
             simulator: sm,
             exit: false,
-            cycle: 0,
             tab: 0,
+            goto: None,
+            save_path: String::from("rv-simulator.json"),
+            message: None,
+            pipeline_scroll: 0,
+            pipeline_offset: 0,
+            script: None,
+            command: None,
+            commands: vec![],
+            command_index: 0,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            mem_view: (0, 64),
+            keys: Keys::default(),
+            theme: Theme::default(),
+            layout: LayoutConfig::default(),
+            area: Rect::default(),
+            scroll: BTreeMap::new(),
+            asm_scroll: 0,
+            selected: None,
+            playing: false,
+            speed: SPEED,
+            tick: Instant::now(),
         }
     }
+    pub fn set_save_path(&mut self, path: String) {
+        self.save_path = path;
+    }
+    pub fn set_script(&mut self, script: Script) {
+        self.script = Some(script);
+    }
+    // keys, colours and layout from the [tui] section of the config
+    pub fn set_config(&mut self, config: &TuiConfig) -> Result<(), String> {
+        self.keys = Keys::new(&config.keys)?;
+        self.theme = Theme::new(config)?;
+        self.layout = config.layout;
+        self.speed = config.speed.unwrap_or(SPEED).clamp(1, MAX_SPEED);
+        Ok(())
+    }
     /// runs the application's main loop until the user quits
     pub fn run(&mut self, terminal: &mut Backend) -> io::Result<()> {
         while !self.exit {
+            self.area = terminal.size()?;
             terminal.draw(|frame| self.render_frame(frame))?;
-            self.handle_events()?;
+            if !self.playing {
+                self.handle_events()?;
+                continue;
+            }
+            // wait for a key no longer than the next frame is due
+            let period = FRAME.max(Duration::from_secs(1) / self.speed);
+            let timeout = (self.tick + period).saturating_duration_since(Instant::now());
+            if event::poll(timeout)? {
+                self.handle_events()?;
+            }
+            if self.playing {
+                self.play(Instant::now());
+            }
         }
         Ok(())
     }
-    fn render_seps(&self, chunk: Rect, buffer: &mut Buffer) {
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage(25),
-                Constraint::Percentage(25),
-                Constraint::Percentage(25),
-                Constraint::Percentage(25),
-            ])
-            .split(chunk);
-        let signals = [
-            self.simulator.if_id.inout(),
-            self.simulator.id_ex.inout(),
-            self.simulator.ex_mem.inout(),
-            self.simulator.mem_wb.inout(),
-        ];
-        chunks
-            .iter()
-            .zip(signals)
-            .zip([" IF/ID ", " ID/EX ", " EX/MEM ", " MEM/WB "].iter())
-            .for_each(|((chunk, signal), title)| {
-                let rows = signal.into_iter().map(|(n, in_, out)| {
-                    Row::new(vec![
-                        n.to_string(),
-                        format!("{:x}", in_),
-                        format!("{:x}", out),
-                    ])
-                });
-                let table = Table::new(
-                    rows,
-                    [
-                        Constraint::Fill(1),
-                        Constraint::Length(8),
-                        Constraint::Length(8),
-                    ],
-                )
-                .block(
-                    Block::default()
-                        .title(*title)
-                        .title_alignment(Alignment::Center)
-                        .borders(Borders::ALL),
-                )
-                .header(Row::new(vec!["Name", "In", "Out"]))
-                .column_spacing(1);
-                Widget::render(table, *chunk, buffer);
-            });
+    // the pipeline register SEPS[index], a field is inspected by clicking it
+    fn render_sep(&self, index: usize, chunk: Rect, buffer: &mut Buffer) {
+        let (_, control) = self.sep(index);
+        let rows = control
+            .inout()
+            .into_iter()
+            .map(|(n, in_, out)| {
+                Row::new(vec![
+                    n.to_string(),
+                    format!("{:x}", in_),
+                    format!("{:x}", out),
+                ])
+            })
+            .collect();
+        let table = Table::new(
+            self.rows(Pane::Sep(index), rows, chunk),
+            [
+                Constraint::Fill(1),
+                Constraint::Length(8),
+                Constraint::Length(8),
+            ],
+        )
+        .block(
+            Block::default()
+                .title([" IF/ID ", " ID/EX ", " EX/MEM ", " MEM/WB "][index])
+                .title_alignment(Alignment::Center)
+                .borders(Borders::ALL),
+        )
+        .header(Row::new(vec!["Name", "In", "Out"]))
+        .column_spacing(1);
+        Widget::render(table, chunk, buffer);
     }
-    fn render_stage(&self, chunk: Rect, buffer: &mut Buffer) {
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(chunk);
-
-        let rows = self
+    fn sep(&self, index: usize) -> (&'static str, &ControlRef) {
+        let name = SEPS[index];
+        let (_, control) = self
             .simulator
-            .ex
-            .inner_signal()
+            .components()
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .unwrap();
+        (name, control)
+    }
+    // the rows of a bordered table with a header that fit in `area` from its scroll
+    // offset, the selected one reversed
+    fn rows<'a>(&self, pane: Pane, rows: Vec<Row<'a>>, area: Rect) -> Vec<Row<'a>> {
+        let offset = self.offset(pane, rows.len(), area);
+        rows.into_iter()
+            .enumerate()
+            .skip(offset)
+            .map(|(i, row)| match self.selected == Some((pane, i)) {
+                true => row.add_modifier(Modifier::REVERSED),
+                false => row,
+            })
+            .collect()
+    }
+    // the scroll offset of the pane, within what keeps the last of `len` rows in view
+    fn offset(&self, pane: Pane, len: usize, area: Rect) -> usize {
+        let visible = area.height.saturating_sub(3) as usize;
+        let offset = self.scroll.get(&pane).copied().unwrap_or_default();
+        offset.min(len.saturating_sub(visible))
+    }
+    // the rows of the table in the pane
+    fn len(&self, pane: Pane) -> usize {
+        match pane {
+            Pane::Sep(index) => self.sep(index).1.inout().len(),
+            Pane::Ex => self.simulator.ex.inner_signal().len(),
+            Pane::Hazard => self.simulator.hazard.output().len(),
+            Pane::Stats(index) => self.stats(index).2.len(),
+            Pane::Decode => inspect::rows(&self.simulator).len(),
+            _ => 0,
+        }
+    }
+    // the EX or hazard signals, when the datapath does not fit
+    fn render_signals(&self, pane: Pane, chunk: Rect, buffer: &mut Buffer) {
+        let (title, signals) = match pane {
+            Pane::Ex => (" EX ", self.simulator.ex.inner_signal()),
+            _ => (" Hazard ", self.simulator.hazard.output()),
+        };
+        let rows = signals
             .into_iter()
             .map(|(name, value)| Row::new(vec![name.to_string(), format!("{:x}", value)]))
             .collect::<Vec<_>>();
         let table = Table::new(
-            rows,
+            self.rows(pane, rows, chunk),
             [Constraint::Percentage(50), Constraint::Percentage(50)],
         )
         .block(
             Block::default()
-                .title(" EX ")
+                .title(title)
                 .title_alignment(Alignment::Center)
                 .borders(Borders::ALL),
         )
         .header(Row::new(vec!["Name", "Value"]))
         .column_spacing(1);
-        Widget::render(table, chunks[0], buffer);
-
-        let rows = self
-            .simulator
-            .hazard
-            .output()
-            .into_iter()
-            .map(|(name, value)| Row::new(vec![name.to_string(), format!("{:x}", value)]))
-            .collect::<Vec<_>>();
+        Widget::render(table, chunk, buffer);
+    }
+    // the title, header and rows of the pipeline counters, or the retired opcodes
+    fn stats(&self, index: usize) -> (&'static str, &'static str, Vec<Row<'static>>) {
+        let sm = &self.simulator;
+        match index {
+            0 => {
+                let mut rows = vec![
+                    Row::new(vec!["cycles".to_string(), sm.cycle.to_string()]),
+                    Row::new(vec!["retired".to_string(), sm.retired().to_string()]),
+                    Row::new(vec!["cpi".to_string(), format!("{:.3}", sm.cpi())]),
+                ];
+                rows.extend(
+                    sm.stats
+                        .counters()
+                        .into_iter()
+                        .map(|(name, count)| Row::new(vec![name.to_string(), count.to_string()])),
+                );
+                if let Some(accuracy) = sm.stats.accuracy() {
+                    rows.push(Row::new(vec![
+                        "branch_accuracy".to_string(),
+                        format!("{:.1}%", accuracy * 100.0),
+                    ]));
+                }
+                (" Pipeline ", "Name", rows)
+            }
+            _ => {
+                let rows = sm
+                    .stats
+                    .opcodes
+                    .iter()
+                    .map(|(op, count)| Row::new(vec![op.to_string(), count.to_string()]))
+                    .collect();
+                (" Retired ", "Opcode", rows)
+            }
+        }
+    }
+    fn render_stats(&self, index: usize, chunk: Rect, buffer: &mut Buffer) {
+        let (title, header, rows) = self.stats(index);
         let table = Table::new(
-            rows,
+            self.rows(Pane::Stats(index), rows, chunk),
             [Constraint::Percentage(50), Constraint::Percentage(50)],
         )
         .block(
             Block::default()
-                .title(" Hazard ")
+                .title(title)
                 .title_alignment(Alignment::Center)
                 .borders(Borders::ALL),
         )
-        .header(Row::new(vec!["Name", "Value"]))
+        .header(Row::new(vec![header, "Count"]))
         .column_spacing(1);
-        Widget::render(table, chunks[1], buffer);
+        Widget::render(table, chunk, buffer);
+    }
+    fn render_pipeline(&self, chunk: Rect, buffer: &mut Buffer) {
+        const LABEL: u16 = 36;
+        let count = (chunk.width.saturating_sub(LABEL + 3) / 5) as usize;
+        let end = (self.simulator.cycle + 1).saturating_sub(self.pipeline_offset);
+        let diagram = Diagram::new(&self.simulator, end.saturating_sub(count)..end);
+        // keep the newest instructions in view unless scrolled up
+        let height = chunk.height.saturating_sub(3) as usize;
+        let skip = diagram
+            .rows
+            .len()
+            .saturating_sub(height + self.pipeline_scroll);
+        let rows = diagram.rows.iter().skip(skip).take(height).map(|row| {
+            let mut cells = vec![Line::from(row.label())];
+            cells.extend(diagram.cycles.clone().map(|cycle| {
+                let style = match row.cell(cycle) {
+                    Some(Cell::Stall(_)) => self.theme.stall,
+                    Some(Cell::Flush) => self.theme.flush,
+                    _ => Style::default(),
+                };
+                Line::from(row.cell(cycle).map(|c| c.to_string()).unwrap_or_default())
+                    .style(style)
+                    .right_aligned()
+            }));
+            Row::new(cells)
+        });
+        let mut widths = vec![Constraint::Length(LABEL)];
+        widths.extend(diagram.cycles.clone().map(|_| Constraint::Length(4)));
+        let mut header = vec![Line::from("Instruction")];
+        header.extend(
+            diagram
+                .cycles
+                .clone()
+                .map(|cycle| Line::from(cycle.to_string()).right_aligned()),
+        );
+        let table = Table::new(rows, widths)
+            .block(
+                Block::default()
+                    .title(" Pipeline ")
+                    .title_alignment(Alignment::Center)
+                    .borders(Borders::ALL),
+            )
+            .header(Row::new(header))
+            .column_spacing(1);
+        Widget::render(table, chunk, buffer);
+    }
+    fn render_memory(&self, chunk: Rect, buffer: &mut Buffer) {
+        let (addr, len) = self.mem_view;
+        let rows = (addr..addr.saturating_add(len))
+            .step_by(16)
+            .map(|row| {
+                let mut cells = vec![format!("{:08x}", row)];
+                cells.extend(
+                    (row..row.saturating_add(16).min(addr.saturating_add(len)))
+                        .step_by(4)
+                        .map(|a| format!("{:08x}", self.simulator.dmem.read(a as usize))),
+                );
+                Row::new(cells)
+            })
+            .collect::<Vec<_>>();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+            ],
+        )
+        .block(
+            Block::default()
+                .title(" Memory ")
+                .title_alignment(Alignment::Center)
+                .borders(Borders::ALL),
+        )
+        .header(Row::new(vec!["Address", "+0", "+4", "+8", "+c"]))
+        .column_spacing(2);
+        Widget::render(table, chunk, buffer);
     }
     fn render_asm(&self, chunk: Rect, buffer: &mut Buffer) {
-        let rows = self
-            .simulator
-            .asm
-            .read(chunk.height as usize)
+        let insts = self.asm_rows(chunk);
+        // forwarding, stalls and flushes of this cycle, with arrows from producer to consumer
+        let notes = hazards::notes(&self.simulator);
+        let pcs = insts.iter().map(|inst| inst.pc).collect::<Vec<_>>();
+        let gutter = hazards::gutter(&notes, &pcs, &self.theme);
+        let gutter_width = gutter.first().map_or(0, |g| g.len()) as u16;
+        let rows = insts
             .into_iter()
-            .map(|inst| {
+            .zip(gutter)
+            .map(|(inst, gutter)| {
                 use crate::build::Stage;
                 let style = match inst.stage {
-                    Stage::Fetch => Style::default().fg(Color::Green),
-                    Stage::Decode => Style::default().fg(Color::Yellow),
-                    Stage::Execute => Style::default().fg(Color::Blue),
-                    Stage::Memory => Style::default().fg(Color::Magenta),
-                    Stage::WriteBack => Style::default().fg(Color::Cyan),
+                    Stage::Fetch => self.theme.stages[0],
+                    Stage::Decode => self.theme.stages[1],
+                    Stage::Execute => self.theme.stages[2],
+                    Stage::Memory => self.theme.stages[3],
+                    Stage::WriteBack => self.theme.stages[4],
                     _ => Style::default(),
                 };
-                Row::new(vec![
-                    Line::from(inst.stage.to_string()).right_aligned(),
-                    Line::from(inst.asm.to_string()).left_aligned(),
+                let notes = notes.iter().filter(|note| note.pc == inst.pc);
+                let flushed = notes.clone().any(|note| note.kind == hazards::Kind::Flush);
+                let mut line = vec![match flushed {
+                    true => Span::styled(
+                        inst.asm.to_string(),
+                        Style::default().add_modifier(Modifier::CROSSED_OUT),
+                    ),
+                    false => Span::raw(inst.asm.to_string()),
+                }];
+                for note in notes {
+                    line.push(Span::raw("  "));
+                    line.push(Span::styled(
+                        note.text.clone(),
+                        note.kind.style(&self.theme),
+                    ));
+                }
+                let mut stage = vec![Span::raw(inst.stage.to_string())];
+                if self.breakpoints.contains(&inst.pc) {
+                    stage.insert(0, Span::styled("■ ", self.theme.breakpoint));
+                }
+                let row = Row::new(vec![
+                    Line::from(stage).right_aligned(),
+                    Line::from(gutter),
+                    Line::from(line).left_aligned(),
                 ])
-                .style(style)
+                .style(style);
+                match self.selected == Some((Pane::Asm, inst.pc as usize)) {
+                    true => row.add_modifier(Modifier::REVERSED),
+                    false => row,
+                }
             })
             .collect::<Vec<_>>();
         let table = Table::new(
             rows,
-            [Constraint::Percentage(30), Constraint::Percentage(70)],
+            [
+                Constraint::Percentage(30),
+                Constraint::Length(gutter_width),
+                Constraint::Fill(1),
+            ],
         )
         .block(
             Block::default()
-                .title(" ASM ")
+                .title(match self.playing {
+                    true => format!(
+                        " ASM (cycle {}, playing at {}/s) ",
+                        self.simulator.cycle, self.speed
+                    ),
+                    false => format!(" ASM (cycle {}) ", self.simulator.cycle),
+                })
                 .title_alignment(Alignment::Center)
                 .borders(Borders::ALL),
         )
         .header(Row::new(vec![
             Line::from("Stage").alignment(Alignment::Center),
+            Line::from(""),
             Line::from("Instruction").alignment(Alignment::Center),
         ]))
         .column_spacing(1);
         Widget::render(table, chunk, buffer);
     }
 
+    // the instructions listed in the ASM pane, moved by the scroll
+    fn asm_rows(&self, chunk: Rect) -> Vec<crate::build::Inst> {
+        self.simulator
+            .asm
+            .read(chunk.height as usize, self.asm_scroll)
+    }
     fn render_taps(&self, chunk: Rect, buffer: &mut Buffer) {
-        let tabs = Tabs::new(vec!["Sep Reg", "Signal"])
-            .highlight_style(Style::default().fg(Color::Yellow))
+        let tabs = Tabs::new(TABS.to_vec())
+            .highlight_style(self.theme.tab)
             .select(self.tab);
         tabs.render(chunk, buffer);
     }
     fn render_footer(&self, chunk: Rect, buffer: &mut Buffer) {
+        if let Some(input) = &self.command {
+            let text = vec![
+                Span::styled(
+                    format!(":{}_", input),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!("  {}", self.message.as_deref().unwrap_or_default())),
+            ];
+            Widget::render(Line::from(text).style(self.theme.footer), chunk, buffer);
+            return;
+        }
+        if let Some(input) = &self.goto {
+            let text = vec![
+                Span::raw(" Goto Cycle: "),
+                Span::styled(
+                    format!("{}_", input),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" Confirm :"),
+                Span::styled("<Enter>", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" Cancel :"),
+                Span::styled("<Esc>", Style::default().add_modifier(Modifier::BOLD)),
+            ];
+            Widget::render(
+                Line::from(text)
+                    .style(self.theme.footer)
+                    .alignment(Alignment::Center),
+                chunk,
+                buffer,
+            );
+            return;
+        }
+        if let Some(message) = &self.message {
+            Widget::render(
+                Line::from(format!(" {} ", message))
+                    .style(self.theme.footer)
+                    .alignment(Alignment::Center),
+                chunk,
+                buffer,
+            );
+            return;
+        }
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let key = |action| Span::styled(format!("<{}>", self.keys.name(action)), bold);
         let text = vec![
             Span::raw(" Quit :"),
-            Span::styled("<Q>", Style::default().add_modifier(Modifier::BOLD)),
+            key(Action::Quit),
             Span::raw(" Previous Cycle :"),
-            Span::styled("<LeftArrow>", Style::default().add_modifier(Modifier::BOLD)),
+            key(Action::Back),
             Span::raw(" Next Cycle :"),
+            key(Action::Step),
+            Span::raw(match self.playing {
+                true => " Pause :",
+                false => " Play :",
+            }),
+            key(Action::Play),
+            Span::raw(" Speed :"),
             Span::styled(
-                "<RightArrow>",
-                Style::default().add_modifier(Modifier::BOLD),
+                format!(
+                    "<{}/{}>",
+                    self.keys.name(Action::Slower),
+                    self.keys.name(Action::Faster)
+                ),
+                bold,
             ),
             Span::raw(" Switch Tabs :"),
-            Span::styled("<Tab>", Style::default().add_modifier(Modifier::BOLD)),
+            key(Action::NextTab),
+            Span::raw(" Goto Cycle :"),
+            key(Action::Goto),
+            Span::raw(" Command :"),
+            key(Action::Command),
+            Span::raw(" Save :"),
+            key(Action::Save),
+            Span::raw(" Load :"),
+            key(Action::Load),
+            Span::raw(" Snapshot :"),
+            key(Action::Snapshot),
+            Span::raw(" Export Pipeline :"),
+            Span::styled(
+                format!(
+                    "<{}/{}>",
+                    self.keys.name(Action::Export),
+                    self.keys.name(Action::ExportCsv)
+                ),
+                bold,
+            ),
         ];
         Widget::render(
             Line::from(text)
-                .style(Style::default().fg(Color::Gray))
+                .style(self.theme.footer)
                 .alignment(Alignment::Center),
             chunk,
             buffer,
         );
     }
-    fn render_frame(&self, frame: &mut Frame) {
+    // where each pane goes on a screen of `area`
+    fn panes(&self, area: Rect) -> Vec<(Pane, Rect)> {
         let chunck = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(1),
-                Constraint::Length(24),
+                match self.layout.top {
+                    Some(percent) => Constraint::Percentage(percent),
+                    None => Constraint::Length(24),
+                },
                 Constraint::Fill(1),
                 Constraint::Length(1),
             ])
-            .split(frame.size());
-        self.render_taps(chunck[0], frame.buffer_mut());
+            .split(area);
+        let split = |area, constraints: &[Constraint]| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(constraints.to_vec())
+                .split(area)
+        };
+        let halves = [Constraint::Percentage(50), Constraint::Percentage(50)];
+        let top = chunck[1];
+        let mut panes = vec![(Pane::Tabs, chunck[0])];
         match self.tab {
-            0 => self.render_seps(chunck[1], frame.buffer_mut()),
-            1 => self.render_stage(chunck[1], frame.buffer_mut()),
-            _ => {}
+            0 => {
+                let quarters = split(top, &[Constraint::Percentage(25); 4]);
+                panes.extend((0..4).map(|i| (Pane::Sep(i), quarters[i])));
+            }
+            // the datapath when it fits, otherwise the EX and hazard signals as tables
+            1 if top.width >= datapath::WIDTH && top.height >= datapath::HEIGHT => {
+                let area = Rect::new(top.x, top.y, datapath::WIDTH, datapath::HEIGHT);
+                panes.push((Pane::Datapath, area));
+            }
+            1 => {
+                let halves = split(top, &halves);
+                panes.extend([(Pane::Ex, halves[0]), (Pane::Hazard, halves[1])]);
+            }
+            2 => {
+                let halves = split(top, &halves);
+                panes.extend([(Pane::Stats(0), halves[0]), (Pane::Stats(1), halves[1])]);
+            }
+            3 => panes.push((Pane::Pipeline, top)),
+            _ => panes.push((Pane::Memory, top)),
+        }
+        // the decoded instructions beside the ASM pane when there is room for both
+        let bottom = match (self.layout.asm, chunck[2].width >= 2 * inspect::WIDTH) {
+            (Some(100..), _) | (None, false) => [chunck[2]].into(),
+            (Some(percent), _) => split(
+                chunck[2],
+                &[Constraint::Percentage(percent), Constraint::Fill(1)],
+            ),
+            (None, true) => split(
+                chunck[2],
+                &[Constraint::Fill(1), Constraint::Length(inspect::WIDTH)],
+            ),
+        };
+        panes.push((Pane::Asm, bottom[0]));
+        if let Some(&area) = bottom.get(1) {
+            panes.push((Pane::Decode, area));
+        }
+        panes.push((Pane::Footer, chunck[3]));
+        panes
+    }
+    fn render_frame(&self, frame: &mut Frame) {
+        for (pane, area) in self.panes(frame.size()) {
+            let buffer = frame.buffer_mut();
+            match pane {
+                Pane::Tabs => self.render_taps(area, buffer),
+                Pane::Sep(index) => self.render_sep(index, area, buffer),
+                Pane::Datapath => datapath::Datapath {
+                    rv: &self.simulator,
+                    theme: &self.theme,
+                }
+                .render(area, buffer),
+                Pane::Ex | Pane::Hazard => self.render_signals(pane, area, buffer),
+                Pane::Stats(index) => self.render_stats(index, area, buffer),
+                Pane::Pipeline => self.render_pipeline(area, buffer),
+                Pane::Memory => self.render_memory(area, buffer),
+                Pane::Asm => self.render_asm(area, buffer),
+                Pane::Decode => {
+                    let rows = self.rows(pane, inspect::rows(&self.simulator), area);
+                    Widget::render(inspect::table(rows), area, buffer);
+                }
+                Pane::Footer => self.render_footer(area, buffer),
+            }
         }
-        self.render_asm(chunck[2], frame.buffer_mut());
-        self.render_footer(chunck[3], frame.buffer_mut());
     }
 
     /// updates the application's state based on user input
@@ -246,18 +693,235 @@ impl App {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                 self.handle_key_event(key_event)
             }
+            Event::Mouse(mouse_event) => self.handle_mouse_event(mouse_event),
             _ => {}
         };
         Ok(())
     }
 
+    // the wheel scrolls the pane under the mouse, a click selects a row: a tab, a
+    // breakpoint toggled in the ASM pane or a pipeline register field inspected
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        let (x, y) = (mouse_event.column, mouse_event.row);
+        let Some((pane, area)) = self
+            .panes(self.area)
+            .into_iter()
+            .find(|(_, area)| area.intersects(Rect::new(x, y, 1, 1)))
+        else {
+            return;
+        };
+        let down = match mouse_event.kind {
+            MouseEventKind::ScrollDown => true,
+            MouseEventKind::ScrollUp => false,
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.click(pane, area, x, y);
+                return;
+            }
+            MouseEventKind::ScrollLeft | MouseEventKind::ScrollRight if pane == Pane::Pipeline => {
+                self.scroll_cycles(mouse_event.kind == MouseEventKind::ScrollLeft);
+                return;
+            }
+            _ => return,
+        };
+        match pane {
+            Pane::Tabs | Pane::Datapath | Pane::Footer => {}
+            // scrolled up from the newest rows
+            Pane::Pipeline if down => self.pipeline_scroll = self.pipeline_scroll.saturating_sub(1),
+            Pane::Pipeline => self.pipeline_scroll += 1,
+            Pane::Memory if down => self.mem_view.0 = self.mem_view.0.saturating_add(16),
+            Pane::Memory => self.mem_view.0 = self.mem_view.0.saturating_sub(16),
+            Pane::Asm => {
+                // no further once the listing stops moving at its first or last instruction
+                let first = |app: &Self| app.asm_rows(area).first().map(|inst| inst.pc);
+                let before = first(self);
+                let step = if down { 1 } else { -1 };
+                self.asm_scroll += step;
+                if first(self) == before {
+                    self.asm_scroll -= step;
+                }
+            }
+            _ => {
+                let len = self.len(pane);
+                let offset = self.offset(pane, len, area);
+                let offset = match down {
+                    true => offset + 1,
+                    false => offset.saturating_sub(1),
+                };
+                self.scroll.insert(pane, offset);
+                // no further than the last row in view
+                self.scroll.insert(pane, self.offset(pane, len, area));
+            }
+        }
+    }
+    // move the pipeline diagram a cycle back, or forward towards this one
+    fn scroll_cycles(&mut self, back: bool) {
+        self.pipeline_offset = match back {
+            true => (self.pipeline_offset + 1).min(self.simulator.cycle),
+            false => self.pipeline_offset.saturating_sub(1),
+        };
+    }
+    fn click(&mut self, pane: Pane, area: Rect, x: u16, y: u16) {
+        self.message = None;
+        if pane == Pane::Tabs {
+            // each title is padded by a space on both sides, with a divider between
+            let mut left = area.x;
+            for (i, title) in TABS.iter().enumerate() {
+                let right = left + title.len() as u16 + 2;
+                if x < right {
+                    self.tab = i;
+                    return;
+                }
+                left = right + 1;
+            }
+            return;
+        }
+        // below the top border and the header
+        let Some(row) = y.checked_sub(area.y + 2).map(|row| row as usize) else {
+            return;
+        };
+        if y + 1 >= area.y + area.height {
+            return;
+        }
+        match pane {
+            Pane::Asm => {
+                let Some(pc) = self.asm_rows(area).get(row).map(|inst| inst.pc) else {
+                    return;
+                };
+                self.selected = Some((pane, pc as usize));
+                self.message = Some(match self.breakpoints.insert(pc) {
+                    true => format!("Breakpoint at {:#x}", pc),
+                    false => {
+                        self.breakpoints.remove(&pc);
+                        format!("Deleted breakpoint at {:#x}", pc)
+                    }
+                });
+            }
+            Pane::Tabs | Pane::Datapath | Pane::Pipeline | Pane::Memory | Pane::Footer => {}
+            _ => {
+                let row = row + self.offset(pane, self.len(pane), area);
+                self.selected = Some((pane, row));
+                if let Pane::Sep(index) = pane {
+                    let (name, control) = self.sep(index);
+                    if let Some((field, in_, out)) = control.inout().get(row).copied() {
+                        self.message = Some(format!(
+                            "{}.{}: in {:#x} ({}), out {:#x} ({}, {:#b})",
+                            name, field, in_, in_, out, out, out
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
     fn handle_key_event(&mut self, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('q') => self.exit(),
-            KeyCode::Tab => self.tab = (self.tab + 1) % 2,
-            KeyCode::Left => self.prec_cycle(),
-            KeyCode::Right => self.next_cycle(),
-            _ => {}
+        self.message = None;
+        if let Some(input) = &mut self.goto {
+            match key_event.code {
+                KeyCode::Char(c) if c.is_ascii_digit() => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    if let Ok(cycle) = input.parse() {
+                        self.goto_cycle(cycle);
+                    }
+                    self.goto = None;
+                }
+                KeyCode::Esc => self.goto = None,
+                _ => {}
+            }
+            return;
+        }
+        if let Some(input) = &mut self.command {
+            match key_event.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Tab => {
+                    let fields: Vec<String> = self
+                        .simulator
+                        .components()
+                        .into_iter()
+                        .flat_map(|(component, control)| {
+                            control
+                                .inout()
+                                .into_iter()
+                                .map(move |(name, _, _)| format!("{}.{}", component, name))
+                        })
+                        .collect();
+                    let symbols = &self.simulator.pgbak.symbols;
+                    let (line, candidates) = command::complete(input, symbols, &fields);
+                    *input = line;
+                    if !candidates.is_empty() {
+                        self.message = Some(candidates.join(" "));
+                    }
+                }
+                KeyCode::Up => {
+                    self.command_index = self.command_index.saturating_sub(1);
+                    if let Some(line) = self.commands.get(self.command_index) {
+                        *input = line.clone();
+                    }
+                }
+                KeyCode::Down => {
+                    self.command_index = (self.command_index + 1).min(self.commands.len());
+                    *input = self
+                        .commands
+                        .get(self.command_index)
+                        .cloned()
+                        .unwrap_or_default();
+                }
+                KeyCode::Enter => {
+                    let line = self.command.take().unwrap_or_default();
+                    if !line.trim().is_empty() && self.commands.last() != Some(&line) {
+                        self.commands.push(line.clone());
+                    }
+                    self.command_index = self.commands.len();
+                    self.execute(&line);
+                }
+                KeyCode::Esc => self.command = None,
+                _ => {}
+            }
+            return;
+        }
+        let Some(action) = self.keys.action(key_event.code) else {
+            return;
+        };
+        match action {
+            Action::Command => self.command = Some(String::new()),
+            Action::Quit => self.exit(),
+            Action::Goto => self.goto = Some(String::new()),
+            Action::Save => self.save(),
+            Action::Load => self.load(),
+            Action::Export => self.export_pipeline(PIPELINE_TEXT),
+            Action::ExportCsv => self.export_pipeline(PIPELINE_CSV),
+            Action::Snapshot => self.snapshot(SNAPSHOT, None),
+            // only the pipeline diagram scrolls from the keyboard, in its own tab
+            Action::ScrollUp if self.tab == 3 => self.pipeline_scroll += 1,
+            Action::ScrollDown if self.tab == 3 => {
+                self.pipeline_scroll = self.pipeline_scroll.saturating_sub(1)
+            }
+            Action::ScrollLeft if self.tab == 3 => self.scroll_cycles(true),
+            Action::ScrollRight if self.tab == 3 => self.scroll_cycles(false),
+            Action::ScrollUp | Action::ScrollDown | Action::ScrollLeft | Action::ScrollRight => {}
+            Action::NextTab => self.tab = (self.tab + 1) % TABS.len(),
+            Action::Play => {
+                self.playing = !self.playing;
+                self.tick = Instant::now();
+            }
+            Action::Faster | Action::Slower => {
+                self.speed = match action {
+                    Action::Faster => (self.speed * 2).min(MAX_SPEED),
+                    _ => (self.speed / 2).max(1),
+                };
+                self.message = Some(format!("{} cycles/s", self.speed));
+            }
+            Action::Back => self.prec_cycle(),
+            Action::Step => {
+                if let Some(reason) = self.next_cycle() {
+                    self.message = Some(reason);
+                }
+            }
         }
     }
 
@@ -265,21 +929,214 @@ impl App {
         self.exit = true;
     }
 
-    fn next_cycle(&mut self) {
-        self.simulator.rasing_edge();
-        self.simulator.falling_edge();
-        self.cycle += 1;
+    // step one cycle, with the reason a run should stop there
+    fn next_cycle(&mut self) -> Option<String> {
+        let commit = Commit::new(&self.simulator);
+        self.history.step(&mut self.simulator);
+        let mut stop = None;
+        if let Some(script) = &mut self.script {
+            // the footer shows the last thing the script printed
+            let result = script.cycle(&self.simulator, commit.as_ref());
+            if let Some(line) = script.take_output().pop() {
+                self.message = Some(line);
+            }
+            stop = result.err().or(script
+                .take_stop()
+                .map(|reason| format!("Stopped by script: {}", reason)));
+            for edit in script.take_edits() {
+                if let Err(e) = self.history.edit(&mut self.simulator, edit) {
+                    stop = stop.or(Some(e));
+                }
+            }
+        }
+        if let Some(commit) = &commit {
+            let addr = commit.load.or(commit.store.map(|(addr, _, _)| addr));
+            if let Some(addr) = addr.filter(|addr| self.watchpoints.contains(&(addr & !3))) {
+                stop = stop.or(Some(format!(
+                    "Watchpoint {:#x} hit by {}",
+                    addr,
+                    self.simulator.disasm(commit.pc)
+                )));
+            }
+        }
+        match self.simulator.asm.stages()[4] {
+            Some(pc) if self.breakpoints.contains(&pc) => {
+                stop.or(Some(format!("Breakpoint at {:#x}", pc)))
+            }
+            _ => stop,
+        }
+    }
+    // the cycles due at `speed` since the last tick, paused by anything that stops a run.
+    // the clock holds while the `:` or goto prompt is open
+    fn play(&mut self, now: Instant) {
+        if self.command.is_some() || self.goto.is_some() {
+            self.tick = now;
+            return;
+        }
+        let due = ((now - self.tick).as_secs_f64() * self.speed as f64) as usize;
+        if due == 0 {
+            return;
+        }
+        // what is left of a cycle counts towards the next
+        self.tick += Duration::from_secs_f64(due as f64 / self.speed as f64);
+        if self.run_cycles(due, |_| false) {
+            self.playing = false;
+        }
+    }
+    // step up to `count` cycles, stopping early at a halt, a breakpoint, a watchpoint,
+    // a stop from the script or once `until` holds, true for a stop
+    fn run_cycles(&mut self, count: usize, until: impl Fn(&Rv32i) -> bool) -> bool {
+        for _ in 0..count {
+            if self.simulator.halted() {
+                self.message = Some(format!(
+                    "Program halted, a0 = {}",
+                    self.simulator.xregs.read(10)
+                ));
+                return true;
+            }
+            if let Some(reason) = self.next_cycle() {
+                self.message = Some(reason);
+                return true;
+            }
+            if until(&self.simulator) {
+                return false;
+            }
+        }
+        if count == RUN_LIMIT {
+            self.message = Some(format!("No stop after {} cycles", RUN_LIMIT));
+        }
+        false
+    }
+    fn execute(&mut self, line: &str) {
+        let command = match command::parse(line, &self.simulator.pgbak.symbols) {
+            Ok(command) => command,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            }
+        };
+        match command {
+            Command::Step(count) => {
+                self.run_cycles(count, |_| false);
+            }
+            Command::Continue => {
+                self.run_cycles(RUN_LIMIT, |_| false);
+            }
+            Command::Break(addr) => {
+                self.breakpoints.insert(addr);
+                self.message = Some(format!(
+                    "Breakpoint at {:#x}: {}",
+                    addr,
+                    self.simulator.disasm(addr)
+                ));
+            }
+            Command::Delete(None) => self.breakpoints.clear(),
+            Command::Delete(Some(addr)) => {
+                if !self.breakpoints.remove(&addr) {
+                    self.message = Some(format!("No breakpoint at {:#x}", addr));
+                }
+            }
+            Command::Watch(addr) => {
+                self.watchpoints.insert(addr & !3);
+                self.message = Some(format!("Watching {:#x}", addr & !3));
+            }
+            Command::Unwatch(None) => self.watchpoints.clear(),
+            Command::Unwatch(Some(addr)) => {
+                if !self.watchpoints.remove(&(addr & !3)) {
+                    self.message = Some(format!("No watchpoint at {:#x}", addr & !3));
+                }
+            }
+            Command::Goto(addr) => {
+                self.run_cycles(RUN_LIMIT, |rv| rv.asm.stages()[4] == Some(addr));
+            }
+            Command::Cycle(cycle) => self.goto_cycle(cycle),
+            Command::Set(edit) => {
+                self.message = Some(match self.history.edit(&mut self.simulator, edit.clone()) {
+                    Ok(()) => edit.to_string(),
+                    Err(e) => e,
+                });
+            }
+            Command::Mem(addr, len) => {
+                self.mem_view = (addr & !3, len);
+                self.tab = 4;
+            }
+            Command::Save(path) => {
+                self.save_path = path.unwrap_or(self.save_path.clone());
+                self.save();
+            }
+            Command::Load(path) => {
+                self.save_path = path.unwrap_or(self.save_path.clone());
+                self.load();
+            }
+            Command::Snapshot(path, cycles) => {
+                self.snapshot(path.as_deref().unwrap_or(SNAPSHOT), cycles)
+            }
+            Command::Help => self.message = Some(command::USAGE.to_string()),
+            Command::Quit => self.exit(),
+        }
     }
     fn prec_cycle(&mut self) {
-        if self.cycle == 0 {
+        if self.simulator.cycle == 0 {
             return;
         }
-        self.simulator = self.simulator.reset();
-        self.cycle -= 1;
-        for _ in 0..self.cycle {
-            self.simulator.rasing_edge();
-            self.simulator.falling_edge();
+        self.goto_cycle(self.simulator.cycle - 1);
+    }
+    fn goto_cycle(&mut self, cycle: usize) {
+        if let Err(e) = self.history.seek(&mut self.simulator, cycle) {
+            self.message = Some(e);
+        }
+    }
+    fn save(&mut self) {
+        self.message = Some(match simulator::save(&self.simulator, &self.save_path) {
+            Ok(()) => format!("Saved to {}", self.save_path),
+            Err(e) => format!("Failed to save {}: {}", self.save_path, e),
+        });
+    }
+    fn export_pipeline(&mut self, path: &str) {
+        let diagram = Diagram::new(&self.simulator, 0..self.simulator.cycle + 1);
+        self.message = Some(match diagram.write(path) {
+            Ok(()) => format!("Exported pipeline diagram to {}", path),
+            Err(e) => format!("Failed to export {}: {}", path, e),
+        });
+    }
+    // the figure of this cycle at `path`, or of every cycle in `cycles` numbered like
+    // snapshot-12.html, coming back to this cycle after
+    fn snapshot(&mut self, path: &str, cycles: Option<Range<usize>>) {
+        let Some(cycles) = cycles else {
+            self.message = Some(match Figure::new(&self.simulator).write(path) {
+                Ok(()) => format!("Exported cycle {} to {}", self.simulator.cycle, path),
+                Err(e) => format!("Failed to export {}: {}", path, e),
+            });
+            return;
+        };
+        let cycle = self.simulator.cycle;
+        let (mut written, mut failed) = (0, None);
+        for at in cycles {
+            if let Err(e) = self.history.seek(&mut self.simulator, at) {
+                failed = Some(format!("Failed to export {}: {}", batch_path(path, at), e));
+                break;
+            }
+            let file = batch_path(path, at);
+            if let Err(e) = Figure::new(&self.simulator).write(&file) {
+                failed = Some(format!("Failed to export {}: {}", file, e));
+                break;
+            }
+            written += 1;
         }
+        self.history
+            .seek(&mut self.simulator, cycle)
+            .expect("history: seek back to the current cycle");
+        self.message = failed.or_else(|| Some(format!("Exported {} cycles to {}", written, path)));
+    }
+    fn load(&mut self) {
+        self.message = Some(match simulator::load(&self.save_path) {
+            Ok(sm) => {
+                self.history = History::new(&sm, CHECKPOINT_INTERVAL);
+                self.simulator = sm;
+                format!("Loaded {}", self.save_path)
+            }
+            Err(e) => format!("Failed to load {}: {}", self.save_path, e),
+        });
     }
 }
 
@@ -306,3 +1163,249 @@ impl Widget for &App {
             .for_each(|block| block.render(chunk[0], buf));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn app() -> App {
+        // 0:  addi x1, x0, 1
+        // 4:  add x5, x5, x1
+        // 8:  sw x5, 0(x0)
+        // c:  lw x6, 0(x0)
+        // 10: add x7, x6, x5
+        // 14: jal x0, -16
+        let insts = [
+            0x00100093u32,
+            0x001282b3,
+            0x00502023,
+            0x00002303,
+            0x005303b3,
+            0xff1ff06f,
+        ];
+        let pg = Program {
+            symbols: [("store".to_string(), 8)].into(),
+            ..Program::from_words(&insts)
+        };
+        App::new(Rv32iBuilder::new(pg).slf_build())
+    }
+    #[test]
+    fn test_commands() {
+        let mut app = app();
+        app.execute("break store");
+        app.execute("continue");
+        assert_eq!(app.message.as_deref(), Some("Breakpoint at 0x8"));
+        assert_eq!(app.simulator.asm.stages()[4], Some(8));
+        app.execute("delete");
+        app.execute("watch 0x2");
+        app.execute("c");
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Watchpoint 0x0 hit by 00502023   sw")
+        );
+        app.execute("c");
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Watchpoint 0x0 hit by 00002303   lw")
+        );
+        assert_eq!(app.simulator.xregs.read(6), 1);
+        app.execute("unwatch");
+        app.execute("goto 0x14");
+        assert_eq!(app.simulator.asm.stages()[4], Some(0x14));
+        app.execute("set t0 -1");
+        assert_eq!(app.simulator.xregs.read(5), u32::MAX);
+        let cycle = app.simulator.cycle;
+        app.execute("step 3");
+        assert_eq!(app.simulator.cycle, cycle + 3);
+        app.execute("cycle 2");
+        assert_eq!(app.simulator.cycle, 2);
+        app.execute("set *0x10 5");
+        app.execute("set id_ex.rd 0x1f");
+        assert_eq!(app.message.as_deref(), Some("id_ex.rd = 0x1f"));
+        app.execute("cycle 1");
+        assert_eq!(app.simulator.dmem.read(0x10), 0);
+        app.execute("cycle 2");
+        assert_eq!(app.simulator.dmem.read(0x10), 5);
+        app.execute("set id_ex.nothing 1");
+        assert_eq!(app.message.as_deref(), Some("no latch nothing in id_ex"));
+        app.execute("mem 0x10 32");
+        assert_eq!((app.tab, app.mem_view), (4, (0x10, 32)));
+        app.execute("jump");
+        assert_eq!(app.message.as_deref(), Some("no command jump, try help"));
+    }
+    #[test]
+    fn test_prompt() {
+        let mut app = app();
+        let key = |app: &mut App, code| app.handle_key_event(KeyEvent::from(code));
+        key(&mut app, KeyCode::Char(':'));
+        key(&mut app, KeyCode::Char('s'));
+        key(&mut app, KeyCode::Tab);
+        assert_eq!(app.command.as_deref(), Some("s"));
+        assert_eq!(app.message.as_deref(), Some("save set snapshot step"));
+        key(&mut app, KeyCode::Char('t'));
+        key(&mut app, KeyCode::Tab);
+        assert_eq!(app.command.as_deref(), Some("step "));
+        key(&mut app, KeyCode::Char('2'));
+        key(&mut app, KeyCode::Enter);
+        assert_eq!(app.command, None);
+        assert_eq!(app.simulator.cycle, 2);
+        key(&mut app, KeyCode::Char(':'));
+        key(&mut app, KeyCode::Up);
+        assert_eq!(app.command.as_deref(), Some("step 2"));
+        key(&mut app, KeyCode::Down);
+        assert_eq!(app.command.as_deref(), Some(""));
+        key(&mut app, KeyCode::Esc);
+        assert_eq!(app.command, None);
+    }
+    #[test]
+    fn test_play() {
+        let mut app = app();
+        app.handle_key_event(KeyEvent::from(KeyCode::Char(' ')));
+        assert!(app.playing);
+        let start = app.tick;
+        app.play(start + Duration::from_millis(100));
+        assert_eq!(app.simulator.cycle, 0);
+        app.play(start + Duration::from_millis(600));
+        assert_eq!(app.simulator.cycle, 2);
+        // the 100ms over makes the next cycle due 150ms later
+        app.play(start + Duration::from_millis(750));
+        assert_eq!(app.simulator.cycle, 3);
+        // nothing runs while a prompt is open, nor is made up after
+        app.handle_key_event(KeyEvent::from(KeyCode::Char(':')));
+        app.play(start + Duration::from_secs(10));
+        assert_eq!(app.simulator.cycle, 3);
+        app.handle_key_event(KeyEvent::from(KeyCode::Esc));
+        app.play(start + Duration::from_millis(10_100));
+        assert_eq!(app.simulator.cycle, 3);
+        assert!(app.playing);
+        app.handle_key_event(KeyEvent::from(KeyCode::Char('+')));
+        assert_eq!(app.message.as_deref(), Some("8 cycles/s"));
+        // a second at 8 cycles/s runs into the breakpoint and pauses there
+        app.execute("break store");
+        app.play(app.tick + Duration::from_secs(1));
+        assert!(!app.playing);
+        assert_eq!(app.message.as_deref(), Some("Breakpoint at 0x8"));
+        assert_eq!(app.simulator.asm.stages()[4], Some(8));
+        for _ in 0..20 {
+            app.handle_key_event(KeyEvent::from(KeyCode::Char('-')));
+        }
+        assert_eq!(app.speed, 1);
+    }
+    #[test]
+    fn test_pipeline_scroll() {
+        let mut app = app();
+        let key = |app: &mut App, code| app.handle_key_event(KeyEvent::from(code));
+        app.execute("step 10");
+        // Up and Down are left alone away from the Pipeline tab
+        key(&mut app, KeyCode::Up);
+        key(&mut app, KeyCode::Char('<'));
+        assert_eq!((app.pipeline_scroll, app.pipeline_offset), (0, 0));
+        app.tab = 3;
+        key(&mut app, KeyCode::Up);
+        for _ in 0..4 {
+            key(&mut app, KeyCode::Char('<'));
+        }
+        key(&mut app, KeyCode::Char('>'));
+        assert_eq!((app.pipeline_scroll, app.pipeline_offset), (1, 3));
+        // the diagram ends at cycle 7 instead of 10
+        let area = Rect::new(0, 0, 100, 20);
+        let mut buffer = Buffer::empty(area);
+        app.render_pipeline(area, &mut buffer);
+        let header: String = (0..area.width).map(|x| buffer.get(x, 1).symbol()).collect();
+        assert!(
+            header.trim_end_matches(['│', ' ']).ends_with(" 7"),
+            "{}",
+            header
+        );
+        for _ in 0..20 {
+            key(&mut app, KeyCode::Char('<'));
+        }
+        assert_eq!(app.pipeline_offset, 10);
+    }
+    #[test]
+    fn test_snapshot() {
+        let dir = std::env::temp_dir().join("rv-simulator-test-tui-snapshot");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cycle.html").to_str().unwrap().to_string();
+        let mut app = app();
+        app.execute("step 3");
+        app.execute(&format!("snapshot {} 1..6", path));
+        assert_eq!(app.message, Some(format!("Exported 5 cycles to {}", path)));
+        assert_eq!(app.simulator.cycle, 3);
+        let html = std::fs::read_to_string(dir.join("cycle-5.html")).unwrap();
+        assert!(html.contains("<h1>Cycle 5</h1>"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 5);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_mouse() {
+        // the program of app() with its listing, for the ASM pane
+        let asm = [
+            "addi ra,zero,1",
+            "add t0,t0,ra",
+            "sw t0,0(zero)",
+            "lw t1,0(zero)",
+            "add t2,t1,t0",
+            "j 0",
+        ];
+        let mut pg = app().simulator.pgbak;
+        pg.asm = std::iter::once("\n00000000 <_start>:".to_string())
+            .chain(asm.iter().enumerate().map(|(i, asm)| {
+                let inst = u32::from_ne_bytes(pg.insts[i * 4..i * 4 + 4].try_into().unwrap());
+                format!(
+                    "{:4x}:\t{:08x}          \t{}",
+                    i * 4,
+                    inst,
+                    asm.replace(' ', "\t")
+                )
+            }))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut app = App::new(Rv32iBuilder::new(pg).slf_build());
+        app.area = Rect::new(0, 0, 160, 50);
+        let mouse = |app: &mut App, kind, column, row| {
+            app.handle_mouse_event(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: event::KeyModifiers::NONE,
+            })
+        };
+        let click = MouseEventKind::Down(MouseButton::Left);
+        // the ASM pane starts on row 25, its first instruction under the border and header
+        mouse(&mut app, click, 10, 28);
+        assert_eq!(app.message.as_deref(), Some("Breakpoint at 0x4"));
+        assert!(app.breakpoints.contains(&4));
+        assert_eq!(app.selected, Some((Pane::Asm, 4)));
+        mouse(&mut app, click, 10, 28);
+        assert_eq!(app.message.as_deref(), Some("Deleted breakpoint at 0x4"));
+        assert!(app.breakpoints.is_empty());
+        // the whole listing is in view, so it does not move
+        mouse(&mut app, MouseEventKind::ScrollDown, 10, 30);
+        mouse(&mut app, MouseEventKind::ScrollUp, 10, 30);
+        assert_eq!(app.asm_scroll, 0);
+        // the third field of ID/EX, a quarter of the way across
+        mouse(&mut app, click, 45, 5);
+        assert_eq!(app.selected, Some((Pane::Sep(1), 2)));
+        assert!(app.message.as_ref().unwrap().starts_with("id_ex."));
+        // in a pane 10 rows high, ID/EX scrolls no further than its last field
+        app.layout.top = Some(20);
+        for _ in 0..100 {
+            mouse(&mut app, MouseEventKind::ScrollDown, 45, 5);
+        }
+        assert_eq!(app.scroll[&Pane::Sep(1)], app.len(Pane::Sep(1)) - 7);
+        mouse(&mut app, MouseEventKind::ScrollUp, 45, 5);
+        assert_eq!(app.scroll[&Pane::Sep(1)], app.len(Pane::Sep(1)) - 8);
+        mouse(&mut app, click, 10, 0);
+        assert_eq!(app.tab, 1);
+        mouse(&mut app, click, 22, 0);
+        assert_eq!(app.tab, 2);
+        let mut buffer = Buffer::empty(app.area);
+        app.render_taps(Rect::new(0, 0, 160, 1), &mut buffer);
+        // the "a" of " Stats "
+        assert_eq!(buffer.get(22, 0).symbol(), "a");
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::simulator::{xreg_index, Edit, XREG_NAMES};
+
+// a line typed at the `:` prompt
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    // advance this many cycles, stopping early like `continue`
+    Step(usize),
+    // run until a breakpoint, a watchpoint, the script or the program stops it
+    Continue,
+    Break(u32),
+    // one breakpoint, or all of them
+    Delete(Option<u32>),
+    Watch(u32),
+    Unwatch(Option<u32>),
+    // run until the instruction at the address is in write back
+    Goto(u32),
+    // go to a cycle, backwards too
+    Cycle(usize),
+    Set(Edit),
+    // show memory from the address, the length in bytes
+    Mem(u32, u32),
+    Save(Option<String>),
+    Load(Option<String>),
+    // write the figure of this cycle, or of each cycle in the range
+    Snapshot(Option<String>, Option<Range<usize>>),
+    Help,
+    Quit,
+}
+
+pub const COMMANDS: [&str; 15] = [
+    "break", "continue", "cycle", "delete", "goto", "help", "load", "mem", "quit", "save", "set",
+    "snapshot", "step", "unwatch", "watch",
+];
+
+pub const USAGE: &str = "step [N] | continue | break ADDR | delete [ADDR] | watch ADDR | \
+    unwatch [ADDR] | goto ADDR | cycle N | set REG|*ADDR|LATCH VALUE | mem ADDR [LEN] | save [PATH] | \
+    load [PATH] | snapshot [PATH] [START..END] | quit";
+
+// decimal, or hexadecimal with 0x, negative values wrap around
+fn number(word: &str) -> Result<i64, String> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }
+    .map_err(|_| format!("not a number: {}", word))?;
+    Ok(if negative { -value } else { value })
+}
+// a number or a symbol
+fn address(word: &str, symbols: &BTreeMap<String, usize>) -> Result<u32, String> {
+    match symbols.get(word) {
+        Some(&addr) => Ok(addr as u32),
+        None => match number(word) {
+            Ok(n) if (i32::MIN as i64..=u32::MAX as i64).contains(&n) => Ok(n as u32),
+            Ok(_) => Err(format!("address out of range: {}", word)),
+            Err(_) => Err(format!("no symbol {}", word)),
+        },
+    }
+}
+
+pub fn parse(line: &str, symbols: &BTreeMap<String, usize>) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((&name, args)) = words.split_first() else {
+        return Err(USAGE.to_string());
+    };
+    let arg = |i: usize| {
+        args.get(i)
+            .copied()
+            .ok_or_else(|| format!("usage: {}", usage(name)))
+    };
+    let command = match name {
+        "step" | "s" => Command::Step(match args.first() {
+            Some(n) => number(n)?.max(0) as usize,
+            None => 1,
+        }),
+        "continue" | "c" => Command::Continue,
+        "break" | "b" => Command::Break(address(arg(0)?, symbols)?),
+        "delete" | "d" => Command::Delete(args.first().map(|a| address(a, symbols)).transpose()?),
+        "watch" | "w" => Command::Watch(address(arg(0)?, symbols)?),
+        "unwatch" => Command::Unwatch(args.first().map(|a| address(a, symbols)).transpose()?),
+        "goto" => Command::Goto(address(arg(0)?, symbols)?),
+        "cycle" => Command::Cycle(number(arg(0)?)?.max(0) as usize),
+        "set" => {
+            let target = arg(0)?;
+            let value = number(arg(1)?)? as u32;
+            // a register, a word of memory as *ADDR or a pipeline register field as id_ex.rd
+            Command::Set(match (target.strip_prefix('*'), target.split_once('.')) {
+                (Some(addr), _) => Edit::Mem(address(addr, symbols)?, value),
+                (None, Some((component, name))) => {
+                    Edit::Latch(component.to_string(), name.to_string(), value)
+                }
+                (None, None) => match xreg_index(target) {
+                    Some(i) => Edit::Reg(i, value),
+                    None => return Err(format!("no register {}", target)),
+                },
+            })
+        }
+        "mem" | "m" => Command::Mem(
+            address(arg(0)?, symbols)?,
+            match args.get(1) {
+                Some(len) => number(len)? as u32,
+                None => 64,
+            },
+        ),
+        "save" => Command::Save(args.first().map(|s| s.to_string())),
+        "load" => Command::Load(args.first().map(|s| s.to_string())),
+        "snapshot" => {
+            let mut path = None;
+            let mut cycles = None;
+            // a path like ../out.html is not a range
+            let cycle = |s| number(s).ok().map(|n| n.max(0) as usize);
+            for arg in args {
+                match arg
+                    .split_once("..")
+                    .and_then(|(start, end)| Some(cycle(start)?..cycle(end)?))
+                {
+                    Some(range) => cycles = Some(range),
+                    None => path = Some(arg.to_string()),
+                }
+            }
+            Command::Snapshot(path, cycles)
+        }
+        "help" | "h" => Command::Help,
+        "quit" | "q" => Command::Quit,
+        _ => return Err(format!("no command {}, try help", name)),
+    };
+    Ok(command)
+}
+fn usage(name: &str) -> &'static str {
+    let name = COMMANDS.iter().find(|c| c.starts_with(name)).unwrap_or(&"");
+    USAGE
+        .split(" | ")
+        .find(|u| u.split(' ').next() == Some(name))
+        .unwrap_or(USAGE)
+}
+
+// complete the last word of the line, commands first and then register names, symbols
+// and pipeline register fields. the line grows by the common prefix of the candidates,
+// which are returned when ambiguous
+pub fn complete(
+    line: &str,
+    symbols: &BTreeMap<String, usize>,
+    fields: &[String],
+) -> (String, Vec<String>) {
+    let start = line.rfind(' ').map_or(0, |i| i + 1);
+    let word = &line[start..];
+    let candidates: Vec<String> = match start {
+        0 => COMMANDS.iter().map(|c| c.to_string()).collect(),
+        _ => XREG_NAMES
+            .iter()
+            .map(|n| n.to_string())
+            .chain((0..32).map(|i| format!("x{}", i)))
+            .chain(symbols.keys().cloned())
+            .chain(fields.iter().cloned())
+            .collect(),
+    };
+    let mut candidates: Vec<String> = candidates
+        .into_iter()
+        .filter(|c| c.starts_with(word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    let Some(first) = candidates.first() else {
+        return (line.to_string(), vec![]);
+    };
+    let common = candidates.iter().fold(first.len(), |len, c| {
+        first
+            .bytes()
+            .zip(c.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    let mut line = format!("{}{}", &line[..start], &first[..common]);
+    if candidates.len() == 1 {
+        line.push(' ');
+        candidates.clear();
+    }
+    (line, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols() -> BTreeMap<String, usize> {
+        BTreeMap::from([
+            ("main".to_string(), 0x100),
+            ("matmul".to_string(), 0x200),
+            ("_start".to_string(), 0),
+        ])
+    }
+    #[test]
+    fn test_parse() {
+        let symbols = symbols();
+        let parse = |line| parse(line, &symbols);
+        assert_eq!(parse("step 100"), Ok(Command::Step(100)));
+        assert_eq!(parse("  step  "), Ok(Command::Step(1)));
+        assert_eq!(parse("break main"), Ok(Command::Break(0x100)));
+        assert_eq!(parse("goto 0x100"), Ok(Command::Goto(0x100)));
+        assert_eq!(parse("watch 0x7ffffff0"), Ok(Command::Watch(0x7ffffff0)));
+        assert_eq!(parse("set x5 42"), Ok(Command::Set(Edit::Reg(5, 42))));
+        assert_eq!(
+            parse("set sp -16"),
+            Ok(Command::Set(Edit::Reg(2, 0xfffffff0)))
+        );
+        assert_eq!(
+            parse("set *main 0x13"),
+            Ok(Command::Set(Edit::Mem(0x100, 0x13)))
+        );
+        assert_eq!(
+            parse("set id_ex.rd 5"),
+            Ok(Command::Set(Edit::Latch("id_ex".into(), "rd".into(), 5)))
+        );
+        assert_eq!(parse("mem 0x1000 64"), Ok(Command::Mem(0x1000, 64)));
+        assert_eq!(parse("delete"), Ok(Command::Delete(None)));
+        assert_eq!(parse("save"), Ok(Command::Save(None)));
+        assert_eq!(
+            parse("save a.json"),
+            Ok(Command::Save(Some("a.json".into())))
+        );
+        assert_eq!(parse("snapshot"), Ok(Command::Snapshot(None, None)));
+        assert_eq!(
+            parse("snapshot ../cycle.svg 10..20"),
+            Ok(Command::Snapshot(Some("../cycle.svg".into()), Some(10..20)))
+        );
+        assert_eq!(parse("break nowhere"), Err("no symbol nowhere".into()));
+        assert_eq!(
+            parse("break 0x100000000"),
+            Err("address out of range: 0x100000000".into())
+        );
+        assert_eq!(parse("mem -4 4"), Ok(Command::Mem(0xfffffffc, 4)));
+        assert_eq!(parse("set y1 1"), Err("no register y1".into()));
+        assert_eq!(
+            parse("set x1"),
+            Err("usage: set REG|*ADDR|LATCH VALUE".into())
+        );
+        assert_eq!(parse("b"), Err("usage: break ADDR".into()));
+        assert!(parse("jump").is_err());
+        assert!(parse("").is_err());
+    }
+    #[test]
+    fn test_complete() {
+        let symbols = symbols();
+        let fields = ["id_ex.rd".to_string(), "id_ex.rs1".to_string()];
+        let complete = |line| complete(line, &symbols, &fields);
+        assert_eq!(complete("br"), ("break ".to_string(), vec![]));
+        assert_eq!(
+            complete("s"),
+            (
+                "s".to_string(),
+                vec![
+                    "save".into(),
+                    "set".into(),
+                    "snapshot".into(),
+                    "step".into()
+                ]
+            )
+        );
+        assert_eq!(complete("se"), ("set ".to_string(), vec![]));
+        assert_eq!(
+            complete("break ma"),
+            ("break ma".to_string(), vec!["main".into(), "matmul".into()])
+        );
+        assert_eq!(complete("break mai"), ("break main ".to_string(), vec![]));
+        assert_eq!(
+            complete("set x3"),
+            (
+                "set x3".to_string(),
+                vec!["x3".into(), "x30".into(), "x31".into()]
+            )
+        );
+        assert_eq!(
+            complete("set s1"),
+            (
+                "set s1".to_string(),
+                vec!["s1".into(), "s10".into(), "s11".into()]
+            )
+        );
+        assert_eq!(
+            complete("set id_ex.r"),
+            (
+                "set id_ex.r".to_string(),
+                vec!["id_ex.rd".into(), "id_ex.rs1".into()]
+            )
+        );
+        assert_eq!(complete("set q"), ("set q".to_string(), vec![]));
+    }
+}
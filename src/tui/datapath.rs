@@ -0,0 +1,369 @@
+use ratatui::prelude::*;
+
+use super::theme::Theme;
+use crate::common::abi::*;
+use crate::simulator::Rv32i;
+
+// the area the diagram needs, smaller areas get the signal tables instead
+pub const WIDTH: u16 = 114;
+pub const HEIGHT: u16 = 20;
+
+// left edge and width of each stage box, the pipeline registers sit in between
+const IF: (u16, u16) = (0, 19);
+const ID: (u16, u16) = (20, 22);
+const EX: (u16, u16) = (43, 32);
+const MEM: (u16, u16) = (76, 18);
+const WB: (u16, u16) = (95, 19);
+const BOX_HEIGHT: u16 = 15;
+
+fn find(signals: &[(&'static str, u32)], name: &str) -> u32 {
+    signals
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| *v)
+        .unwrap_or_else(|| panic!("datapath: no signal {}", name))
+}
+// what a pipeline register field takes in and holds
+fn field(control: &ControlRef, name: &str) -> (u32, u32) {
+    control
+        .inout()
+        .into_iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, in_, out)| (in_, out))
+        .unwrap_or_else(|| panic!("datapath: no field {}", name))
+}
+
+// draws text at positions relative to the area, clipped to it
+struct Canvas<'a> {
+    area: Rect,
+    buf: &'a mut Buffer,
+}
+impl Canvas<'_> {
+    fn text(&mut self, x: u16, y: u16, text: &str, style: Style) {
+        if x >= self.area.width || y >= self.area.height {
+            return;
+        }
+        let width = (self.area.width - x) as usize;
+        self.buf
+            .set_stringn(self.area.x + x, self.area.y + y, text, width, style);
+    }
+    fn frame(&mut self, (x, w): (u16, u16), title: &str, style: Style) {
+        let top = format!("┌{:─<1$}┐", title, w as usize - 2);
+        self.text(x, 1, &top, style);
+        for y in 2..BOX_HEIGHT {
+            self.text(x, y, "│", style);
+            self.text(x + w - 1, y, "│", style);
+        }
+        let bottom = format!("└{}┘", "─".repeat(w as usize - 2));
+        self.text(x, BOX_HEIGHT, &bottom, style);
+    }
+    // a wire running back from `from` to the arrow at `to`, labelled in the middle
+    fn feedback(&mut self, y: u16, to: u16, from: u16, label: &str, style: Style) {
+        let len = (from - to) as usize;
+        let side = len.saturating_sub(label.chars().count() + 4) / 2;
+        let wire = format!(
+            "◀{} {} {}┘",
+            "─".repeat(side),
+            label,
+            "─".repeat(len.saturating_sub(side + label.chars().count() + 4))
+        );
+        self.text(to, y, &wire, style);
+    }
+}
+
+// the five stage datapath with the values on its wires. the paths taken this
+// cycle are highlighted, e.g. which input each forwarding mux selects
+pub struct Datapath<'a> {
+    pub rv: &'a Rv32i,
+    pub theme: &'a Theme,
+}
+impl Widget for Datapath<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rv = self.rv;
+        let mut c = Canvas { area, buf };
+        let (dim, on) = (self.theme.dim, self.theme.active);
+        let (stall, flush) = (self.theme.stall, self.theme.flush);
+        let pick = |active: bool| if active { on } else { dim };
+        let plain = Style::default();
+
+        let stages = rv.asm.stages();
+        let title = |name: &str, stage: usize| match stages[stage] {
+            Some(pc) => format!(" {} {:08x} ", name, pc),
+            None => format!(" {} bubble ", name),
+        };
+        c.frame(IF, &title("IF", 0), plain);
+        c.frame(ID, &title("ID", 1), plain);
+        c.frame(EX, &title("EX", 2), plain);
+        c.frame(MEM, &title("MEM", 3), plain);
+        c.frame(WB, &title("WB", 4), plain);
+        for (x, name) in [(17, "IF/ID"), (40, "ID/EX"), (73, "EX/MEM"), (92, "MEM/WB")] {
+            c.text(x, 0, name, plain);
+            for y in 1..=BOX_HEIGHT {
+                c.text(x + 2, y, "┃", plain);
+            }
+        }
+
+        let ex = rv.ex.inner_signal();
+        let taken = find(&ex, "npc_en") == 1;
+        let target = find(&ex, "target");
+        let fetch = rv.if_stage.inner_signal();
+        let pred = !taken && find(&fetch, "pred") == 1;
+        let alu_res = find(&rv.ex.output(), "res");
+        let hazard = rv.hazard.output();
+        let stalled = find(&hazard, "en") == 0;
+        let load_use = find(&hazard, "raw") == 1;
+        let regs = rv.id_stage.input();
+        let (wb_rd, wb_data, wb_write) = (
+            find(&regs, "rd"),
+            find(&regs, "rd_data"),
+            find(&regs, "write") == 1 && find(&regs, "rd") != 0,
+        );
+
+        // fetch: the next pc comes from pc+4, the predicted target or EX correcting
+        // a misprediction
+        let (x, y) = (IF.0 + 2, 2);
+        let (npc, pc) = (field(&rv.if_id, "npc").0, field(&rv.if_id, "pc").0);
+        c.text(x, y, "next pc", plain);
+        c.text(
+            x,
+            y + 1,
+            &format!("pc+4 {:08x}", npc),
+            pick(!taken && !pred),
+        );
+        c.text(
+            x,
+            y + 2,
+            &format!("br   {:08x}", target),
+            if taken { flush } else { dim },
+        );
+        c.text(
+            x,
+            y + 3,
+            &format!("pred {:08x}", find(&fetch, "pred_target")),
+            pick(pred),
+        );
+        c.text(x, y + 4, &format!("PC   {:08x}", pc), plain);
+        c.text(
+            x,
+            y + 5,
+            &format!("IMEM {:08x}", field(&rv.if_id, "inst").0),
+            plain,
+        );
+        if stalled {
+            c.text(x, y + 7, "stalled", stall);
+        }
+
+        // decode: register reads, the immediate and the register file write port
+        let (x, y) = (ID.0 + 2, 2);
+        c.text(
+            x,
+            y,
+            &format!("inst {:08x}", field(&rv.if_id, "inst").1),
+            plain,
+        );
+        for (i, (reg, data)) in [("rs1", "rs1_data"), ("rs2", "rs2_data")]
+            .into_iter()
+            .enumerate()
+        {
+            let text = format!(
+                "{} x{:<2} {:08x}",
+                reg,
+                field(&rv.id_ex, reg).0,
+                field(&rv.id_ex, data).0
+            );
+            c.text(x, y + 1 + i as u16, &text, plain);
+        }
+        c.text(
+            x,
+            y + 3,
+            &format!("rd  x{}", field(&rv.id_ex, "rd").0),
+            plain,
+        );
+        c.text(
+            x,
+            y + 4,
+            &format!("imm {:08x}", field(&rv.id_ex, "imm").0),
+            plain,
+        );
+        c.text(x, y + 6, "regs write", plain);
+        c.text(
+            x,
+            y + 7,
+            &format!("x{:<2} ◀ {:08x}", wb_rd, wb_data),
+            pick(wb_write),
+        );
+        if load_use {
+            c.text(x, y + 9, "load-use stall", stall);
+        }
+        if taken {
+            c.text(x, y + 10, "flushed", flush);
+        }
+
+        // execute: the forwarding muxes, the operand muxes, the ALU and the branch unit
+        let (x, y) = (EX.0 + 2, 2);
+        let ex_mem_res = field(&rv.ex_mem, "alu_res").1;
+        for (i, (fwd, data, op, alt, sel)) in [
+            ("fwd1", "rs1_data", "op1", "pc", "pc_sel"),
+            ("fwd2", "rs2_data", "op2", "imm", "imm_sel"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let y = y + i as u16 * 5;
+            let select = find(&ex, fwd);
+            let inputs = [
+                ("ID/EX ", field(&rv.id_ex, data).1),
+                ("EX/MEM", ex_mem_res),
+                ("MEM/WB", wb_data),
+            ];
+            for (j, (name, value)) in inputs.into_iter().enumerate() {
+                let label = if j == 0 { fwd } else { "" };
+                let text = format!("{:<4} {} {:08x}", label, name, value);
+                c.text(x, y + j as u16, &text, pick(select == j as u32));
+            }
+            let source = match field(&rv.id_ex, sel).1 {
+                0 => fwd,
+                _ => alt,
+            };
+            let text = format!("{} ◀ {:<4}   {:08x}", op, source, find(&ex, op));
+            // taking pc or imm bypasses the forwarding mux
+            c.text(x, y + 3, &text, pick(source == alt));
+        }
+        c.text(
+            x,
+            y + 10,
+            &format!(
+                "ALU {:>2}        ▶ {:08x}",
+                field(&rv.id_ex, "alu_ctrl").1,
+                alu_res
+            ),
+            plain,
+        );
+        c.text(
+            x,
+            y + 11,
+            &match (taken, find(&ex, "taken") == 1) {
+                (true, _) => format!("mispredicted ▶ {:08x}", target),
+                (false, true) => "branch taken as predicted".to_string(),
+                (false, false) => "branch not taken".to_string(),
+            },
+            if taken { flush } else { dim },
+        );
+
+        // memory: address, store data and the word read
+        let (x, y) = (MEM.0 + 2, 2);
+        let mem_write = field(&rv.ex_mem, "mem_write").1 == 1;
+        let mem_read = field(&rv.ex_mem, "mem_read").1 == 1;
+        c.text(x, y, &format!("addr  {:08x}", ex_mem_res), plain);
+        c.text(
+            x,
+            y + 1,
+            &format!("wdata {:08x}", field(&rv.ex_mem, "rs2_data").1),
+            pick(mem_write),
+        );
+        c.text(x, y + 2, "write", pick(mem_write));
+        c.text(x + 6, y + 2, "read", pick(mem_read));
+        c.text(
+            x,
+            y + 4,
+            &format!("rdata {:08x}", field(&rv.mem_wb, "mem_data").0),
+            pick(mem_read),
+        );
+
+        // write back: the result mux
+        let (x, y) = (WB.0 + 2, 2);
+        let wb_sel = field(&rv.mem_wb, "wb_sel").1;
+        c.text(x, y, "wb mux", plain);
+        for (i, name) in ["npc", "alu_res", "mem_data"].into_iter().enumerate() {
+            let label = ["npc", "alu", "mem"][i];
+            let text = format!("{} {:08x}", label, field(&rv.mem_wb, name).1);
+            c.text(x + 1, y + 1 + i as u16, &text, pick(wb_sel == i as u32));
+        }
+        c.text(
+            x,
+            y + 5,
+            &format!("x{:<2} ◀ {:08x}", wb_rd, wb_data),
+            pick(wb_write),
+        );
+
+        // the wires running backwards under the stages
+        let fwd = [find(&ex, "fwd1"), find(&ex, "fwd2")];
+        let y = BOX_HEIGHT + 1;
+        c.feedback(
+            y,
+            ID.0 + 2,
+            WB.0 + 2,
+            &format!("write back x{} {:08x}", wb_rd, wb_data),
+            pick(wb_write),
+        );
+        c.feedback(
+            y + 1,
+            EX.0 + 2,
+            MEM.0 + 2,
+            &format!("EX/MEM {:08x}", ex_mem_res),
+            pick(fwd.contains(&1)),
+        );
+        c.feedback(
+            y + 2,
+            EX.0 + 2,
+            WB.0 + 2,
+            &format!("MEM/WB {:08x}", wb_data),
+            pick(fwd.contains(&2)),
+        );
+        c.feedback(
+            y + 3,
+            IF.0 + 2,
+            EX.0 + 2,
+            &format!("branch {:08x}", target),
+            if taken { flush } else { dim },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn line(buf: &Buffer, y: u16) -> String {
+        (0..WIDTH).map(|x| buf.get(x, y).symbol()).collect()
+    }
+    #[test]
+    fn test_datapath() {
+        // 0: addi x1, x0, 1
+        // 4: add x5, x5, x1
+        let insts: [u32; 2] = [0x00100093, 0x001282b3];
+        let pg = Program::from_words(&insts);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        for _ in 0..3 {
+            rv.step();
+        }
+        // add is in EX and takes x1 from EX/MEM
+        let area = Rect::new(0, 0, WIDTH, HEIGHT);
+        let mut buf = Buffer::empty(area);
+        Datapath {
+            rv: &rv,
+            theme: &Theme::default(),
+        }
+        .render(area, &mut buf);
+        assert!(line(&buf, 1).contains(" EX 00000004 "));
+        assert!(line(&buf, 1).contains(" MEM 00000000 "));
+        let fwd = line(&buf, 8);
+        let x = fwd.find("EX/MEM 00000001").unwrap();
+        let x = fwd[..x].chars().count() as u16;
+        assert_eq!(buf.get(x, 8).fg, Color::Green);
+        assert_eq!(buf.get(x, 7).fg, Color::DarkGray);
+        assert_eq!(buf.get(x, 3).fg, Color::DarkGray);
+        assert!(line(&buf, 17).contains("EX/MEM 00000001"));
+        assert!(line(&buf, 13).contains("branch not taken"));
+        // a smaller area is clipped
+        let area = Rect::new(0, 0, 40, 10);
+        let mut buf = Buffer::empty(area);
+        Datapath {
+            rv: &rv,
+            theme: &Theme::default(),
+        }
+        .render(area, &mut buf);
+    }
+}
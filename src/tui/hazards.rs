@@ -0,0 +1,163 @@
+use ratatui::prelude::*;
+
+use super::theme::Theme;
+use crate::common::abi::*;
+use crate::simulator::{inst_format, Rv32i};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Forward,
+    Stall,
+    Flush,
+}
+impl Kind {
+    pub fn style(self, theme: &Theme) -> Style {
+        match self {
+            Kind::Forward => theme.forward,
+            Kind::Stall => theme.stall,
+            Kind::Flush => theme.flush.add_modifier(Modifier::CROSSED_OUT),
+        }
+    }
+}
+
+// why the instruction at `pc` is held, bypassed or thrown away this cycle, `from` is the
+// instruction it depends on
+#[derive(Debug, PartialEq)]
+pub struct Note {
+    pub pc: u32,
+    pub from: Option<u32>,
+    pub text: String,
+    pub kind: Kind,
+}
+
+fn find(signals: &[(&'static str, u32)], name: &str) -> u32 {
+    signals
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or(0, |(_, v)| *v)
+}
+fn held(control: &ControlRef, name: &str) -> u32 {
+    control
+        .inout()
+        .into_iter()
+        .find(|(n, _, _)| *n == name)
+        .map_or(0, |(_, _, out)| out)
+}
+
+pub fn notes(rv: &Rv32i) -> Vec<Note> {
+    let stages = rv.asm.stages();
+    let ex = rv.ex.inner_signal();
+    let mut notes = vec![];
+    // the forwarding muxes in EX, only for the source registers the format has
+    if let Some(pc) = stages[2] {
+        let format = inst_format(rv.imem.read(pc as usize)).unwrap_or('?');
+        for (fwd, rs, formats) in [("fwd1", "rs1", "RISB"), ("fwd2", "rs2", "RSB")] {
+            let (latch, from) = match find(&ex, fwd) {
+                1 => ("EX/MEM", stages[3]),
+                2 => ("MEM/WB", stages[4]),
+                _ => continue,
+            };
+            if formats.contains(format) {
+                notes.push(Note {
+                    pc,
+                    from,
+                    text: format!("{} -> {} x{}", latch, rs, held(&rv.id_ex, rs)),
+                    kind: Kind::Forward,
+                });
+            }
+        }
+    }
+    // a load in EX writes a register the instruction in ID reads
+    if let (Some(pc), 1) = (stages[1], find(&rv.hazard.output(), "raw")) {
+        notes.push(Note {
+            pc,
+            from: stages[2],
+            text: format!("load-use on x{}, stall", held(&rv.id_ex, "rd")),
+            kind: Kind::Stall,
+        });
+    }
+    // a mispredicted branch or jump in EX throws away what was fetched after it
+    if let (Some(branch), 1) = (stages[2], find(&ex, "npc_en")) {
+        for pc in stages[..2].iter().flatten() {
+            notes.push(Note {
+                pc: *pc,
+                from: None,
+                text: format!("flushed, branch at {:x}", branch),
+                kind: Kind::Flush,
+            });
+        }
+    }
+    notes
+}
+
+// a column per dependency whose ends are both on screen, the producer marked ● and the
+// consumer ▶ with │ between them
+pub fn gutter(notes: &[Note], rows: &[u32], theme: &Theme) -> Vec<Vec<Span<'static>>> {
+    let row = |pc: u32| rows.iter().position(|&r| r == pc);
+    let mut gutter = vec![vec![]; rows.len()];
+    for note in notes {
+        let (Some(to), Some(from)) = (row(note.pc), note.from.and_then(row)) else {
+            continue;
+        };
+        if to == from {
+            continue;
+        }
+        for (i, cell) in gutter.iter_mut().enumerate() {
+            let mark = match i {
+                _ if i == from => "●",
+                _ if i == to => "▶",
+                _ if i > from.min(to) && i < from.max(to) => "│",
+                _ => " ",
+            };
+            cell.push(Span::styled(mark, note.kind.style(theme)));
+        }
+    }
+    gutter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Program;
+    use crate::simulator::Rv32iBuilder;
+
+    fn run(insts: &[u32], cycles: usize) -> Rv32i {
+        let pg = Program::from_words(insts);
+        let mut rv = Rv32iBuilder::new(pg).slf_build();
+        for _ in 0..cycles {
+            rv.step();
+        }
+        rv
+    }
+    #[test]
+    fn test_notes() {
+        // addi x1, x0, 1; add x5, x5, x1
+        let rv = run(&[0x00100093, 0x001282b3], 3);
+        let forward = Note {
+            pc: 4,
+            from: Some(0),
+            text: "EX/MEM -> rs2 x1".into(),
+            kind: Kind::Forward,
+        };
+        assert_eq!(notes(&rv), vec![forward]);
+        let gutter = gutter(&notes(&rv), &[0, 4, 8], &Theme::default());
+        assert_eq!(gutter[0][0].content, "●");
+        assert_eq!(gutter[1][0].content, "▶");
+        assert_eq!(gutter[2][0].content, " ");
+
+        // lw x5, 0(x0); add x6, x5, x0
+        let rv = run(&[0x00002283, 0x00028333], 2);
+        let notes = notes(&rv);
+        assert_eq!(notes[0].text, "load-use on x5, stall");
+        assert_eq!((notes[0].pc, notes[0].from), (4, Some(0)));
+
+        // jal x0, 8; addi x1, x0, 1; addi x1, x0, 1
+        let rv = run(&[0x0080006f, 0x00100093, 0x00100093], 2);
+        let flushed: Vec<_> = super::notes(&rv)
+            .into_iter()
+            .filter(|n| n.kind == Kind::Flush)
+            .map(|n| n.pc)
+            .collect();
+        assert_eq!(flushed, vec![8, 4]);
+    }
+}
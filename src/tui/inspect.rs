@@ -0,0 +1,126 @@
+use ratatui::{prelude::*, widgets::*};
+
+use crate::simulator::{decode, inst_format, Rv32i};
+
+// width the panel takes next to the ASM pane
+pub const WIDTH: u16 = 58;
+const STAGES: [&str; 5] = ["IF", "ID", "EX", "MEM", "WB"];
+
+// the encoding fields of an instruction then the control signals decoded from it,
+// fields its format does not have are left out as "-"
+pub fn fields(inst: u32) -> Vec<(&'static str, String)> {
+    let format = inst_format(inst);
+    let signals = decode(inst);
+    let signal = |name: &str| {
+        signals
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+            .unwrap_or_default()
+    };
+    let opcode = inst & 0b111_1111;
+    let funct3 = (inst >> 12) & 0b111;
+    // slli, srli and srai keep funct7 in the immediate
+    let shift = opcode == 0b001_0011 && (funct3 == 0b001 || funct3 == 0b101);
+    let has = |formats: &str| format.is_some_and(|f| formats.contains(f));
+    let show = |present: bool, text: String| if present { text } else { "-".to_string() };
+    let mut fields = vec![
+        ("format", format.map_or("?".to_string(), |f| f.to_string())),
+        ("opcode", format!("{:07b}", opcode)),
+        ("funct3", show(has("RISB"), format!("{:03b}", funct3))),
+        (
+            "funct7",
+            show(has("R") || shift, format!("{:07b}", inst >> 25)),
+        ),
+        ("rs1", show(has("RISB"), format!("x{}", signal("rs1")))),
+        ("rs2", show(has("RSB"), format!("x{}", signal("rs2")))),
+        ("rd", show(has("RIUJ"), format!("x{}", signal("rd")))),
+        ("imm", show(has("ISBUJ"), format!("{:08x}", signal("imm")))),
+    ];
+    fields.extend(signals.iter().skip(4).map(|(name, value)| {
+        let text = match *name {
+            "alu_ctrl" => format!("{:05b}", value),
+            _ => value.to_string(),
+        };
+        (*name, text)
+    }));
+    fields
+}
+
+// the instruction in each stage taken apart, so its decoding can be checked by hand
+pub fn rows(rv: &Rv32i) -> Vec<Row<'static>> {
+    let names: Vec<&str> = std::iter::once("pc")
+        .chain(fields(0).into_iter().map(|(name, _)| name))
+        .collect();
+    // one column of values per stage, a bubble is all "-"
+    let columns: Vec<Vec<String>> = rv
+        .asm
+        .stages()
+        .into_iter()
+        .map(|pc| match pc {
+            Some(pc) => std::iter::once(format!("{:08x}", pc))
+                .chain(
+                    fields(rv.imem.read(pc as usize))
+                        .into_iter()
+                        .map(|(_, value)| value),
+                )
+                .collect(),
+            None => vec!["-".to_string(); names.len()],
+        })
+        .collect();
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let cells = std::iter::once(name.to_string())
+                .chain(columns.iter().map(|column| column[i].clone()));
+            Row::new(cells)
+        })
+        .collect()
+}
+pub fn table(rows: Vec<Row>) -> Table {
+    let mut widths = vec![Constraint::Length(9)];
+    widths.extend(STAGES.iter().map(|_| Constraint::Length(8)));
+    Table::new(rows, widths)
+        .block(
+            Block::default()
+                .title(" Decode ")
+                .title_alignment(Alignment::Center)
+                .borders(Borders::ALL),
+        )
+        .header(Row::new(std::iter::once("").chain(STAGES)))
+        .column_spacing(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        // sw x8, 428(x2)
+        let fields = fields(0x1a812623);
+        let get = |name| fields.iter().find(|(n, _)| *n == name).unwrap().1.clone();
+        assert_eq!(get("format"), "S");
+        assert_eq!(get("opcode"), "0100011");
+        assert_eq!(get("funct3"), "010");
+        assert_eq!(get("funct7"), "-");
+        assert_eq!(
+            (get("rs1"), get("rs2"), get("rd")),
+            ("x2".into(), "x8".into(), "-".into())
+        );
+        assert_eq!(get("imm"), "000001ac");
+        assert_eq!(
+            (get("mem_write"), get("reg_write")),
+            ("1".into(), "0".into())
+        );
+        // sub x10, x10, x11
+        let fields = super::fields(0x40b50533);
+        let get = |name| fields.iter().find(|(n, _)| *n == name).unwrap().1.clone();
+        assert_eq!(
+            (get("format"), get("funct7")),
+            ("R".into(), "0100000".into())
+        );
+        assert_eq!((get("imm"), get("alu_ctrl")), ("-".into(), "10001".into()));
+    }
+}
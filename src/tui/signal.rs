@@ -5,6 +5,7 @@ use ratatui::{
     widgets::{List, Widget},
 };
 
+#[allow(dead_code)]
 pub struct SignalList {
     pub signals: Rc<RefCell<Vec<(String, u32)>>>,
 }
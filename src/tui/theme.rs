@@ -0,0 +1,277 @@
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use crate::config::TuiConfig;
+
+// what a key outside the `:` and goto prompts does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Step,
+    Back,
+    NextTab,
+    Goto,
+    Command,
+    Save,
+    Load,
+    Export,
+    ExportCsv,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    Play,
+    Faster,
+    Slower,
+    Snapshot,
+}
+// name in the config file and default key of each action
+const ACTIONS: [(&str, Action, &str); 18] = [
+    ("quit", Action::Quit, "q"),
+    ("step", Action::Step, "Right"),
+    ("back", Action::Back, "Left"),
+    ("tab", Action::NextTab, "Tab"),
+    ("goto", Action::Goto, "g"),
+    ("command", Action::Command, ":"),
+    ("save", Action::Save, "s"),
+    ("load", Action::Load, "l"),
+    ("export", Action::Export, "e"),
+    ("export_csv", Action::ExportCsv, "E"),
+    ("scroll_up", Action::ScrollUp, "Up"),
+    ("scroll_down", Action::ScrollDown, "Down"),
+    ("scroll_left", Action::ScrollLeft, "<"),
+    ("scroll_right", Action::ScrollRight, ">"),
+    ("play", Action::Play, "Space"),
+    ("faster", Action::Faster, "+"),
+    ("slower", Action::Slower, "-"),
+    ("snapshot", Action::Snapshot, "x"),
+];
+
+// a single character, or a named key like Tab, Left or F5
+fn key(name: &str) -> Result<KeyCode, String> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+    Ok(match name.to_lowercase().as_str() {
+        "tab" => KeyCode::Tab,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            Some(n) => KeyCode::F(n),
+            None => return Err(format!("no key {}", name)),
+        },
+    })
+}
+
+#[derive(Debug)]
+pub struct Keys {
+    actions: HashMap<KeyCode, Action>,
+    // as written in the config, for the footer
+    names: HashMap<Action, String>,
+}
+impl Keys {
+    pub fn new(config: &BTreeMap<String, String>) -> Result<Self, String> {
+        if let Some(name) = config
+            .keys()
+            .find(|name| ACTIONS.iter().all(|(n, _, _)| n != name))
+        {
+            return Err(format!("no action {}", name));
+        }
+        let mut keys = Keys {
+            actions: HashMap::new(),
+            names: HashMap::new(),
+        };
+        for (name, action, default) in ACTIONS {
+            let binding = config.get(name).map_or(default, |k| k.as_str());
+            if let Some(other) = keys.actions.insert(key(binding)?, action) {
+                return Err(format!(
+                    "key {} is bound to {:?} and {:?}",
+                    binding, other, action
+                ));
+            }
+            keys.names.insert(action, binding.to_string());
+        }
+        Ok(keys)
+    }
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.actions.get(&code).copied()
+    }
+    pub fn name(&self, action: Action) -> &str {
+        &self.names[&action]
+    }
+}
+impl Default for Keys {
+    fn default() -> Self {
+        Keys::new(&BTreeMap::new()).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    // fetch to write back in the ASM pane
+    pub stages: [Style; 5],
+    pub forward: Style,
+    pub stall: Style,
+    pub flush: Style,
+    // paths taken and not taken in the datapath
+    pub active: Style,
+    pub dim: Style,
+    pub tab: Style,
+    pub footer: Style,
+    pub breakpoint: Style,
+}
+impl Theme {
+    pub fn preset(name: &str) -> Result<Self, String> {
+        let fg = |color| Style::default().fg(color);
+        let bold = |color| fg(color).add_modifier(Modifier::BOLD);
+        let modifier = |modifier| Style::default().add_modifier(modifier);
+        Ok(match name {
+            "default" => Theme {
+                stages: [
+                    fg(Color::Green),
+                    fg(Color::Yellow),
+                    fg(Color::Blue),
+                    fg(Color::Magenta),
+                    fg(Color::Cyan),
+                ],
+                forward: fg(Color::Green),
+                stall: fg(Color::Yellow),
+                flush: fg(Color::Red),
+                active: bold(Color::Green),
+                dim: fg(Color::DarkGray),
+                tab: fg(Color::Yellow),
+                footer: fg(Color::Gray),
+                breakpoint: fg(Color::Red),
+            },
+            // no colour at all, stages and hazards told apart by the text style
+            "monochrome" => Theme {
+                stages: [
+                    modifier(Modifier::ITALIC),
+                    modifier(Modifier::BOLD),
+                    modifier(Modifier::REVERSED),
+                    modifier(Modifier::UNDERLINED),
+                    modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                ],
+                forward: modifier(Modifier::BOLD),
+                stall: modifier(Modifier::UNDERLINED),
+                flush: modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                active: modifier(Modifier::REVERSED),
+                dim: modifier(Modifier::DIM),
+                tab: modifier(Modifier::REVERSED),
+                footer: Style::default(),
+                breakpoint: modifier(Modifier::BOLD),
+            },
+            // bright and bold for projectors
+            "high-contrast" => Theme {
+                stages: [
+                    bold(Color::LightGreen),
+                    bold(Color::LightYellow),
+                    bold(Color::LightCyan),
+                    bold(Color::LightMagenta),
+                    bold(Color::White),
+                ],
+                forward: bold(Color::LightGreen),
+                stall: bold(Color::LightYellow),
+                flush: bold(Color::LightRed),
+                active: bold(Color::LightGreen).add_modifier(Modifier::REVERSED),
+                dim: fg(Color::Gray),
+                tab: bold(Color::LightYellow).add_modifier(Modifier::REVERSED),
+                footer: fg(Color::White),
+                breakpoint: bold(Color::LightRed),
+            },
+            _ => return Err(format!("no theme {}", name)),
+        })
+    }
+    // the preset with the colours of the config over it
+    pub fn new(config: &TuiConfig) -> Result<Self, String> {
+        let mut theme = Theme::preset(config.theme.as_deref().unwrap_or("default"))?;
+        for (name, color) in &config.colors {
+            let color =
+                Color::from_str(color).map_err(|_| format!("no colour {} for {}", color, name))?;
+            let style = match name.as_str() {
+                "fetch" => &mut theme.stages[0],
+                "decode" => &mut theme.stages[1],
+                "execute" => &mut theme.stages[2],
+                "memory" => &mut theme.stages[3],
+                "writeback" => &mut theme.stages[4],
+                "forward" => &mut theme.forward,
+                "stall" => &mut theme.stall,
+                "flush" => &mut theme.flush,
+                "active" => &mut theme.active,
+                "dim" => &mut theme.dim,
+                "tab" => &mut theme.tab,
+                "footer" => &mut theme.footer,
+                "breakpoint" => &mut theme.breakpoint,
+                _ => return Err(format!("no colour {}", name)),
+            };
+            *style = style.fg(color);
+        }
+        Ok(theme)
+    }
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::preset("default").unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        let keys = Keys::default();
+        assert_eq!(keys.action(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(keys.action(KeyCode::Right), Some(Action::Step));
+        assert_eq!(keys.name(Action::NextTab), "Tab");
+        let config = BTreeMap::from([
+            ("step".to_string(), "n".to_string()),
+            ("back".to_string(), "p".to_string()),
+            ("quit".to_string(), "F10".to_string()),
+        ]);
+        let keys = Keys::new(&config).unwrap();
+        assert_eq!(keys.action(KeyCode::Char('n')), Some(Action::Step));
+        assert_eq!(keys.action(KeyCode::F(10)), Some(Action::Quit));
+        assert_eq!(keys.action(KeyCode::Right), None);
+        let config = BTreeMap::from([("step".to_string(), "q".to_string())]);
+        assert!(Keys::new(&config).is_err());
+        let config = BTreeMap::from([("jump".to_string(), "j".to_string())]);
+        assert_eq!(Keys::new(&config).err(), Some("no action jump".into()));
+        let config = BTreeMap::from([("step".to_string(), "Nope".to_string())]);
+        assert_eq!(Keys::new(&config).err(), Some("no key Nope".into()));
+    }
+    #[test]
+    fn test_theme() {
+        assert_eq!(Theme::default().stages[0].fg, Some(Color::Green));
+        let mono = Theme::preset("monochrome").unwrap();
+        assert!(mono.stages.iter().all(|s| s.fg.is_none()));
+        let config = TuiConfig {
+            theme: Some("high-contrast".into()),
+            colors: BTreeMap::from([("fetch".to_string(), "#ff8000".to_string())]),
+            ..Default::default()
+        };
+        let theme = Theme::new(&config).unwrap();
+        assert_eq!(theme.stages[0].fg, Some(Color::Rgb(0xff, 0x80, 0)));
+        assert!(theme.stages[0].add_modifier.contains(Modifier::BOLD));
+        assert!(Theme::preset("neon").is_err());
+        let config = TuiConfig {
+            colors: BTreeMap::from([("fetch".to_string(), "plaid".to_string())]),
+            ..Default::default()
+        };
+        assert!(Theme::new(&config).is_err());
+    }
+}
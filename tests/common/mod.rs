@@ -0,0 +1,49 @@
+// shared by the compliance test targets, each uses only part of it
+#![allow(dead_code)]
+use rv_simulator::abi::*;
+use rv_simulator::{load_elf, Iss, Program, Rv32i, Rv32iBuilder};
+
+// every test is done long before this, a runaway one is a failure
+const MAX_CYCLES: usize = 200_000;
+
+pub fn program(path: &str) -> Program {
+    let path = format!("{}/tests/{}", env!("CARGO_MANIFEST_DIR"), path);
+    let dat = std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    load_elf(&dat).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+// what the test has written to `tohost` when it finished, 0 if it never did
+pub fn run_pipeline(pg: &Program) -> (u32, Rv32i) {
+    let tohost = pg.symbols["tohost"];
+    let mut rv = Rv32iBuilder::new(pg.clone()).slf_build();
+    while rv.cycle < MAX_CYCLES && rv.dmem.read(tohost) == 0 {
+        rv.step();
+    }
+    (rv.dmem.read(tohost), rv)
+}
+
+pub fn run_iss(pg: &Program) -> (u32, Iss) {
+    let tohost = pg.symbols["tohost"];
+    let mut iss = Iss::new(pg);
+    while iss.retired < MAX_CYCLES && iss.dmem.read(tohost) == 0 {
+        iss.step();
+    }
+    (iss.dmem.read(tohost), iss)
+}
+
+// the HTIF convention: 1 is a pass, otherwise the failing test case is in the upper bits
+pub fn check_tohost(name: &str, tohost: u32) {
+    match tohost {
+        1 => {}
+        0 => panic!("{}: did not finish in {} cycles", name, MAX_CYCLES),
+        _ => panic!("{}: failed test case {}", name, tohost >> 1),
+    }
+}
+
+// the words from `begin_signature` up to `end_signature`, one per line as in the reference output
+pub fn signature(pg: &Program, read: impl Fn(usize) -> u32) -> String {
+    (pg.symbols["begin_signature"]..pg.symbols["end_signature"])
+        .step_by(4)
+        .map(|addr| format!("{:08x}\n", read(addr)))
+        .collect()
+}
@@ -0,0 +1,12 @@
+#!/bin/sh
+# Rebuilds bin/count.elf, a program with line debug info, from count.S.
+#
+# Needs llvm-mc and an lld, e.g. the rust-lld shipped with rustup:
+#   LD="$(find ~/.rustup -name rust-lld | head -n1) -flavor gnu" ./build.sh
+set -e
+cd "$(dirname "$0")"
+LD=${LD:-ld.lld}
+mkdir -p bin
+llvm-mc -triple=riscv32 -mattr=-relax -g -fdebug-compilation-dir=. -filetype=obj -o bin/count.o count.S
+$LD -Ttext=0x10000 -e _start -o bin/count.elf bin/count.o
+rm bin/count.o
@@ -0,0 +1,33 @@
+#!/bin/sh
+# Rebuilds the checked-in test binaries in bin/ from rv32i_m/I/src/*.S against
+# env/model_test.h, the macros of this simulator. Run gen.py first to regenerate
+# the sources and the references.
+#
+# With REF set to an upstream tag or commit, one that still has the references
+# under riscv-test-suite/rv32i_m/I/references, the sources, the Sail references
+# and env/arch_test.h are fetched from riscv-arch-test instead, to check in
+# afterwards. Needs cpp, llvm-mc and an lld, e.g. the rust-lld shipped with
+# rustup, and git for REF:
+#   LD="$(find ~/.rustup -name rust-lld | head -n1) -flavor gnu" ./build.sh
+set -e
+cd "$(dirname "$0")"
+LD=${LD:-ld.lld}
+if [ -n "$REF" ]; then
+    upstream=$(mktemp -d)
+    trap 'rm -rf "$upstream"' EXIT
+    git clone -q https://github.com/riscv-non-isa/riscv-arch-test "$upstream"
+    git -C "$upstream" checkout -q "$REF"
+    rm -rf rv32i_m
+    mkdir -p rv32i_m/I
+    cp -r "$upstream/riscv-test-suite/rv32i_m/I/src" "$upstream/riscv-test-suite/rv32i_m/I/references" rv32i_m/I/
+    cp "$upstream/riscv-test-suite/env/arch_test.h" env/
+fi
+rm -rf bin
+mkdir -p bin
+for src in rv32i_m/I/src/*.S; do
+    name=$(basename "$src" .S)
+    cpp -P -x assembler-with-cpp -Ienv -DXLEN=32 "$src" |
+        llvm-mc -triple=riscv32 -mattr=-relax -filetype=obj -o "bin/$name.o"
+    $LD -T ../riscv-tests/env/link.ld -o "bin/$name.elf" "bin/$name.o"
+    rm "bin/$name.o"
+done
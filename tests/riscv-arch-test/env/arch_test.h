@@ -0,0 +1,111 @@
+// The test macros of riscv-arch-test, the RV32I subset. Every macro leaves
+// its result in the signature and nothing is checked on the target: the
+// signature is compared with the reference output afterwards.
+
+#ifndef _ARCH_TEST_H
+#define _ARCH_TEST_H
+
+#define XLEN 32
+#define MASK_XLEN(x) ((x) & 0xffffffff)
+
+#define RVTEST_ISA(_STR)
+#define RVTEST_CASE(_PNAME, _DSTR, ...)
+
+#define RVTEST_CODE_BEGIN                                               \
+  .section .text.init;                                                  \
+  .align 4;                                                             \
+  .globl _start;                                                        \
+_start:                                                                 \
+  RVMODEL_BOOT
+
+#define RVTEST_CODE_END
+
+#define RVTEST_DATA_BEGIN                                               \
+  .data;                                                                \
+  .align 4; .global rvtest_data_begin; rvtest_data_begin:
+
+#define RVTEST_DATA_END .global rvtest_data_end; rvtest_data_end:
+
+#define RVTEST_SIGBASE(_R, _TAG) la _R, _TAG
+
+#define RVTEST_SIGUPD(_BR, _R, _OFF) sw _R, _OFF(_BR)
+
+// rd = rs1 op rs2
+#define TEST_RR_OP(inst, destreg, reg1, reg2, correctval, val1, val2, swreg, offset) \
+  li reg1, MASK_XLEN(val1);                                             \
+  li reg2, MASK_XLEN(val2);                                             \
+  inst destreg, reg1, reg2;                                             \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+// rd = rs1 op imm
+#define TEST_IMM_OP(inst, destreg, reg, correctval, val, imm, swreg, offset) \
+  li reg, MASK_XLEN(val);                                               \
+  inst destreg, reg, imm;                                               \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+// rd = imm << 12 for lui, the same relative to the instruction for auipc
+#define TEST_LUI_OP(destreg, correctval, imm, swreg, offset)           \
+  lui destreg, imm;                                                     \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+#define TEST_AUIPC_OP(destreg, tempreg, correctval, imm, swreg, offset) \
+1: auipc destreg, imm;                                                  \
+  la tempreg, 1b;                                                       \
+  sub destreg, destreg, tempreg;                                        \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+// tempreg is 2 when the branch is taken and 3 when it falls through,
+// first forward then backward
+#define TEST_BRANCH_OP(inst, tempreg, reg1, reg2, val1, val2, swreg, offset) \
+  li reg1, MASK_XLEN(val1);                                             \
+  li reg2, MASK_XLEN(val2);                                             \
+  li tempreg, 0;                                                        \
+  inst reg1, reg2, 1f;                                                  \
+  addi tempreg, tempreg, 1;                                             \
+1: addi tempreg, tempreg, 2;                                            \
+  RVTEST_SIGUPD(swreg, tempreg, offset);                                \
+  li tempreg, 0;                                                        \
+  j 2f;                                                                 \
+1: addi tempreg, tempreg, 2;                                            \
+  j 3f;                                                                 \
+2: inst reg1, reg2, 1b;                                                 \
+  addi tempreg, tempreg, 3;                                             \
+3: RVTEST_SIGUPD(swreg, tempreg, offset + 4)
+
+// the link is stored relative to the jump, then the count of skipped
+// instructions that ran, which has to be 0
+#define TEST_JAL_OP(tempreg, rd, swreg, offset)                         \
+  li tempreg, 0;                                                        \
+1: jal rd, 2f;                                                          \
+  addi tempreg, tempreg, 1;                                             \
+  addi tempreg, tempreg, 1;                                             \
+2: RVTEST_SIGUPD(swreg, tempreg, offset + 4);                           \
+  la tempreg, 1b;                                                       \
+  sub rd, rd, tempreg;                                                  \
+  RVTEST_SIGUPD(swreg, rd, offset)
+
+#define TEST_JALR_OP(tempreg, rd, rs1, imm, swreg, offset)              \
+  la rs1, 2f - (imm);                                                   \
+  li tempreg, 0;                                                        \
+1: jalr rd, imm(rs1);                                                   \
+  addi tempreg, tempreg, 1;                                             \
+  addi tempreg, tempreg, 1;                                             \
+2: RVTEST_SIGUPD(swreg, tempreg, offset + 4);                           \
+  la tempreg, 1b;                                                       \
+  sub rd, rd, tempreg;                                                  \
+  RVTEST_SIGUPD(swreg, rd, offset)
+
+// load from rvtest_data + index with the base register set up so that
+// index = base + imm
+#define TEST_LOAD(swreg, testreg, index, rs1, destreg, imm, offset, inst) \
+  la rs1, rvtest_data + (index) - (imm);                                \
+  inst destreg, imm(rs1);                                               \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+// store straight into the signature
+#define TEST_STORE(swreg, testreg, index, rs1, rs2, rs2_val, imm, offset, inst) \
+  li rs2, MASK_XLEN(rs2_val);                                           \
+  addi rs1, swreg, (offset) + (index) - (imm);                          \
+  inst rs2, imm(rs1)
+
+#endif
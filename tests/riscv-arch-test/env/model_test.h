@@ -0,0 +1,40 @@
+// The model specific macros of riscv-arch-test for this simulator: it halts
+// by storing 1 to `tohost` and the signature is read back from data memory
+// between `begin_signature` and `end_signature`.
+
+#ifndef _COMPLIANCE_MODEL_H
+#define _COMPLIANCE_MODEL_H
+
+#define RVMODEL_DATA_SECTION                                            \
+  .pushsection .tohost,"aw",@progbits;                                  \
+  .align 8; .global tohost; tohost: .word 0; .word 0;                   \
+  .align 8; .global fromhost; fromhost: .word 0; .word 0;               \
+  .popsection;
+
+#define RVMODEL_BOOT
+
+#define RVMODEL_HALT                                                    \
+  li t0, 1;                                                             \
+  sw t0, tohost, t1;                                                    \
+1: j 1b;
+
+#define RVMODEL_DATA_BEGIN                                              \
+  RVMODEL_DATA_SECTION                                                  \
+  .align 4; .global begin_signature; begin_signature:
+
+#define RVMODEL_DATA_END                                                \
+  .align 4; .global end_signature; end_signature:
+
+// no console and no interrupts
+#define RVMODEL_IO_INIT
+#define RVMODEL_IO_WRITE_STR(_R, _STR)
+#define RVMODEL_IO_CHECK()
+#define RVMODEL_IO_ASSERT_GPR_EQ(_S, _R, _I)
+#define RVMODEL_IO_ASSERT_SFPR_EQ(_F, _R, _I)
+#define RVMODEL_IO_ASSERT_DFPR_EQ(_D, _R, _I)
+#define RVMODEL_SET_MSW_INT
+#define RVMODEL_CLEAR_MSW_INT
+#define RVMODEL_CLEAR_MTIMER_INT
+#define RVMODEL_CLEAR_MEXT_INT
+
+#endif
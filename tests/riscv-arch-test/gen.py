@@ -0,0 +1,319 @@
+#!/usr/bin/env python3
+# Writes rv32i_m/I/src/*.S in the layout of riscv-arch-test together with
+# their reference signatures in rv32i_m/I/references, computed by the small
+# RV32I model below instead of Sail. Run build.sh afterwards.
+import os
+import random
+
+M = 0xFFFFFFFF
+SIG = 1  # signature pointer
+TMP = 31  # scratch register of the branch and jump macros
+CORNERS = [
+    0x00000000, 0x00000001, 0x00000002, 0x00000003, 0x00000005, 0x00000400,
+    0x00010000, 0x33333333, 0x55555555, 0x66666666, 0x7FFFFFFE, 0x7FFFFFFF,
+    0x80000000, 0x80000001, 0x99999999, 0xAAAAAAAA, 0xCCCCCCCC, 0xFFFF0000,
+    0xFFFFFBFF, 0xFFFFFFFE, 0xFFFFFFFF,
+]
+IMM_CORNERS = [0, 1, 2, -1, -2, 0x7FF, -0x800, 0x555, -0x556, 0x3FF, -0x400, 0x10]
+DATA = [0xBABECAFE, 0xABECAFEB, 0xBECAFEBA, 0xECAFEBAB]
+
+
+def s32(x):
+    x &= M
+    return x - (1 << 32) if x >> 31 else x
+
+
+def sext(x, bits):
+    x &= (1 << bits) - 1
+    return x - (1 << bits) if x >> (bits - 1) else x
+
+
+RR = {
+    "add": lambda a, b: a + b,
+    "sub": lambda a, b: a - b,
+    "sll": lambda a, b: a << (b & 31),
+    "srl": lambda a, b: a >> (b & 31),
+    "sra": lambda a, b: s32(a) >> (b & 31),
+    "slt": lambda a, b: int(s32(a) < s32(b)),
+    "sltu": lambda a, b: int(a < b),
+    "and": lambda a, b: a & b,
+    "or": lambda a, b: a | b,
+    "xor": lambda a, b: a ^ b,
+}
+IMM = {
+    "addi": "add", "slti": "slt", "sltiu": "sltu", "andi": "and",
+    "ori": "or", "xori": "xor", "slli": "sll", "srli": "srl", "srai": "sra",
+}
+BRANCH = {
+    "beq": lambda a, b: a == b,
+    "bne": lambda a, b: a != b,
+    "blt": lambda a, b: s32(a) < s32(b),
+    "bge": lambda a, b: s32(a) >= s32(b),
+    "bltu": lambda a, b: a < b,
+    "bgeu": lambda a, b: a >= b,
+}
+LOAD = {
+    "lb-align": ("lb", 1, True), "lbu-align": ("lbu", 1, False),
+    "lh-align": ("lh", 2, True), "lhu-align": ("lhu", 2, False),
+    "lw-align": ("lw", 4, True),
+}
+STORE = {"sb-align": ("sb", 1), "sh-align": ("sh", 2), "sw-align": ("sw", 4)}
+
+
+def regs(rng, n, exclude=()):
+    pool = [r for r in range(32) if r not in (SIG, TMP) + tuple(exclude)]
+    return [rng.choice(pool) for _ in range(n)]
+
+
+def value(rng):
+    return rng.choice(CORNERS) if rng.random() < 0.6 else rng.getrandbits(32)
+
+
+def x(r):
+    return "x%d" % r
+
+
+def coverage(rd, rs1, rs2=None):
+    notes = []
+    if rs2 is not None:
+        if rs1 == rs2 == rd:
+            notes.append("rs1 == rs2 == rd")
+        elif rs1 == rs2:
+            notes.append("rs1 == rs2 != rd")
+        elif rd == rs1:
+            notes.append("rs1 == rd != rs2")
+        elif rd == rs2:
+            notes.append("rs2 == rd != rs1")
+        else:
+            notes.append("rs1 != rs2 != rd")
+    elif rs1 == rd:
+        notes.append("rs1 == rd")
+    else:
+        notes.append("rs1 != rd")
+    if rd == 0:
+        notes.append("rd == x0")
+    return ", ".join(notes)
+
+
+def rr_test(name, rng):
+    op = RR[name]
+    body, sig = [], []
+    shift = name in ("sll", "srl", "sra")
+    for i in range(24):
+        rd, rs1, rs2 = regs(rng, 3)
+        if i % 6 == 1:
+            rs2 = rs1
+        elif i % 6 == 2:
+            rd = rs1
+        a, b = value(rng), value(rng)
+        if shift and i % 2 == 0:
+            b = rng.randrange(32)
+        if rs1 == rs2:
+            a = b
+        va = 0 if rs1 == 0 else a
+        vb = 0 if rs2 == 0 else b
+        res = 0 if rd == 0 else op(va, vb) & M
+        body.append("inst_%d:\n// %s, rs1_val == 0x%08x, rs2_val == 0x%08x" % (i, coverage(rd, rs1, rs2), va, vb))
+        body.append("TEST_RR_OP(%s, %s, %s, %s, 0x%08x, 0x%08x, 0x%08x, %s, %d)"
+                    % (name, x(rd), x(rs1), x(rs2), res, a, b, x(SIG), 4 * len(sig)))
+        sig.append(res)
+    return body, sig
+
+
+def imm_test(name, rng):
+    op = RR[IMM[name]]
+    shift = name in ("slli", "srli", "srai")
+    body, sig = [], []
+    for i in range(24):
+        rd, rs1 = regs(rng, 2)
+        if i % 4 == 1:
+            rd = rs1
+        a = value(rng)
+        if shift:
+            imm = rng.randrange(32)
+        else:
+            imm = rng.choice(IMM_CORNERS) if rng.random() < 0.6 else rng.randrange(-0x800, 0x800)
+        va = 0 if rs1 == 0 else a
+        res = 0 if rd == 0 else op(va, imm & M) & M
+        body.append("inst_%d:\n// %s, rs1_val == 0x%08x, imm_val == %d" % (i, coverage(rd, rs1), va, imm))
+        body.append("TEST_IMM_OP(%s, %s, %s, 0x%08x, 0x%08x, %d, %s, %d)"
+                    % (name, x(rd), x(rs1), res, a, imm, x(SIG), 4 * len(sig)))
+        sig.append(res)
+    return body, sig
+
+
+def upper_test(name, rng):
+    body, sig = [], []
+    for i in range(16):
+        (rd,) = regs(rng, 1)
+        imm = rng.choice([0, 1, 0x7FFFF, 0x80000, 0xFFFFF, 0x55555, 0xAAAAA]) if i < 8 else rng.getrandbits(20)
+        res = 0 if rd == 0 else (imm << 12) & M
+        body.append("inst_%d:\n// %s, imm_val == 0x%05x" % (i, "rd == x0" if rd == 0 else "rd != x0", imm))
+        if name == "lui":
+            body.append("TEST_LUI_OP(%s, 0x%08x, 0x%05x, %s, %d)" % (x(rd), res, imm, x(SIG), 4 * len(sig)))
+        else:
+            body.append("TEST_AUIPC_OP(%s, %s, 0x%08x, 0x%05x, %s, %d)"
+                        % (x(rd), x(TMP), res, imm, x(SIG), 4 * len(sig)))
+        sig.append(res)
+    return body, sig
+
+
+def branch_test(name, rng):
+    op = BRANCH[name]
+    body, sig = [], []
+    for i in range(16):
+        rs1, rs2 = regs(rng, 2)
+        a, b = value(rng), value(rng)
+        if i % 4 == 0:
+            b = a
+        elif i % 4 == 1:
+            rs2 = rs1
+        if rs1 == rs2:
+            a = b
+        va = 0 if rs1 == 0 else a
+        vb = 0 if rs2 == 0 else b
+        res = 2 if op(va, vb) else 3
+        body.append("inst_%d:\n// rs1_val == 0x%08x, rs2_val == 0x%08x, %s"
+                    % (i, va, vb, "taken" if res == 2 else "not taken"))
+        body.append("TEST_BRANCH_OP(%s, %s, %s, %s, 0x%08x, 0x%08x, %s, %d)"
+                    % (name, x(TMP), x(rs1), x(rs2), a, b, x(SIG), 4 * len(sig)))
+        sig += [res, res]
+    return body, sig
+
+
+def jump_test(name, rng):
+    body, sig = [], []
+    for i in range(12):
+        rd, rs1 = regs(rng, 2, exclude=(0,))
+        if i % 4 == 0:
+            rd = 0
+        res = 0 if rd == 0 else 4
+        if name == "jal":
+            body.append("inst_%d:\n// %s" % (i, "rd == x0" if rd == 0 else "rd != x0"))
+            body.append("TEST_JAL_OP(%s, %s, %s, %d)" % (x(TMP), x(rd), x(SIG), 4 * len(sig)))
+        else:
+            if i % 4 == 1:
+                rd = rs1
+            imm = rng.choice([0, 4, -4, 0x7F0, -0x800, 0x555 & ~3])
+            body.append("inst_%d:\n// %s, imm_val == %d" % (i, coverage(rd, rs1), imm))
+            body.append("TEST_JALR_OP(%s, %s, %s, %d, %s, %d)" % (x(TMP), x(rd), x(rs1), imm, x(SIG), 4 * len(sig)))
+        sig += [res, 0]
+    return body, sig
+
+
+def load_test(name, rng):
+    inst, size, signed = LOAD[name]
+    mem = b"".join(w.to_bytes(4, "little") for w in DATA)
+    body, sig = [], []
+    for i in range(16):
+        rd, rs1 = regs(rng, 2, exclude=(0,))
+        if i % 4 == 3:
+            rd = 0
+        index = rng.randrange(0, len(mem), size)
+        imm = rng.choice([0, 4, -4, 0x7F0, -0x800, 0x555 & ~(size - 1)]) if i else 0
+        val = int.from_bytes(mem[index:index + size], "little")
+        if signed:
+            val = sext(val, size * 8)
+        res = 0 if rd == 0 else val & M
+        body.append("inst_%d:\n// %s, ea_align == 0, imm_val == %d, index == %d" % (i, coverage(rd, rs1), imm, index))
+        body.append("TEST_LOAD(%s, %s, %d, %s, %s, %d, %d, %s)"
+                    % (x(SIG), x(TMP), index, x(rs1), x(rd), imm, 4 * len(sig), inst))
+        sig.append(res)
+    return body, sig
+
+
+def store_test(name, rng):
+    inst, size = STORE[name]
+    body, words = [], []
+    for i in range(16):
+        rs1, rs2 = regs(rng, 2, exclude=(0,))
+        while rs2 == rs1:
+            (rs2,) = regs(rng, 1, exclude=(0,))
+        if i % 4 == 3:
+            rs2 = 0
+        val = value(rng)
+        index = rng.randrange(0, 4, size)
+        imm = rng.choice([0, 4, -4, 0x100, -0x100, 0x10])
+        stored = 0 if rs2 == 0 else val
+        word = 0xDEADBEEF
+        mask = ((1 << (size * 8)) - 1) << (index * 8)
+        word = (word & ~mask | (stored << (index * 8)) & mask) & M
+        body.append("inst_%d:\n// rs2_val == 0x%08x, ea_align == %d, imm_val == %d" % (i, stored, index, imm))
+        body.append("TEST_STORE(%s, %s, %d, %s, %s, 0x%08x, %d, %d, %s)"
+                    % (x(SIG), x(TMP), index, x(rs1), x(rs2), val, imm, 4 * len(words), inst))
+        words.append(word)
+    return body, words
+
+
+def generate(name):
+    rng = random.Random(name)
+    if name in RR:
+        return rr_test(name, rng)
+    if name in IMM:
+        return imm_test(name, rng)
+    if name in ("lui", "auipc"):
+        return upper_test(name, rng)
+    if name in BRANCH:
+        return branch_test(name, rng)
+    if name in ("jal", "jalr"):
+        return jump_test(name, rng)
+    if name in LOAD:
+        return load_test(name, rng)
+    return store_test(name, rng)
+
+
+TEMPLATE = """// -----------
+// This file was generated by gen.py
+// -----------
+// This assembly file tests the {inst} instruction of the RISC-V I extension.
+
+#include "model_test.h"
+#include "arch_test.h"
+RVTEST_ISA("RV32I")
+
+RVTEST_CODE_BEGIN
+
+RVTEST_CASE(0,"//check ISA:=regex(.*32.*);check ISA:=regex(.*I.*);def TEST_CASE_1=True;",{inst})
+
+RVTEST_SIGBASE({sig},signature_{sig}_1)
+
+{body}
+
+RVTEST_CODE_END
+RVMODEL_HALT
+
+RVTEST_DATA_BEGIN
+.align 4
+rvtest_data:
+{data}
+RVTEST_DATA_END
+
+RVMODEL_DATA_BEGIN
+
+signature_{sig}_1:
+    .fill {words},4,0xdeadbeef
+
+RVMODEL_DATA_END
+"""
+
+
+def main():
+    os.chdir(os.path.dirname(os.path.abspath(__file__)))
+    names = list(RR) + list(IMM) + ["lui", "auipc"] + list(BRANCH) + ["jal", "jalr"] + list(LOAD) + list(STORE)
+    for name in names:
+        body, sig = generate(name)
+        src = TEMPLATE.format(
+            inst=name.split("-")[0],
+            sig=x(SIG),
+            body="\n".join(body),
+            data="\n".join(".word 0x%08x" % w for w in DATA),
+            words=len(sig),
+        )
+        with open("rv32i_m/I/src/%s-01.S" % name, "w") as f:
+            f.write(src)
+        with open("rv32i_m/I/references/%s-01.reference_output" % name, "w") as f:
+            f.write("".join("%08x\n" % w for w in sig))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,16 @@
+#!/bin/sh
+# Rebuilds the checked-in test binaries in bin/ from rv32ui/*.S.
+#
+# Needs cpp, llvm-mc and an lld, e.g. the rust-lld shipped with rustup:
+#   LD="$(find ~/.rustup -name rust-lld | head -n1) -flavor gnu" ./build.sh
+set -e
+cd "$(dirname "$0")"
+LD=${LD:-ld.lld}
+mkdir -p bin
+for src in rv32ui/*.S; do
+    name=rv32ui-p-$(basename "$src" .S)
+    cpp -P -x assembler-with-cpp -Ienv "$src" |
+        llvm-mc -triple=riscv32 -mattr=-relax -filetype=obj -o "bin/$name.o"
+    $LD -T env/link.ld -o "bin/$name" "bin/$name.o"
+    rm "bin/$name.o"
+done
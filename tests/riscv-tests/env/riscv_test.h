@@ -0,0 +1,62 @@
+// The physical-memory ("p") environment of riscv-tests, cut down to what the
+// simulator implements: there are no CSRs or traps, so instead of an ecall
+// into a handler the result is stored straight to `tohost`.
+//   tohost == 1                  pass
+//   tohost == (TESTNUM << 1) | 1 failed in test case TESTNUM
+
+#ifndef _ENV_PHYSICAL_SINGLE_CORE_H
+#define _ENV_PHYSICAL_SINGLE_CORE_H
+
+#define RVTEST_RV32U                                                    \
+  .macro init;                                                          \
+  .endm
+
+#define TESTNUM gp
+
+#define INIT_XREG                                                       \
+  li x1, 0;  li x2, 0;  li x3, 0;  li x4, 0;                            \
+  li x5, 0;  li x6, 0;  li x7, 0;  li x8, 0;                            \
+  li x9, 0;  li x10, 0; li x11, 0; li x12, 0;                           \
+  li x13, 0; li x14, 0; li x15, 0; li x16, 0;                           \
+  li x17, 0; li x18, 0; li x19, 0; li x20, 0;                           \
+  li x21, 0; li x22, 0; li x23, 0; li x24, 0;                           \
+  li x25, 0; li x26, 0; li x27, 0; li x28, 0;                           \
+  li x29, 0; li x30, 0; li x31, 0;
+
+#define RVTEST_CODE_BEGIN                                               \
+  .section .text.init;                                                  \
+  .align 6;                                                             \
+  .globl _start;                                                        \
+_start:                                                                 \
+  INIT_XREG;                                                            \
+  li TESTNUM, 0;                                                        \
+  init;
+
+#define RVTEST_CODE_END                                                 \
+  unimp
+
+#define RVTEST_PASS                                                     \
+  li TESTNUM, 1;                                                        \
+  sw TESTNUM, tohost, t5;                                               \
+1: j 1b;
+
+#define RVTEST_FAIL                                                     \
+1: beqz TESTNUM, 1b;                                                    \
+  sll TESTNUM, TESTNUM, 1;                                              \
+  or TESTNUM, TESTNUM, 1;                                               \
+  sw TESTNUM, tohost, t5;                                               \
+1: j 1b;
+
+#define EXTRA_DATA
+
+#define RVTEST_DATA_BEGIN                                               \
+  EXTRA_DATA                                                            \
+  .pushsection .tohost,"aw",@progbits;                                  \
+  .align 6; .global tohost; tohost: .word 0; .word 0;                   \
+  .align 6; .global fromhost; fromhost: .word 0; .word 0;               \
+  .popsection;                                                          \
+  .align 4; .global begin_signature; begin_signature:
+
+#define RVTEST_DATA_END .align 4; .global end_signature; end_signature:
+
+#endif
@@ -0,0 +1,52 @@
+// rv32i_m/I in the layout of riscv-arch-test, see tests/riscv-arch-test/gen.py
+mod common;
+use common::*;
+use rv_simulator::abi::*;
+
+macro_rules! arch_tests {
+    ($run:ident: $($name:ident => $test:literal),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                let pg = program(concat!("riscv-arch-test/bin/", $test, ".elf"));
+                let (tohost, model) = $run(&pg);
+                check_tohost($test, tohost);
+                let reference = std::fs::read_to_string(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/riscv-arch-test/rv32i_m/I/references/",
+                    $test,
+                    ".reference_output"
+                ))
+                .unwrap();
+                assert_eq!(signature(&pg, |addr| model.dmem.read(addr)), reference);
+            }
+        )*
+    };
+}
+
+macro_rules! rv32i {
+    ($run:ident) => {
+        arch_tests!($run:
+            add => "add-01", addi => "addi-01", and => "and-01", andi => "andi-01",
+            auipc => "auipc-01", beq => "beq-01", bge => "bge-01", bgeu => "bgeu-01",
+            blt => "blt-01", bltu => "bltu-01", bne => "bne-01", jal => "jal-01",
+            jalr => "jalr-01", lb => "lb-align-01", lbu => "lbu-align-01",
+            lh => "lh-align-01", lhu => "lhu-align-01", lui => "lui-01",
+            lw => "lw-align-01", or => "or-01", ori => "ori-01", sb => "sb-align-01",
+            sh => "sh-align-01", sll => "sll-01", slli => "slli-01", slt => "slt-01",
+            slti => "slti-01", sltiu => "sltiu-01", sltu => "sltu-01", sra => "sra-01",
+            srai => "srai-01", srl => "srl-01", srli => "srli-01", sub => "sub-01",
+            sw => "sw-align-01", xor => "xor-01", xori => "xori-01",
+        );
+    };
+}
+
+mod pipeline {
+    use super::*;
+    rv32i!(run_pipeline);
+}
+
+mod iss {
+    use super::*;
+    rv32i!(run_iss);
+}
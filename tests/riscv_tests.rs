@@ -0,0 +1,54 @@
+// rv32ui-p-* from riscv-tests, see tests/riscv-tests/build.sh
+mod common;
+use common::*;
+
+macro_rules! riscv_tests {
+    ($run:ident: $($name:ident),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                let name = concat!("rv32ui-p-", stringify!($name));
+                let pg = program(&format!("riscv-tests/bin/{}", name));
+                check_tohost(name, $run(&pg).0);
+            }
+        )*
+    };
+}
+
+mod pipeline {
+    use super::*;
+    riscv_tests!(run_pipeline:
+        add, addi, and, andi, auipc, beq, bge, bgeu, blt, bltu, bne, jal, jalr, lb, lbu, lh, lhu,
+        lui, lw, or, ori, sb, sh, simple, sll, slli, slt, slti, sltiu, sltu, sra, srai, srl, srli,
+        sub, sw, xor, xori,
+    );
+}
+
+// the branch and jump tests again under every predictor, mispredictions included
+mod predictors {
+    use super::*;
+    use rv_simulator::PREDICTORS;
+
+    #[test]
+    fn branches() {
+        for kind in PREDICTORS {
+            for name in [
+                "beq", "bge", "bgeu", "blt", "bltu", "bne", "jal", "jalr", "simple",
+            ] {
+                let name = format!("rv32ui-p-{}", name);
+                let mut pg = program(&format!("riscv-tests/bin/{}", name));
+                pg.predictor.kind = kind.to_string();
+                check_tohost(&format!("{} ({})", name, kind), run_pipeline(&pg).0);
+            }
+        }
+    }
+}
+
+mod iss {
+    use super::*;
+    riscv_tests!(run_iss:
+        add, addi, and, andi, auipc, beq, bge, bgeu, blt, bltu, bne, jal, jalr, lb, lbu, lh, lhu,
+        lui, lw, or, ori, sb, sh, simple, sll, slli, slt, slti, sltiu, sltu, sra, srai, srl, srli,
+        sub, sw, xor, xori,
+    );
+}